@@ -0,0 +1,195 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::constants::{mBTC, NUMBER_SATOSHI_IN_mBTC, BTC, NUMBER_SATOSHI_IN_BTC, SATOSHI};
+
+/// Currency of a bill. Bitcoin amounts are always kept in satoshi, so BTC, mBTC and sats
+/// only differ in how the amount is written. Fiat amounts are kept in hundredths.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Currency {
+    Bitcoin,
+    MilliBitcoin,
+    Satoshi,
+    Fiat(String),
+}
+
+impl Currency {
+    pub fn from_code(code: &str) -> Result<Self, AmountError> {
+        if code == BTC {
+            Ok(Currency::Bitcoin)
+        } else if code == mBTC {
+            Ok(Currency::MilliBitcoin)
+        } else if code == SATOSHI {
+            Ok(Currency::Satoshi)
+        } else if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) {
+            Ok(Currency::Fiat(code.to_string()))
+        } else {
+            Err(AmountError::UnknownCurrency(code.to_string()))
+        }
+    }
+
+    pub fn code(&self) -> String {
+        match self {
+            Currency::Bitcoin => BTC.to_string(),
+            Currency::MilliBitcoin => mBTC.to_string(),
+            Currency::Satoshi => SATOSHI.to_string(),
+            Currency::Fiat(code) => code.clone(),
+        }
+    }
+
+    /// Decimal places of the currency, relative to the unit amounts are kept in.
+    pub fn decimals(&self) -> u32 {
+        match self {
+            Currency::Bitcoin => NUMBER_SATOSHI_IN_BTC.ilog10(),
+            Currency::MilliBitcoin => NUMBER_SATOSHI_IN_mBTC.ilog10(),
+            Currency::Satoshi => 0,
+            Currency::Fiat(_) => 2,
+        }
+    }
+
+    pub fn is_bitcoin(&self) -> bool {
+        !matches!(self, Currency::Fiat(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Amount {
+    minor_units: u64,
+    currency: Currency,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AmountError {
+    Malformed(String),
+    TooPrecise { decimals: u32 },
+    Overflow,
+    UnknownCurrency(String),
+    IncompatibleCurrencies { from: String, to: String },
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountError::Malformed(text) => write!(f, "{:?} is not an amount", text),
+            AmountError::TooPrecise { decimals } => {
+                write!(f, "amount has more than {} decimal places", decimals)
+            }
+            AmountError::Overflow => write!(f, "amount is too large"),
+            AmountError::UnknownCurrency(code) => write!(f, "unknown currency {:?}", code),
+            AmountError::IncompatibleCurrencies { from, to } => {
+                write!(f, "cannot convert {} to {}", from, to)
+            }
+        }
+    }
+}
+
+impl Error for AmountError {}
+
+impl Amount {
+    /// `minor_units` are satoshi for bitcoin currencies and hundredths for fiat.
+    pub fn new(minor_units: u64, currency: Currency) -> Self {
+        Self {
+            minor_units,
+            currency,
+        }
+    }
+
+    /// Amount of a bill field, `amount_numbers` in the unit of `currency_code`.
+    pub fn from_code(minor_units: u64, currency_code: &str) -> Result<Self, AmountError> {
+        Ok(Self::new(minor_units, Currency::from_code(currency_code)?))
+    }
+
+    /// Parses a decimal amount like `1.5` written in `currency`.
+    pub fn parse(text: &str, currency: Currency) -> Result<Self, AmountError> {
+        let text = text.trim();
+        let malformed = || AmountError::Malformed(text.to_string());
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole.chars().all(|c| c.is_ascii_digit())
+            || !fraction.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(malformed());
+        }
+
+        let decimals = currency.decimals();
+        if fraction.len() > decimals as usize {
+            return Err(AmountError::TooPrecise { decimals });
+        }
+        let scale = 10u64.pow(decimals);
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| AmountError::Overflow)?
+        };
+        let fraction: u64 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<u64>().map_err(|_| malformed())?
+                * 10u64.pow(decimals - fraction.len() as u32)
+        };
+
+        let minor_units = whole
+            .checked_mul(scale)
+            .and_then(|minor_units| minor_units.checked_add(fraction))
+            .ok_or(AmountError::Overflow)?;
+        Ok(Self::new(minor_units, currency))
+    }
+
+    pub fn minor_units(&self) -> u64 {
+        self.minor_units
+    }
+
+    pub fn currency(&self) -> &Currency {
+        &self.currency
+    }
+
+    /// Amount to pay on chain, `None` for fiat bills.
+    pub fn to_sats(&self) -> Option<u64> {
+        self.convert(Currency::Satoshi)
+            .ok()
+            .map(|amount| amount.minor_units)
+    }
+
+    /// Writes the amount in another bitcoin unit. Fiat amounts can not be converted.
+    pub fn convert(&self, currency: Currency) -> Result<Self, AmountError> {
+        if self.currency == currency || self.currency.is_bitcoin() && currency.is_bitcoin() {
+            Ok(Self::new(self.minor_units, currency))
+        } else {
+            Err(AmountError::IncompatibleCurrencies {
+                from: self.currency.code(),
+                to: currency.code(),
+            })
+        }
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = self.currency.decimals();
+        let scale = 10u64.pow(decimals);
+        let whole = self.minor_units / scale;
+        let fraction = self.minor_units % scale;
+
+        match self.currency {
+            Currency::Fiat(_) => write!(
+                f,
+                "{}.{:0width$} {}",
+                whole,
+                fraction,
+                self.currency.code(),
+                width = decimals as usize
+            ),
+            _ if fraction == 0 => write!(f, "{} {}", whole, self.currency.code()),
+            _ => {
+                let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+                write!(
+                    f,
+                    "{}.{} {}",
+                    whole,
+                    fraction.trim_end_matches('0'),
+                    self.currency.code()
+                )
+            }
+        }
+    }
+}
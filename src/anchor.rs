@@ -0,0 +1,413 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use std::{env, fmt, fs, path};
+
+use bitcoin::absolute::LockTime;
+use bitcoin::script::PushBytesBuf;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{Address, OutPoint, PrivateKey, ScriptBuf, Sequence, TxIn, TxOut, Txid, Witness};
+use chrono::Utc;
+use log::{info, warn};
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{self, BackendError, Utxo};
+use crate::blockchain::Chain;
+use crate::constants::{
+    ANCHOR_FEE_RATE, ANCHOR_INTERVAL_ENV_VAR, BILLS_FOLDER_PATH, BILL_ANCHORS_FOLDER_PATH, USEDNET,
+};
+use crate::read_identity_from_file;
+
+/// Start of the OP_RETURN data of anchoring transactions, followed by the Merkle root.
+pub const ANCHOR_TAG: &[u8; 4] = b"BCA1";
+/// Outputs below this are not worth creating.
+pub const DUST_LIMIT: u64 = 294;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProofStep {
+    pub hash: String,
+    /// Whether `hash` is hashed on the left.
+    pub left: bool,
+}
+
+/// Commitment of a chain head in an anchoring transaction.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Anchor {
+    pub block_id: u64,
+    pub block_hash: String,
+    pub merkle_root: String,
+    /// Path from the leaf of the head to `merkle_root`.
+    pub proof: Vec<ProofStep>,
+    pub txid: String,
+    pub anchored_at: i64,
+    /// Time of the bitcoin block that confirmed `txid`, once it did.
+    #[serde(default)]
+    pub block_time: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AnchorVerification {
+    pub anchor: Anchor,
+    pub confirmed: bool,
+    pub block_time: Option<i64>,
+}
+
+#[derive(Debug)]
+pub enum AnchorError {
+    NothingToAnchor,
+    NotAnchored,
+    ProofMismatch,
+    NotInTransaction,
+    /// The address of the identity does not have enough confirmed coins.
+    InsufficientFunds(String),
+    Request(BackendError),
+}
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnchorError::NothingToAnchor => write!(f, "all chain heads are anchored"),
+            AnchorError::NotAnchored => write!(f, "block is not anchored"),
+            AnchorError::ProofMismatch => write!(f, "inclusion proof does not match the root"),
+            AnchorError::NotInTransaction => {
+                write!(f, "anchoring transaction does not commit to the root")
+            }
+            AnchorError::InsufficientFunds(address) => {
+                write!(f, "not enough confirmed coins on {} to anchor", address)
+            }
+            AnchorError::Request(error) => write!(f, "chain backend failed: {}", error),
+        }
+    }
+}
+
+impl Error for AnchorError {}
+
+impl From<BackendError> for AnchorError {
+    fn from(error: BackendError) -> Self {
+        AnchorError::Request(error)
+    }
+}
+
+/// Anchors the chain heads every [`ANCHOR_INTERVAL_ENV_VAR`] seconds, if it is set.
+pub fn start_anchoring_service() {
+    let interval = match env::var(ANCHOR_INTERVAL_ENV_VAR) {
+        Ok(seconds) => seconds
+            .parse::<u64>()
+            .expect("Anchor interval must be a number of seconds."),
+        Err(_) => return,
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            interval.tick().await;
+            match anchor_chain_heads().await {
+                Ok(txid) => info!("anchored chain heads in transaction {}", txid),
+                Err(AnchorError::NothingToAnchor) => {}
+                Err(error) => warn!("can not anchor chain heads: {}", error),
+            }
+            confirm_anchors().await;
+        }
+    });
+}
+
+/// Commits the Merkle root of the chain heads that are not anchored yet in an
+/// OP_RETURN and stores the inclusion proofs, returns the txid.
+pub async fn anchor_chain_heads() -> Result<String, AnchorError> {
+    let mut heads = Vec::new();
+    for file in fs::read_dir(BILLS_FOLDER_PATH).unwrap() {
+        let file_name = file.unwrap().file_name().into_string().unwrap();
+        let bill_name = path::Path::file_stem(path::Path::new(&file_name))
+            .expect("File name error")
+            .to_str()
+            .expect("File name error")
+            .to_string();
+        let head = Chain::read_chain_from_file(&bill_name)
+            .get_latest_block()
+            .clone();
+        let anchored = read_anchors(&bill_name)
+            .iter()
+            .any(|anchor| anchor.block_hash == head.hash);
+        if !anchored {
+            heads.push((bill_name, head));
+        }
+    }
+    if heads.is_empty() {
+        return Err(AnchorError::NothingToAnchor);
+    }
+
+    let leaves: Vec<[u8; 32]> = heads
+        .iter()
+        .map(|(bill_name, head)| leaf_hash(bill_name, &head.hash))
+        .collect();
+    let (root, proofs) = merkle_tree(&leaves);
+
+    let private_key = PrivateKey::from_wif(&read_identity_from_file().bitcoin_private_key).unwrap();
+    let address = anchoring_address(&private_key);
+    let utxos = api::backend().address_utxos(&address.to_string()).await?;
+    let transaction = anchoring_transaction(&utxos, &root, &private_key, ANCHOR_FEE_RATE)?;
+    let txid = api::backend()
+        .broadcast(bitcoin::consensus::encode::serialize_hex(&transaction))
+        .await?;
+
+    let anchored_at = Utc::now().timestamp();
+    for ((bill_name, head), proof) in heads.into_iter().zip(proofs) {
+        let mut anchors = read_anchors(&bill_name);
+        anchors.push(Anchor {
+            block_id: head.id,
+            block_hash: head.hash,
+            merkle_root: hex::encode(root),
+            proof,
+            txid: txid.clone(),
+            anchored_at,
+            block_time: None,
+        });
+        write_anchors(&bill_name, &anchors);
+    }
+    Ok(txid)
+}
+
+/// Records the block time of anchoring transactions that confirmed since the last pass.
+async fn confirm_anchors() {
+    for file in fs::read_dir(BILL_ANCHORS_FOLDER_PATH).unwrap() {
+        let file_name = file.unwrap().file_name().into_string().unwrap();
+        let Some(bill_name) = file_name.strip_suffix(".json") else {
+            continue;
+        };
+        let mut anchors = read_anchors(bill_name);
+        let mut confirmed = false;
+        for anchor in anchors
+            .iter_mut()
+            .filter(|anchor| anchor.block_time.is_none())
+        {
+            match api::backend().transaction(&anchor.txid).await {
+                Ok(transaction) => {
+                    anchor.block_time = transaction.status.block_time;
+                    confirmed |= anchor.block_time.is_some();
+                }
+                Err(error) => warn!("can not look up anchor {}: {}", anchor.txid, error),
+            }
+        }
+        if confirmed {
+            write_anchors(bill_name, &anchors);
+        }
+    }
+}
+
+/// Time the earliest confirmed anchor of the chain head `block_hash` of the bill attests.
+pub fn anchored_time(bill_name: &str, block_hash: &str) -> Option<i64> {
+    read_anchors(bill_name)
+        .iter()
+        .filter(|anchor| anchor.block_hash == block_hash)
+        .filter_map(|anchor| anchor.block_time)
+        .min()
+}
+
+/// Checks that block `block_id` leads to an anchored head and that the anchoring
+/// transaction commits to it.
+pub async fn verify_anchor(
+    bill_name: &String,
+    block_id: u64,
+) -> Result<AnchorVerification, AnchorError> {
+    let chain = Chain::read_chain_from_file(bill_name);
+    let anchor = read_anchors(bill_name)
+        .into_iter()
+        .filter(|anchor| anchor.block_id >= block_id)
+        .find(|anchor| leads_to(&chain, block_id, anchor))
+        .ok_or(AnchorError::NotAnchored)?;
+
+    let root = root_from_proof(leaf_hash(bill_name, &anchor.block_hash), &anchor.proof);
+    if hex::encode(root) != anchor.merkle_root {
+        return Err(AnchorError::ProofMismatch);
+    }
+    let transaction = api::backend().transaction(&anchor.txid).await?;
+    let script = op_return_script(&root).to_hex_string();
+    if !transaction
+        .vout
+        .iter()
+        .any(|output| output.scriptpubkey == script)
+    {
+        return Err(AnchorError::NotInTransaction);
+    }
+    Ok(AnchorVerification {
+        anchor,
+        confirmed: transaction.status.confirmed,
+        block_time: transaction.status.block_time,
+    })
+}
+
+/// Whether the blocks from `block_id` up to the anchored head are linked by their hashes.
+fn leads_to(chain: &Chain, block_id: u64, anchor: &Anchor) -> bool {
+    let blocks: Vec<_> = chain
+        .blocks
+        .iter()
+        .filter(|block| block.id >= block_id && block.id <= anchor.block_id)
+        .collect();
+    blocks.first().map(|block| block.id) == Some(block_id)
+        && blocks.last().map(|block| &block.hash) == Some(&anchor.block_hash)
+        && blocks
+            .windows(2)
+            .all(|pair| pair[1].previous_hash == pair[0].hash)
+}
+
+pub fn read_anchors(bill_name: &str) -> Vec<Anchor> {
+    let path = BILL_ANCHORS_FOLDER_PATH.to_string() + "/" + bill_name + ".json";
+    if !Path::new(&path).exists() {
+        return Vec::new();
+    }
+    let anchors = fs::read(path).expect("file not found");
+    serde_json::from_slice(&anchors).unwrap()
+}
+
+fn write_anchors(bill_name: &str, anchors: &[Anchor]) {
+    let path = BILL_ANCHORS_FOLDER_PATH.to_string() + "/" + bill_name + ".json";
+    fs::write(path, serde_json::to_string_pretty(anchors).unwrap()).unwrap();
+}
+
+/// Address the anchoring transactions are paid from and their change goes to.
+pub fn anchoring_address(private_key: &PrivateKey) -> Address {
+    let public_key = private_key.public_key(&Secp256k1::new());
+    Address::p2wpkh(&public_key, USEDNET).unwrap()
+}
+
+pub fn leaf_hash(bill_name: &str, block_hash: &str) -> [u8; 32] {
+    sha256(format!("{}:{}", bill_name, block_hash).as_bytes())
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    sha256(&[&left[..], &right[..]].concat())
+}
+
+/// Root over `leaves` and the proof of each leaf. A node without a sibling moves up unchanged.
+pub fn merkle_tree(leaves: &[[u8; 32]]) -> ([u8; 32], Vec<Vec<ProofStep>>) {
+    let mut proofs = vec![Vec::new(); leaves.len()];
+    // Leaves below each node of the current level.
+    let mut level: Vec<([u8; 32], Vec<usize>)> = leaves
+        .iter()
+        .enumerate()
+        .map(|(index, leaf)| (*leaf, vec![index]))
+        .collect();
+    while level.len() > 1 {
+        let mut next_level = Vec::new();
+        for pair in level.chunks(2) {
+            if let [(left, left_leaves), (right, right_leaves)] = pair {
+                for &leaf in left_leaves {
+                    proofs[leaf].push(ProofStep {
+                        hash: hex::encode(right),
+                        left: false,
+                    });
+                }
+                for &leaf in right_leaves {
+                    proofs[leaf].push(ProofStep {
+                        hash: hex::encode(left),
+                        left: true,
+                    });
+                }
+                let leaves = [&left_leaves[..], &right_leaves[..]].concat();
+                next_level.push((node_hash(left, right), leaves));
+            } else {
+                next_level.push(pair[0].clone());
+            }
+        }
+        level = next_level;
+    }
+    (level[0].0, proofs)
+}
+
+pub fn root_from_proof(leaf: [u8; 32], proof: &[ProofStep]) -> [u8; 32] {
+    proof.iter().fold(leaf, |hash, step| {
+        let mut sibling = [0u8; 32];
+        hex::decode_to_slice(&step.hash, &mut sibling).unwrap_or_default();
+        if step.left {
+            node_hash(&sibling, &hash)
+        } else {
+            node_hash(&hash, &sibling)
+        }
+    })
+}
+
+fn op_return_script(root: &[u8; 32]) -> ScriptBuf {
+    let data = PushBytesBuf::try_from([&ANCHOR_TAG[..], &root[..]].concat()).unwrap();
+    ScriptBuf::new_op_return(&data)
+}
+
+/// Signed transaction with the OP_RETURN of `root`, spending confirmed `utxos` of
+/// [`anchoring_address`] and returning the change to it.
+pub fn anchoring_transaction(
+    utxos: &[Utxo],
+    root: &[u8; 32],
+    private_key: &PrivateKey,
+    fee_rate: u64,
+) -> Result<bitcoin::Transaction, AnchorError> {
+    let address = anchoring_address(private_key);
+    let mut utxos: Vec<&Utxo> = utxos.iter().filter(|utxo| utxo.status.confirmed).collect();
+    utxos.sort_by_key(|utxo| Reverse(utxo.value));
+
+    // Virtual sizes of the transaction overhead, a P2WPKH input and the outputs.
+    let fee = |inputs: u64| fee_rate * (11 + 68 * inputs + 47 + 31);
+    let mut inputs = Vec::new();
+    let mut total = 0;
+    for utxo in utxos {
+        if total >= fee(inputs.len() as u64) + DUST_LIMIT {
+            break;
+        }
+        total += utxo.value;
+        inputs.push(utxo);
+    }
+    let fee = fee(inputs.len() as u64);
+    if total < fee + DUST_LIMIT {
+        return Err(AnchorError::InsufficientFunds(address.to_string()));
+    }
+
+    let mut transaction = bitcoin::Transaction {
+        version: 2,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::from_str(&utxo.txid).unwrap(),
+                    vout: utxo.vout,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![
+            TxOut {
+                value: 0,
+                script_pubkey: op_return_script(root),
+            },
+            TxOut {
+                value: total - fee,
+                script_pubkey: address.script_pubkey(),
+            },
+        ],
+    };
+
+    let secp = Secp256k1::new();
+    let public_key = private_key.public_key(&secp);
+    let script_code = address.script_pubkey().p2wpkh_script_code().unwrap();
+    let mut witnesses = Vec::new();
+    let mut sighash_cache = SighashCache::new(&transaction);
+    for (index, utxo) in inputs.iter().enumerate() {
+        let sighash = sighash_cache
+            .segwit_signature_hash(index, &script_code, utxo.value, EcdsaSighashType::All)
+            .unwrap();
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        let signature =
+            bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa(&message, &private_key.inner));
+        witnesses.push(Witness::from_slice(&[
+            signature.to_vec(),
+            public_key.to_bytes(),
+        ]));
+    }
+    for (input, witness) in transaction.input.iter_mut().zip(witnesses) {
+        input.witness = witness;
+    }
+    Ok(transaction)
+}
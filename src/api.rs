@@ -1,6 +1,30 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use std::{env, fmt, io};
+
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::{Address, Network, ScriptBuf};
+use log::warn;
+use openssl::sha::sha256;
+use openssl::ssl::{SslConnector, SslMethod};
 use serde_derive::Deserialize;
+use serde_json::{json, Value};
 
-#[derive(Deserialize, Debug)]
+use crate::constants::{
+    BITCOIND_PASSWORD_ENV_VAR, BITCOIND_URL_ENV_VAR, BITCOIND_USER_ENV_VAR,
+    BITCOIND_WALLET_ENV_VAR, CHAIN_BACKEND_ENV_VAR, ELECTRUM_URL_ENV_VAR, ESPLORA_URL_ENV_VAR,
+    USEDNET,
+};
+
+#[derive(Deserialize, Debug, Default)]
 pub struct ChainStats {
     pub funded_txo_count: u64,
     pub funded_txo_sum: u64,
@@ -9,7 +33,7 @@ pub struct ChainStats {
     pub tx_count: u64,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub struct MempoolStats {
     pub funded_txo_count: u64,
     pub funded_txo_sum: u64,
@@ -26,33 +50,872 @@ pub struct AddressInfo {
 }
 
 impl AddressInfo {
-    pub async fn get_testnet_address_info(address: String) -> Self {
-        let request_url = format!(
-            "https://blockstream.info/testnet/api/address/{address}",
-            address = address
-        );
-        let address: AddressInfo = reqwest::get(&request_url)
-            .await
-            .expect("Failed to send request")
+    /// Stats of `address` from its transactions and the outputs it did not spend yet.
+    pub fn from_transactions(address: &str, transactions: &[Transaction], utxos: &[Utxo]) -> Self {
+        let mut chain_stats = ChainStats::default();
+        let mut mempool_stats = MempoolStats::default();
+        for transaction in transactions {
+            let outputs = transaction
+                .vout
+                .iter()
+                .filter(|output| output.scriptpubkey_address.as_deref() == Some(address));
+            let (count, sum) = outputs.fold((0, 0), |(count, sum), output| {
+                (count + 1, sum + output.value)
+            });
+            if transaction.status.confirmed {
+                chain_stats.tx_count += 1;
+                chain_stats.funded_txo_count += count;
+                chain_stats.funded_txo_sum += sum;
+            } else {
+                mempool_stats.tx_count += 1;
+                mempool_stats.funded_txo_count += count;
+                mempool_stats.funded_txo_sum += sum;
+            }
+        }
+        let (confirmed, unconfirmed): (Vec<&Utxo>, Vec<&Utxo>) =
+            utxos.iter().partition(|utxo| utxo.status.confirmed);
+        let unspent_sum = |utxos: &[&Utxo]| utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+        chain_stats.spent_txo_count = chain_stats
+            .funded_txo_count
+            .saturating_sub(confirmed.len() as u64);
+        chain_stats.spent_txo_sum = chain_stats
+            .funded_txo_sum
+            .saturating_sub(unspent_sum(&confirmed));
+        mempool_stats.spent_txo_count = mempool_stats
+            .funded_txo_count
+            .saturating_sub(unconfirmed.len() as u64);
+        mempool_stats.spent_txo_sum = mempool_stats
+            .funded_txo_sum
+            .saturating_sub(unspent_sum(&unconfirmed));
+
+        Self {
+            address: address.to_string(),
+            chain_stats,
+            mempool_stats,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionStatus {
+    pub confirmed: bool,
+    #[serde(default)]
+    pub block_height: Option<u64>,
+    pub block_time: Option<i64>,
+}
+
+impl TransactionStatus {
+    /// Blocks on top of the one the transaction is in, including that one.
+    pub fn confirmations(&self, tip_height: u64) -> u64 {
+        match self.block_height {
+            Some(block_height) if self.confirmed && tip_height >= block_height => {
+                tip_height - block_height + 1
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionOutput {
+    /// Hex of the output script.
+    #[serde(default)]
+    pub scriptpubkey: String,
+    pub scriptpubkey_address: Option<String>,
+    pub value: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Transaction {
+    pub txid: String,
+    pub status: TransactionStatus,
+    pub vout: Vec<TransactionOutput>,
+}
+
+impl Transaction {
+    /// Sum of the outputs of this transaction that pay to `address`.
+    pub fn received_by(&self, address: &str) -> u64 {
+        self.vout
+            .iter()
+            .filter(|output| output.scriptpubkey_address.as_deref() == Some(address))
+            .map(|output| output.value)
+            .sum()
+    }
+
+    fn from_raw(transaction: &bitcoin::Transaction, status: TransactionStatus) -> Self {
+        Self {
+            txid: transaction.txid().to_string(),
+            status,
+            vout: transaction
+                .output
+                .iter()
+                .map(|output| TransactionOutput {
+                    scriptpubkey: output.script_pubkey.to_hex_string(),
+                    scriptpubkey_address: Address::from_script(&output.script_pubkey, USEDNET)
+                        .ok()
+                        .map(|address| address.to_string()),
+                    value: output.value,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+    pub status: UtxoStatus,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UtxoStatus {
+    pub confirmed: bool,
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    Request(String),
+    Rpc(String),
+    Malformed(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Request(error) => write!(f, "request failed: {}", error),
+            BackendError::Rpc(error) => write!(f, "node returned an error: {}", error),
+            BackendError::Malformed(error) => write!(f, "response is malformed: {}", error),
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+impl From<reqwest::Error> for BackendError {
+    fn from(error: reqwest::Error) -> Self {
+        BackendError::Request(error.to_string())
+    }
+}
+
+impl From<io::Error> for BackendError {
+    fn from(error: io::Error) -> Self {
+        BackendError::Request(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BackendError {
+    fn from(error: serde_json::Error) -> Self {
+        BackendError::Malformed(error.to_string())
+    }
+}
+
+impl From<bitcoin::consensus::encode::Error> for BackendError {
+    fn from(error: bitcoin::consensus::encode::Error) -> Self {
+        BackendError::Malformed(error.to_string())
+    }
+}
+
+/// Source of the addresses and transactions on the bitcoin network.
+#[async_trait]
+pub trait ChainBackend: Send + Sync {
+    /// Transactions paying to `address`, newest first.
+    async fn address_transactions(&self, address: &str) -> Result<Vec<Transaction>, BackendError>;
+
+    async fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, BackendError>;
+
+    async fn transaction(&self, txid: &str) -> Result<Transaction, BackendError>;
+
+    /// The whole transaction, as needed to spend its outputs.
+    async fn raw_transaction(&self, txid: &str) -> Result<bitcoin::Transaction, BackendError>;
+
+    /// Broadcasts the hex of a signed transaction and returns its txid.
+    async fn broadcast(&self, transaction_hex: String) -> Result<String, BackendError>;
+
+    async fn genesis_hash(&self) -> Result<String, BackendError>;
+
+    /// Height of the last block.
+    async fn tip_height(&self) -> Result<u64, BackendError>;
+
+    /// Fee rate in sat/vB to be confirmed within `target` blocks, at least 1.
+    async fn fee_rate(&self, target: u16) -> Result<u64, BackendError>;
+
+    async fn address_info(&self, address: &str) -> Result<AddressInfo, BackendError> {
+        let transactions = self.address_transactions(address).await?;
+        let utxos = self.address_utxos(address).await?;
+        Ok(AddressInfo::from_transactions(
+            address,
+            &transactions,
+            &utxos,
+        ))
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn ChainBackend>> = OnceLock::new();
+
+/// Backend in [`CHAIN_BACKEND_ENV_VAR`], `esplora`, `electrum` or `bitcoind`. Bitcoind on
+/// regtest and Esplora otherwise by default, at the default URLs of [`USEDNET`]. An unknown
+/// backend is reported and the default one is used instead.
+pub fn backend() -> &'static dyn ChainBackend {
+    BACKEND
+        .get_or_init(|| {
+            let kind = env::var(CHAIN_BACKEND_ENV_VAR)
+                .unwrap_or_else(|_| default_backend_kind(USEDNET).to_string());
+            chain_backend(&kind).unwrap_or_else(|| {
+                let default_kind = default_backend_kind(USEDNET);
+                warn!("unknown chain backend {:?}, using {}", kind, default_kind);
+                chain_backend(default_kind).unwrap()
+            })
+        })
+        .as_ref()
+}
+
+pub fn default_backend_kind(network: Network) -> &'static str {
+    if network == Network::Regtest {
+        "bitcoind"
+    } else {
+        "esplora"
+    }
+}
+
+/// Backend of the `kind` named in [`CHAIN_BACKEND_ENV_VAR`], configured from the environment.
+pub fn chain_backend(kind: &str) -> Option<Box<dyn ChainBackend>> {
+    let setting = |name: &str, default: String| env::var(name).unwrap_or(default);
+    match kind {
+        "esplora" => Some(Box::new(EsploraBackend {
+            url: setting(ESPLORA_URL_ENV_VAR, default_esplora_url(USEDNET)),
+        })),
+        "electrum" => Some(Box::new(ElectrumBackend {
+            url: setting(ELECTRUM_URL_ENV_VAR, default_electrum_url(USEDNET)),
+        })),
+        "bitcoind" => Some(Box::new(BitcoindBackend::new(
+            setting(BITCOIND_URL_ENV_VAR, default_bitcoind_url(USEDNET)),
+            setting(BITCOIND_USER_ENV_VAR, String::new()),
+            setting(BITCOIND_PASSWORD_ENV_VAR, String::new()),
+            setting(BITCOIND_WALLET_ENV_VAR, "bitcredit".to_string()),
+        ))),
+        _ => None,
+    }
+}
+
+/// Warns when the backend follows another network than [`USEDNET`].
+pub async fn check_backend_network() {
+    let expected = genesis_block(USEDNET).block_hash().to_string();
+    match backend().genesis_hash().await {
+        Ok(genesis_hash) if genesis_hash == expected => {}
+        Ok(_) => warn!("chain backend is not on the {} network", USEDNET),
+        Err(error) => warn!("can not reach the chain backend: {}", error),
+    }
+}
+
+pub fn default_esplora_url(network: Network) -> String {
+    match network {
+        Network::Bitcoin => "https://blockstream.info/api",
+        Network::Testnet => "https://blockstream.info/testnet/api",
+        Network::Signet => "https://mempool.space/signet/api",
+        _ => "http://127.0.0.1:3002",
+    }
+    .to_string()
+}
+
+pub fn default_electrum_url(network: Network) -> String {
+    match network {
+        Network::Bitcoin => "ssl://electrum.blockstream.info:50002",
+        Network::Testnet => "ssl://electrum.blockstream.info:60002",
+        Network::Signet => "ssl://mempool.space:60602",
+        _ => "tcp://127.0.0.1:60401",
+    }
+    .to_string()
+}
+
+pub fn default_bitcoind_url(network: Network) -> String {
+    match network {
+        Network::Bitcoin => "http://127.0.0.1:8332",
+        Network::Testnet => "http://127.0.0.1:18332",
+        Network::Signet => "http://127.0.0.1:38332",
+        _ => "http://127.0.0.1:18443",
+    }
+    .to_string()
+}
+
+fn address_script(address: &str) -> Result<ScriptBuf, BackendError> {
+    let address = Address::from_str(address).map_err(|e| BackendError::Malformed(e.to_string()))?;
+    Ok(address.assume_checked().script_pubkey())
+}
+
+/// Rounds a fee rate in BTC/kvB up to sat/vB, unknown rates give the minimum.
+fn fee_rate_from_btc_per_kvb(fee_rate: Option<f64>) -> u64 {
+    fee_rate
+        .filter(|fee_rate| *fee_rate > 0.0)
+        .map_or(1, |fee_rate| ((fee_rate * 100_000.0).ceil() as u64).max(1))
+}
+
+/// Height of the block a transaction with `confirmations` is in. A tip that moved since the
+/// confirmations were counted can put it above the tip, the height then stops at 0.
+fn block_height(tip_height: u64, confirmations: u64) -> u64 {
+    tip_height.saturating_add(1).saturating_sub(confirmations)
+}
+
+fn decode_transaction(hex: &str) -> Result<bitcoin::Transaction, BackendError> {
+    let bytes = hex::decode(hex).map_err(|e| BackendError::Malformed(e.to_string()))?;
+    Ok(deserialize(&bytes)?)
+}
+
+/// Confirmed transactions Esplora lists per page of the transactions of an address.
+const ESPLORA_CHAIN_PAGE_LENGTH: usize = 25;
+
+/// Esplora HTTP API, as run by blockstream.info and mempool.space.
+pub struct EsploraBackend {
+    pub url: String,
+}
+
+impl EsploraBackend {
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, BackendError> {
+        let request_url = format!("{}{}", self.url, path);
+        Ok(reqwest::get(&request_url)
+            .await?
+            .error_for_status()?
             .json()
+            .await?)
+    }
+}
+
+#[async_trait]
+impl ChainBackend for EsploraBackend {
+    async fn address_transactions(&self, address: &str) -> Result<Vec<Transaction>, BackendError> {
+        // The first page has the mempool and the newest confirmed transactions, the older
+        // ones come in pages after the last confirmed transaction seen.
+        let mut transactions: Vec<Transaction> =
+            self.get(&format!("/address/{}/txs", address)).await?;
+        let mut page_length = transactions.iter().filter(|tx| tx.status.confirmed).count();
+        while page_length >= ESPLORA_CHAIN_PAGE_LENGTH {
+            let last_txid = match transactions.iter().rev().find(|tx| tx.status.confirmed) {
+                Some(transaction) => transaction.txid.clone(),
+                None => break,
+            };
+            let page: Vec<Transaction> = self
+                .get(&format!("/address/{}/txs/chain/{}", address, last_txid))
+                .await?;
+            page_length = page.len();
+            transactions.extend(page);
+        }
+        Ok(transactions)
+    }
+
+    async fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, BackendError> {
+        self.get(&format!("/address/{}/utxo", address)).await
+    }
+
+    async fn transaction(&self, txid: &str) -> Result<Transaction, BackendError> {
+        self.get(&format!("/tx/{}", txid)).await
+    }
+
+    async fn raw_transaction(&self, txid: &str) -> Result<bitcoin::Transaction, BackendError> {
+        let request_url = format!("{}/tx/{}/hex", self.url, txid);
+        decode_transaction(
+            &reqwest::get(&request_url)
+                .await?
+                .error_for_status()?
+                .text()
+                .await?,
+        )
+    }
+
+    async fn broadcast(&self, transaction_hex: String) -> Result<String, BackendError> {
+        let request_url = format!("{}/tx", self.url);
+        Ok(reqwest::Client::new()
+            .post(&request_url)
+            .body(transaction_hex)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+
+    async fn genesis_hash(&self) -> Result<String, BackendError> {
+        let request_url = format!("{}/block-height/0", self.url);
+        Ok(reqwest::get(&request_url)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+
+    async fn tip_height(&self) -> Result<u64, BackendError> {
+        self.get("/blocks/tip/height").await
+    }
+
+    async fn fee_rate(&self, target: u16) -> Result<u64, BackendError> {
+        // Estimates in sat/vB by confirmation target, only some targets are listed.
+        let estimates: HashMap<String, f64> = self.get("/fee-estimates").await?;
+        let fee_rate = estimates
+            .iter()
+            .filter_map(|(blocks, fee_rate)| Some((blocks.parse::<u16>().ok()?, *fee_rate)))
+            .filter(|(blocks, _)| *blocks <= target)
+            .max_by_key(|(blocks, _)| *blocks)
+            .map(|(_, fee_rate)| fee_rate);
+        Ok(fee_rate.map_or(1, |fee_rate| (fee_rate.ceil() as u64).max(1)))
+    }
+
+    async fn address_info(&self, address: &str) -> Result<AddressInfo, BackendError> {
+        self.get(&format!("/address/{}", address)).await
+    }
+}
+
+/// Electrum server at `tcp://host:port` or `ssl://host:port`.
+pub struct ElectrumBackend {
+    pub url: String,
+}
+
+trait ElectrumStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> ElectrumStream for T {}
+
+struct ElectrumConnection {
+    stream: BufReader<Box<dyn ElectrumStream>>,
+    next_id: u64,
+}
+
+impl ElectrumConnection {
+    fn open(url: &str) -> Result<Self, BackendError> {
+        let (tls, host_and_port) = match url.split_once("://") {
+            Some(("ssl", host_and_port)) => (true, host_and_port),
+            Some(("tcp", host_and_port)) => (false, host_and_port),
+            None => (false, url),
+            Some(_) => return Err(BackendError::Request(format!("unknown scheme in {}", url))),
+        };
+        let tcp = TcpStream::connect(host_and_port)?;
+        tcp.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let stream: Box<dyn ElectrumStream> = if tls {
+            let host = host_and_port
+                .rsplit_once(':')
+                .map_or(host_and_port, |(host, _)| host);
+            let connector = SslConnector::builder(SslMethod::tls())
+                .map_err(|e| BackendError::Request(e.to_string()))?
+                .build();
+            Box::new(
+                connector
+                    .connect(host, tcp)
+                    .map_err(|e| BackendError::Request(e.to_string()))?,
+            )
+        } else {
+            Box::new(tcp)
+        };
+        Ok(Self {
+            stream: BufReader::new(stream),
+            next_id: 0,
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, BackendError> {
+        self.next_id += 1;
+        let request =
+            json!({"jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params});
+        let stream = self.stream.get_mut();
+        writeln!(stream, "{}", request)?;
+        stream.flush()?;
+        // Skip notifications of subscriptions until the response to this request.
+        let mut response: Value = loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(BackendError::Request("connection closed".to_string()));
+            }
+            let response: Value = serde_json::from_str(&line)?;
+            if response["id"] == self.next_id {
+                break response;
+            }
+        };
+        match response.get("error") {
+            Some(error) if !error.is_null() => Err(BackendError::Rpc(error.to_string())),
+            _ => Ok(response["result"].take()),
+        }
+    }
+
+    fn call_str(&mut self, method: &str, params: Value) -> Result<String, BackendError> {
+        match self.call(method, params)? {
+            Value::String(result) => Ok(result),
+            result => Err(BackendError::Malformed(result.to_string())),
+        }
+    }
+
+    fn header(&mut self, height: i64) -> Result<Header, BackendError> {
+        let header = self.call_str("blockchain.block.header", json!([height]))?;
+        Ok(deserialize(
+            &hex::decode(header).map_err(|e| BackendError::Malformed(e.to_string()))?,
+        )?)
+    }
+
+    /// Transaction `txid`, confirmed at `height` if it is above 0.
+    fn transaction(&mut self, txid: &str, height: i64) -> Result<Transaction, BackendError> {
+        let raw = decode_transaction(&self.call_str("blockchain.transaction.get", json!([txid]))?)?;
+        let block_time = if height > 0 {
+            Some(self.header(height)?.time as i64)
+        } else {
+            None
+        };
+        Ok(Transaction::from_raw(
+            &raw,
+            TransactionStatus {
+                confirmed: height > 0,
+                block_height: u64::try_from(height).ok().filter(|&height| height > 0),
+                block_time,
+            },
+        ))
+    }
+
+    fn tip_height(&mut self) -> Result<u64, BackendError> {
+        let tip = self.call("blockchain.headers.subscribe", json!([]))?;
+        tip["height"]
+            .as_u64()
+            .ok_or_else(|| BackendError::Malformed(tip.to_string()))
+    }
+}
+
+/// Key of `address` in the Electrum protocol.
+fn script_hash(address: &str) -> Result<String, BackendError> {
+    let mut hash = sha256(address_script(address)?.as_bytes());
+    hash.reverse();
+    Ok(hex::encode(hash))
+}
+
+#[derive(Deserialize)]
+struct ElectrumHistoryItem {
+    tx_hash: String,
+    height: i64,
+}
+
+#[derive(Deserialize)]
+struct ElectrumUnspent {
+    tx_hash: String,
+    tx_pos: u32,
+    value: u64,
+    height: i64,
+}
+
+impl ElectrumBackend {
+    async fn with_connection<T, F>(&self, f: F) -> Result<T, BackendError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut ElectrumConnection) -> Result<T, BackendError> + Send + 'static,
+    {
+        let url = self.url.clone();
+        tokio::task::spawn_blocking(move || f(&mut ElectrumConnection::open(&url)?))
             .await
-            .expect("Failed to read response");
+            .map_err(|e| BackendError::Request(e.to_string()))?
+    }
+}
 
-        address
+#[async_trait]
+impl ChainBackend for ElectrumBackend {
+    async fn address_transactions(&self, address: &str) -> Result<Vec<Transaction>, BackendError> {
+        let script_hash = script_hash(address)?;
+        self.with_connection(move |connection| {
+            let history: Vec<ElectrumHistoryItem> = serde_json::from_value(
+                connection.call("blockchain.scripthash.get_history", json!([script_hash]))?,
+            )?;
+            history
+                .iter()
+                .rev()
+                .map(|item| connection.transaction(&item.tx_hash, item.height))
+                .collect()
+        })
+        .await
     }
 
-    async fn get_mainnet_address_info(address: String) -> Self {
-        let request_url = format!(
-            "https://blockstream.info/api/address/{address}",
-            address = address
-        );
-        let address: AddressInfo = reqwest::get(&request_url)
+    async fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, BackendError> {
+        let script_hash = script_hash(address)?;
+        self.with_connection(move |connection| {
+            let unspent: Vec<ElectrumUnspent> = serde_json::from_value(
+                connection.call("blockchain.scripthash.listunspent", json!([script_hash]))?,
+            )?;
+            Ok(unspent
+                .into_iter()
+                .map(|unspent| Utxo {
+                    txid: unspent.tx_hash,
+                    vout: unspent.tx_pos,
+                    value: unspent.value,
+                    status: UtxoStatus {
+                        confirmed: unspent.height > 0,
+                    },
+                })
+                .collect())
+        })
+        .await
+    }
+
+    async fn transaction(&self, txid: &str) -> Result<Transaction, BackendError> {
+        let txid = txid.to_string();
+        self.with_connection(move |connection| {
+            // Only the verbose form tells if it is confirmed, not every server supports it.
+            let verbose = connection
+                .call("blockchain.transaction.get", json!([txid, true]))
+                .unwrap_or(Value::Null);
+            let confirmations = verbose["confirmations"].as_u64().unwrap_or(0);
+            let mut transaction = connection.transaction(&txid, 0)?;
+            let block_height = if confirmations > 0 {
+                Some(block_height(connection.tip_height()?, confirmations))
+            } else {
+                None
+            };
+            transaction.status = TransactionStatus {
+                confirmed: confirmations > 0,
+                block_height,
+                block_time: verbose["blocktime"].as_i64(),
+            };
+            Ok(transaction)
+        })
+        .await
+    }
+
+    async fn broadcast(&self, transaction_hex: String) -> Result<String, BackendError> {
+        self.with_connection(move |connection| {
+            connection.call_str("blockchain.transaction.broadcast", json!([transaction_hex]))
+        })
+        .await
+    }
+
+    async fn genesis_hash(&self) -> Result<String, BackendError> {
+        self.with_connection(|connection| Ok(connection.header(0)?.block_hash().to_string()))
+            .await
+    }
+
+    async fn tip_height(&self) -> Result<u64, BackendError> {
+        self.with_connection(|connection| connection.tip_height())
             .await
-            .expect("Failed to send request")
+    }
+
+    async fn raw_transaction(&self, txid: &str) -> Result<bitcoin::Transaction, BackendError> {
+        let txid = txid.to_string();
+        self.with_connection(move |connection| {
+            decode_transaction(&connection.call_str("blockchain.transaction.get", json!([txid]))?)
+        })
+        .await
+    }
+
+    async fn fee_rate(&self, target: u16) -> Result<u64, BackendError> {
+        self.with_connection(move |connection| {
+            let fee_rate = connection.call("blockchain.estimatefee", json!([target]))?;
+            Ok(fee_rate_from_btc_per_kvb(fee_rate.as_f64()))
+        })
+        .await
+    }
+}
+
+/// JSON-RPC of a bitcoind node. Addresses are watched in the descriptor wallet `wallet`,
+/// which is created without private keys if it doesn't exist.
+pub struct BitcoindBackend {
+    url: String,
+    user: String,
+    password: String,
+    wallet: String,
+    watched: Mutex<HashSet<String>>,
+}
+
+impl BitcoindBackend {
+    pub fn new(url: String, user: String, password: String, wallet: String) -> Self {
+        Self {
+            url,
+            user,
+            password,
+            wallet,
+            watched: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, BackendError> {
+        self.call_url(&self.url, method, params).await
+    }
+
+    pub async fn wallet_call(&self, method: &str, params: Value) -> Result<Value, BackendError> {
+        let url = format!("{}/wallet/{}", self.url, self.wallet);
+        self.call_url(&url, method, params).await
+    }
+
+    async fn call_url(
+        &self,
+        url: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, BackendError> {
+        let request =
+            json!({"jsonrpc": "1.0", "id": "bitcredit", "method": method, "params": params});
+        // Errors come with a status other than 200 but still in the body.
+        let mut response: Value = reqwest::Client::new()
+            .post(url)
+            .basic_auth(&self.user, Some(&self.password))
+            .json(&request)
+            .send()
+            .await?
             .json()
+            .await?;
+        match response.get("error") {
+            Some(error) if !error.is_null() => Err(BackendError::Rpc(error.to_string())),
+            _ => Ok(response["result"].take()),
+        }
+    }
+
+    async fn ensure_wallet(&self) -> Result<(), BackendError> {
+        let wallets = self.call("listwallets", json!([])).await?;
+        if wallets
+            .as_array()
+            .is_some_and(|wallets| wallets.iter().any(|wallet| wallet == &self.wallet.as_str()))
+        {
+            return Ok(());
+        }
+        if self.call("loadwallet", json!([self.wallet])).await.is_ok() {
+            return Ok(());
+        }
+        self.call(
+            "createwallet",
+            json!([self.wallet, true, true, "", false, true]),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Imports `address` into the wallet and rescans for its past transactions.
+    async fn watch(&self, address: &str) -> Result<(), BackendError> {
+        if self.watched.lock().unwrap().contains(address) {
+            return Ok(());
+        }
+        self.ensure_wallet().await?;
+        let info = self
+            .call("getdescriptorinfo", json!([format!("addr({})", address)]))
+            .await?;
+        let imported = self
+            .wallet_call(
+                "importdescriptors",
+                json!([[{"desc": info["descriptor"], "timestamp": 0}]]),
+            )
+            .await?;
+        if imported[0]["success"] != true {
+            return Err(BackendError::Rpc(imported.to_string()));
+        }
+        self.watched.lock().unwrap().insert(address.to_string());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChainBackend for BitcoindBackend {
+    async fn address_transactions(&self, address: &str) -> Result<Vec<Transaction>, BackendError> {
+        self.watch(address).await?;
+        let entries = self
+            .wallet_call("listtransactions", json!(["*", 1000, 0, true]))
+            .await?;
+        let mut txids: Vec<&str> = Vec::new();
+        for entry in entries.as_array().into_iter().flatten().rev() {
+            if entry["address"] == address {
+                if let Some(txid) = entry["txid"].as_str() {
+                    if !txids.contains(&txid) {
+                        txids.push(txid);
+                    }
+                }
+            }
+        }
+        let mut transactions = Vec::new();
+        for txid in txids {
+            transactions.push(self.transaction(txid).await?);
+        }
+        Ok(transactions)
+    }
+
+    async fn address_utxos(&self, address: &str) -> Result<Vec<Utxo>, BackendError> {
+        self.watch(address).await?;
+        let unspent = self
+            .wallet_call("listunspent", json!([0, 9999999, [address]]))
+            .await?;
+        unspent
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|unspent| {
+                let value = unspent["amount"].as_f64().unwrap_or_default();
+                Ok(Utxo {
+                    txid: unspent["txid"].as_str().unwrap_or_default().to_string(),
+                    vout: unspent["vout"].as_u64().unwrap_or_default() as u32,
+                    value: bitcoin::Amount::from_btc(value)
+                        .map_err(|e| BackendError::Malformed(e.to_string()))?
+                        .to_sat(),
+                    status: UtxoStatus {
+                        confirmed: unspent["confirmations"].as_i64().unwrap_or(0) > 0,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    async fn transaction(&self, txid: &str) -> Result<Transaction, BackendError> {
+        // Transactions outside of the wallet need `-txindex` or to be in the mempool.
+        let details = match self
+            .wallet_call("gettransaction", json!([txid, true]))
             .await
-            .expect("Failed to read response");
+        {
+            Ok(details) => details,
+            Err(_) => self.call("getrawtransaction", json!([txid, true])).await?,
+        };
+        let raw = decode_transaction(details["hex"].as_str().unwrap_or_default())?;
+        let confirmations = details["confirmations"].as_u64().unwrap_or(0);
+        let block_height = match details["blockheight"].as_u64() {
+            Some(block_height) => Some(block_height),
+            None if confirmations > 0 => {
+                Some(block_height(self.tip_height().await?, confirmations))
+            }
+            None => None,
+        };
+        Ok(Transaction::from_raw(
+            &raw,
+            TransactionStatus {
+                confirmed: confirmations > 0,
+                block_height,
+                block_time: details["blocktime"].as_i64(),
+            },
+        ))
+    }
+
+    async fn broadcast(&self, transaction_hex: String) -> Result<String, BackendError> {
+        match self
+            .call("sendrawtransaction", json!([transaction_hex]))
+            .await?
+        {
+            Value::String(txid) => Ok(txid),
+            result => Err(BackendError::Malformed(result.to_string())),
+        }
+    }
+
+    async fn genesis_hash(&self) -> Result<String, BackendError> {
+        match self.call("getblockhash", json!([0])).await? {
+            Value::String(hash) => Ok(hash),
+            result => Err(BackendError::Malformed(result.to_string())),
+        }
+    }
+
+    async fn raw_transaction(&self, txid: &str) -> Result<bitcoin::Transaction, BackendError> {
+        let details = match self
+            .wallet_call("gettransaction", json!([txid, true]))
+            .await
+        {
+            Ok(details) => details,
+            Err(_) => self.call("getrawtransaction", json!([txid, true])).await?,
+        };
+        decode_transaction(details["hex"].as_str().unwrap_or_default())
+    }
+
+    async fn fee_rate(&self, target: u16) -> Result<u64, BackendError> {
+        // Fails without enough blocks seen to estimate, like on a fresh regtest.
+        let estimate = self
+            .call("estimatesmartfee", json!([target]))
+            .await
+            .unwrap_or(Value::Null);
+        Ok(fee_rate_from_btc_per_kvb(estimate["feerate"].as_f64()))
+    }
 
-        address
+    async fn tip_height(&self) -> Result<u64, BackendError> {
+        let height = self.call("getblockcount", json!([])).await?;
+        height
+            .as_u64()
+            .ok_or_else(|| BackendError::Malformed(height.to_string()))
     }
 }
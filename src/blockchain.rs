@@ -1,5 +1,11 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::secp256k1::{schnorr, KeyPair, Message, Secp256k1};
 use borsh::{BorshDeserialize, BorshSerialize};
 use chrono::prelude::*;
+use chrono::Days;
 use log::{info, warn};
 use openssl::hash::MessageDigest;
 use openssl::pkey::PKey;
@@ -9,16 +15,72 @@ use openssl::sha::Sha256;
 use openssl::sign::{Signer, Verifier};
 use serde::{Deserialize, Serialize};
 
-use crate::blockchain::OperationCode::Endorse;
-use crate::constants::BILLS_FOLDER_PATH;
+use crate::amount::Amount;
+use crate::anchor;
+use crate::constants::{BILLS_FOLDER_PATH, TIMESTAMP_TOLERANCE_SECONDS};
+use crate::key_rotation::{self, KeyRotation};
+use crate::lightning::LightningPayment;
+use crate::maturity::Maturity;
+use crate::payment_address::PaymentAddressType;
+use crate::protest::{parse_maturity_date, ProtestDocument, ProtestReason};
+use crate::recourse::RecourseClaim;
+use crate::timestamp::{self, TimestampError};
 use crate::{
-    bill_from_byte_array, decrypt_bytes, encrypt_bytes, private_key_from_pem_u8,
-    public_key_from_pem_u8, read_keys_from_bill_file, BitcreditBill, IdentityPublicData,
+    decrypt_bytes, encrypt_bytes, private_key_from_pem_u8, read_keys_from_bill_file, BitcreditBill,
+    IdentityPublicData,
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Chain {
     pub blocks: Vec<Block>,
+    /// Resolved forks of this bill, kept for the audit trail.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<Fork>,
+}
+
+/// Two valid branches of a bill that diverge after a common ancestor.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fork {
+    /// Id of the last block both branches share.
+    pub ancestor_id: u64,
+    /// Branch that won the resolution and is part of [`Chain::blocks`].
+    pub kept: Vec<Block>,
+    /// Branch that lost the resolution.
+    pub discarded: Vec<Block>,
+    pub detected_at: i64,
+}
+
+impl Fork {
+    /// Describes the conflicting blocks, e.g. a double endorsement.
+    pub fn conflict(&self) -> String {
+        let kept = self.kept.first().expect("branches are not empty");
+        let discarded = self.discarded.first().expect("branches are not empty");
+        let operation = |block: &Block| {
+            block
+                .operation_code
+                .clone()
+                .get_string_from_operation_code()
+        };
+        if kept.operation_code == discarded.operation_code {
+            format!(
+                "double {} after block {}",
+                operation(kept).to_lowercase(),
+                self.ancestor_id
+            )
+        } else {
+            format!(
+                "{} conflicts with {} after block {}",
+                operation(kept),
+                operation(discarded),
+                self.ancestor_id
+            )
+        }
+    }
+
+    fn is_between(&self, first: &Block, second: &Block) -> bool {
+        let hashes = [&self.kept[0].hash, &self.discarded[0].hash];
+        hashes.contains(&&first.hash) && hashes.contains(&&second.hash)
+    }
 }
 
 impl Chain {
@@ -26,7 +88,10 @@ impl Chain {
         let mut blocks = Vec::new();
         blocks.push(first_block);
 
-        Self { blocks }
+        Self {
+            blocks,
+            forks: Vec::new(),
+        }
     }
 
     pub fn read_chain_from_file(bill_name: &String) -> Self {
@@ -44,29 +109,68 @@ impl Chain {
         .unwrap();
     }
 
+    /// Checks every block against the one before it and the state of the chain up to it,
+    /// decrypting each block once.
     pub fn is_chain_valid(&self) -> bool {
-        for i in 0..self.blocks.len() {
-            if i == 0 {
-                continue;
+        let mut state = match self.get_first_state() {
+            Ok(state) => state,
+            Err(error) => {
+                warn!("first block is invalid: {}", error);
+                return false;
             }
-            let first: &Block = &self.blocks[i - 1];
-            let second: &Block = &self.blocks[i];
-            if !is_block_valid(second, first) {
+        };
+        for blocks in self.blocks.windows(2) {
+            if !is_block_valid(&blocks[1], &blocks[0]) {
                 return false;
             }
+            match authorized_block_data(&blocks[1], &state) {
+                Some(block_data) => state.apply(&block_data),
+                None => return false,
+            }
         }
         true
     }
 
     pub fn try_add_block(&mut self, block: Block) -> bool {
+        match self.get_state() {
+            Ok(mut state) => self.try_add_block_with_state(block, &mut state),
+            Err(error) => {
+                error!("could not add block - chain is unreadable: {}", error);
+                false
+            }
+        }
+    }
+
+    /// Appends `block` if it may follow this chain in `state`, the state of the chain
+    /// after its last block, and takes the block into `state`.
+    fn try_add_block_with_state(&mut self, block: Block, state: &mut ChainState) -> bool {
         let latest_block = self.blocks.last().expect("there is at least one block");
         if is_block_valid(&block, latest_block) {
-            self.blocks.push(block);
-            return true;
-        } else {
-            error!("could not add block - invalid");
-            return false;
+            if let Some(block_data) = authorized_block_data(&block, state) {
+                state.apply(&block_data);
+                self.blocks.push(block);
+                return true;
+            }
         }
+        error!("could not add block - invalid");
+        false
+    }
+
+    /// State of the chain after its Issue block.
+    fn get_first_state(&self) -> Result<ChainState, BlockDataError> {
+        Ok(ChainState::new(
+            &self.try_get_first_version_bill()?,
+            key_rotation::read_key_rotations(),
+        ))
+    }
+
+    /// State of the chain after its last block.
+    pub fn get_state(&self) -> Result<ChainState, BlockDataError> {
+        let mut state = self.get_first_state()?;
+        for block in &self.blocks[1..] {
+            state.apply(&block.get_block_data()?);
+        }
+        Ok(state)
     }
 
     pub fn get_latest_block(&self) -> &Block {
@@ -90,6 +194,10 @@ impl Chain {
         last_version_block
     }
 
+    pub fn is_paid(&self) -> bool {
+        self.exist_block_with_operation_code(OperationCode::Paid)
+    }
+
     pub fn exist_block_with_operation_code(&self, operation_code: OperationCode) -> bool {
         let mut exist_block_with_operation_code = false;
         for block in &self.blocks {
@@ -101,86 +209,187 @@ impl Chain {
     }
 
     pub fn get_last_version_bill(&self) -> BitcreditBill {
-        let first_block = self.get_first_block();
-
-        let bill_keys = read_keys_from_bill_file(&first_block.bill_name);
-        let key: Rsa<Private> =
-            Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-        let bytes = hex::decode(first_block.data.clone()).unwrap();
-        let decrypted_bytes = decrypt_bytes(&bytes, &key);
-        let bill_first_version: BitcreditBill = bill_from_byte_array(&decrypted_bytes);
-
-        let mut last_endorsee = IdentityPublicData {
-            peer_id: "".to_string(),
-            name: "".to_string(),
-            bitcoin_public_key: "".to_string(),
-            postal_address: "".to_string(),
-            email: "".to_string(),
-            rsa_public_key_pem: "".to_string(),
-        };
+        let mut bill = self.get_first_version_bill();
 
-        if self.blocks.len() > 1 && self.exist_block_with_operation_code(Endorse.clone()) {
-            let last_version_block = self.get_last_version_block_with_operation_code(Endorse);
-
-            let bill_keys = read_keys_from_bill_file(&last_version_block.bill_name);
-            let key: Rsa<Private> =
-                Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-            let bytes = hex::decode(last_version_block.data.clone()).unwrap();
-            let decrypted_bytes = decrypt_bytes(&bytes, &key);
-            let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-            let mut part_with_endorsee = block_data_decrypted
-                .split("Endorsed to ")
-                .collect::<Vec<&str>>()
-                .get(1)
-                .unwrap()
-                .to_string();
-
-            part_with_endorsee = part_with_endorsee
-                .split(" endorsed by ")
-                .collect::<Vec<&str>>()
-                .get(0)
-                .unwrap()
-                .to_string();
-            let endorsee = hex::decode(part_with_endorsee).unwrap();
-            last_endorsee = serde_json::from_slice(&endorsee).unwrap();
-        }
-
-        let bill = BitcreditBill {
-            name: bill_first_version.name,
-            to_payee: bill_first_version.to_payee,
-            bill_jurisdiction: bill_first_version.bill_jurisdiction,
-            timestamp_at_drawing: bill_first_version.timestamp_at_drawing,
-            drawee: bill_first_version.drawee,
-            drawer: bill_first_version.drawer,
-            payee: bill_first_version.payee,
-            endorsee: last_endorsee.clone(),
-            place_of_drawing: bill_first_version.place_of_drawing,
-            currency_code: bill_first_version.currency_code,
-            amount_numbers: bill_first_version.amount_numbers,
-            amounts_letters: bill_first_version.amounts_letters,
-            maturity_date: bill_first_version.maturity_date,
-            date_of_issue: bill_first_version.date_of_issue,
-            compounding_interest_rate: bill_first_version.compounding_interest_rate,
-            type_of_interest_calculation: bill_first_version.type_of_interest_calculation,
-            place_of_payment: bill_first_version.place_of_payment,
-            public_key: bill_first_version.public_key,
-            private_key: bill_first_version.private_key,
-            language: bill_first_version.language,
-        };
+        for block in &self.blocks {
+            if block.operation_code == OperationCode::Endorse {
+                match block
+                    .get_block_data()
+                    .expect("Endorse block data is invalid")
+                {
+                    BlockData::Endorse { endorsee, .. } => bill.endorsee = endorsee,
+                    _ => unreachable!("block data is checked against operation code"),
+                }
+            }
+        }
+        if let Some(due_date) = self.get_due_date() {
+            bill.maturity_date = due_date.to_string();
+        }
 
         bill
     }
 
     fn get_first_version_bill(&self) -> BitcreditBill {
-        let first_block_data = &self.get_first_block();
-        let bill_keys = read_keys_from_bill_file(&first_block_data.bill_name);
-        let key: Rsa<Private> =
-            Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-        let bytes = hex::decode(first_block_data.data.clone()).unwrap();
-        let decrypted_bytes = decrypt_bytes(&bytes, &key);
-        let bill_first_version: BitcreditBill = bill_from_byte_array(&decrypted_bytes);
-        bill_first_version
+        self.try_get_first_version_bill()
+            .expect("Issue block data is invalid")
+    }
+
+    fn try_get_first_version_bill(&self) -> Result<BitcreditBill, BlockDataError> {
+        match self.get_first_block().get_block_data()? {
+            BlockData::Issue(bill) => Ok(bill),
+            block_data => Err(BlockDataError::OperationCodeMismatch {
+                expected: OperationCode::Issue,
+                found: block_data.operation_code(),
+            }),
+        }
+    }
+
+    pub fn get_protests(&self) -> Vec<ProtestDocument> {
+        let mut protests = Vec::new();
+        for block in &self.blocks {
+            match block.get_block_data() {
+                Ok(BlockData::ProtestNonAcceptance { protest })
+                | Ok(BlockData::ProtestNonPayment { protest }) => protests.push(protest),
+                _ => {}
+            }
+        }
+        protests
+    }
+
+    pub fn get_recourses(&self) -> Vec<RecourseClaim> {
+        let mut recourses = Vec::new();
+        for block in &self.blocks {
+            if let Ok(BlockData::Recourse { recourse }) = block.get_block_data() {
+                recourses.push(recourse);
+            }
+        }
+        recourses
+    }
+
+    /// The drawer, everybody who endorsed the bill and all guarantors, in the order they
+    /// signed it.
+    pub fn get_liable_parties(&self) -> Vec<IdentityPublicData> {
+        let mut liable_parties = vec![self.get_first_version_bill().drawer];
+        for block in &self.blocks {
+            let party = match block.get_block_data() {
+                Ok(BlockData::Endorse { endorser, .. }) => endorser,
+                Ok(BlockData::Aval { guarantor, .. }) => guarantor,
+                _ => continue,
+            };
+            if !liable_parties
+                .iter()
+                .any(|liable_party| liable_party.peer_id.eq(&party.peer_id))
+            {
+                liable_parties.push(party);
+            }
+        }
+        liable_parties
+    }
+
+    /// Amount the drawee accepted, `None` while the bill is not accepted. A bill is
+    /// accepted once, later acceptances don't count.
+    pub fn get_accepted_amount(&self) -> Option<u64> {
+        for block in &self.blocks {
+            match block.get_block_data() {
+                Ok(BlockData::Accept { .. }) => {
+                    return Some(self.get_first_version_bill().amount_numbers)
+                }
+                Ok(BlockData::PartialAccept { amount, .. }) => return Some(amount),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Date the bill falls due, `None` while it depends on a presentment that did not
+    /// happen yet. Bills payable after sight count from the acceptance, or from the
+    /// protest when acceptance was refused.
+    pub fn get_due_date(&self) -> Option<NaiveDate> {
+        let bill = self.get_first_version_bill();
+        let date_of_issue = parse_maturity_date(&bill.date_of_issue)?;
+        let block_date = |operation_codes: &[OperationCode]| {
+            self.blocks
+                .iter()
+                .find(|block| operation_codes.contains(&block.operation_code))
+                .and_then(|block| NaiveDateTime::from_timestamp_opt(block.timestamp, 0))
+                .map(|date_time| date_time.date())
+        };
+
+        match bill.maturity {
+            Maturity::FixedDate | Maturity::AfterDate { .. } => bill
+                .maturity
+                .fixed_due_date(date_of_issue, &bill.maturity_date),
+            Maturity::AtSight => block_date(&[OperationCode::RequestToPay]),
+            Maturity::AfterSight { days } => {
+                block_date(&[OperationCode::Accept, OperationCode::ProtestNonAcceptance])?
+                    .checked_add_days(Days::new(days as u64))
+            }
+        }
+    }
+
+    /// Last day the drawee can be asked to accept, `None` for sight bills.
+    pub fn get_acceptance_deadline(&self) -> Option<NaiveDate> {
+        let bill = self.get_first_version_bill();
+        bill.maturity.acceptance_deadline(
+            parse_maturity_date(&bill.date_of_issue)?,
+            &bill.maturity_date,
+        )
+    }
+
+    /// The accepted amount once the bill is accepted, the bill amount before.
+    pub fn get_amount_to_pay(&self) -> u64 {
+        self.get_accepted_amount()
+            .unwrap_or(self.get_first_version_bill().amount_numbers)
+    }
+
+    pub fn get_avals(&self) -> Vec<Guarantee> {
+        let bill = self.get_first_version_bill();
+        let mut avals = Vec::new();
+        for block in &self.blocks {
+            if let Ok(BlockData::Aval {
+                guarantor,
+                guaranteed,
+            }) = block.get_block_data()
+            {
+                let liable_as = if guaranteed.peer_id.eq(&bill.drawee.peer_id) {
+                    "drawee"
+                } else if guaranteed.peer_id.eq(&bill.drawer.peer_id) {
+                    "drawer"
+                } else {
+                    "endorser"
+                };
+                avals.push(Guarantee {
+                    guarantor,
+                    guaranteed,
+                    liable_as: liable_as.to_string(),
+                });
+            }
+        }
+        avals
+    }
+
+    /// The party that asked `guarantor_peer_id` for an aval which was not given yet.
+    pub fn get_pending_aval_request(
+        &self,
+        guarantor_peer_id: &String,
+    ) -> Option<IdentityPublicData> {
+        let avals = self.get_avals();
+        for block in &self.blocks {
+            if let Ok(BlockData::RequestToAval {
+                requester,
+                guarantor,
+            }) = block.get_block_data()
+            {
+                let already_given = avals.iter().any(|aval| {
+                    aval.guarantor.peer_id.eq(guarantor_peer_id)
+                        && aval.guaranteed.peer_id.eq(&requester.peer_id)
+                });
+                if guarantor.peer_id.eq(guarantor_peer_id) && !already_given {
+                    return Some(requester);
+                }
+            }
+        }
+        None
     }
 
     pub fn get_block_by_id(&self, id: u64) -> Block {
@@ -194,6 +403,13 @@ impl Chain {
     }
 
     pub fn compare_chain(&mut self, other_chain: Chain, bill_name: &String) {
+        if other_chain.blocks.is_empty() {
+            return;
+        }
+        if let Some(ancestor_index) = self.find_fork(&other_chain) {
+            self.resolve_fork(ancestor_index, other_chain, bill_name);
+            return;
+        }
         let local_chain_last_id = self.get_latest_block().id.clone();
         let other_chain_last_id = other_chain.get_latest_block().id.clone();
         if local_chain_last_id.eq(&other_chain_last_id) {
@@ -201,11 +417,14 @@ impl Chain {
         } else if local_chain_last_id > other_chain_last_id {
             return;
         } else {
+            let mut state = match self.get_state() {
+                Ok(state) => state,
+                Err(_) => return,
+            };
             let difference_in_id = other_chain_last_id - local_chain_last_id;
             for block_id in 1..difference_in_id + 1 {
                 let block = other_chain.get_block_by_id(local_chain_last_id.clone() + block_id);
-                let try_add_block = self.try_add_block(block);
-                if try_add_block && self.is_chain_valid() {
+                if self.try_add_block_with_state(block, &mut state) {
                     self.write_chain_to_file(&bill_name);
                 } else {
                     return;
@@ -214,152 +433,188 @@ impl Chain {
         }
     }
 
+    /// Index of the last block shared with `other` if both chains continue differently after it.
+    pub fn find_fork(&self, other: &Chain) -> Option<usize> {
+        let shared = self
+            .blocks
+            .iter()
+            .zip(&other.blocks)
+            .take_while(|(local, remote)| local.hash == remote.hash)
+            .count();
+        if shared == 0 || shared == self.blocks.len() || shared == other.blocks.len() {
+            return None;
+        }
+        Some(shared - 1)
+    }
+
+    /// A copy of this chain where `block` replaces the block at its height, if they conflict.
+    pub fn branch_with(&self, block: &Block) -> Option<Chain> {
+        let index = usize::try_from(block.id).ok()?.checked_sub(1)?;
+        if index == 0 || index >= self.blocks.len() {
+            return None;
+        }
+        let existing = &self.blocks[index];
+        if existing.hash == block.hash || self.blocks[index - 1].hash != block.previous_hash {
+            return None;
+        }
+        let mut blocks = self.blocks[..index].to_vec();
+        blocks.push(block.clone());
+        Some(Chain {
+            blocks,
+            forks: Vec::new(),
+        })
+    }
+
+    /// Keeps the branch whose first block has the earliest valid timestamp and
+    /// records the other one in [`Chain::forks`].
+    fn resolve_fork(&mut self, ancestor_index: usize, other_chain: Chain, bill_name: &String) {
+        let mut remote_chain = Chain {
+            blocks: self.blocks[..=ancestor_index].to_vec(),
+            forks: Vec::new(),
+        };
+        let mut state = match remote_chain.get_state() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        for block in &other_chain.blocks[ancestor_index + 1..] {
+            if !remote_chain.try_add_block_with_state(block.clone(), &mut state) {
+                warn!(
+                    "ignoring invalid fork of bill {} after block {}",
+                    bill_name,
+                    ancestor_index + 1
+                );
+                return;
+            }
+        }
+
+        let ancestor = &self.blocks[ancestor_index];
+        let local_branch = self.blocks[ancestor_index + 1..].to_vec();
+        let remote_branch = remote_chain.blocks[ancestor_index + 1..].to_vec();
+        let remote_wins = prefers_branch(&remote_branch[0], &local_branch[0]);
+        let (kept, discarded) = if remote_wins {
+            (remote_branch, local_branch)
+        } else {
+            (local_branch, remote_branch)
+        };
+        let fork = Fork {
+            ancestor_id: ancestor.id,
+            kept,
+            discarded,
+            detected_at: Utc::now().timestamp(),
+        };
+        warn!("fork in bill {}: {}", bill_name, fork.conflict());
+
+        if remote_wins {
+            self.blocks = remote_chain.blocks;
+        }
+        self.forks
+            .retain(|known| !known.is_between(&fork.kept[0], &fork.discarded[0]));
+        self.forks.push(fork);
+        self.write_chain_to_file(bill_name);
+    }
+
     pub fn get_bill_history(&self) -> Vec<BlockForHistory> {
         let mut history: Vec<BlockForHistory> = Vec::new();
+        let bill_currency_code = self.get_first_version_bill().currency_code;
 
         for block in &self.blocks {
-            let mut line = String::new();
-            match block.operation_code {
-                OperationCode::Issue => {
-                    let bill = self.get_first_version_bill();
-                    let time_of_issue = Utc.timestamp_opt(block.timestamp.clone(), 0).unwrap();
-                    line = format!(
-                        "Bill issued by {} at {} in {}",
-                        bill.drawer.name, time_of_issue, bill.place_of_drawing
-                    );
-                }
-                OperationCode::Endorse => {
-                    let block = self.get_block_by_id(block.id.clone());
-                    let time_of_endorse = Utc.timestamp_opt(block.timestamp.clone(), 0).unwrap();
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let mut part_with_endorsee = block_data_decrypted
-                        .split("Endorsed to ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-
-                    let part_with_endorsed_by = part_with_endorsee
-                        .clone()
-                        .split(" endorsed by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-
-                    part_with_endorsee = part_with_endorsee
-                        .split(" endorsed by ")
-                        .collect::<Vec<&str>>()
-                        .get(0)
-                        .unwrap()
-                        .to_string();
-
-                    let endorsee_bill_u8 = hex::decode(part_with_endorsee).unwrap();
-                    let endorsee_bill: IdentityPublicData =
-                        serde_json::from_slice(&endorsee_bill_u8).unwrap();
-
-                    let endorser_bill_u8 = hex::decode(part_with_endorsed_by).unwrap();
-                    let endorser_bill: IdentityPublicData =
-                        serde_json::from_slice(&endorser_bill_u8).unwrap();
-                    line = format!(
-                        "Bill endorsed to {} at {} by {} in {}",
-                        endorsee_bill.name,
-                        time_of_endorse,
-                        endorser_bill.name,
-                        endorser_bill.postal_address
-                    );
-                }
-                OperationCode::RequestToAccept => {
-                    let block = self.get_block_by_id(block.id.clone());
-                    let time_of_request_to_accept =
-                        Utc.timestamp_opt(block.timestamp.clone(), 0).unwrap();
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let part_with_identity = block_data_decrypted
-                        .split("Requested to accept by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-                    let requester_to_accept_bill_u8 = hex::decode(part_with_identity).unwrap();
-                    let requester_to_accept_bill: IdentityPublicData =
-                        serde_json::from_slice(&requester_to_accept_bill_u8).unwrap();
-                    line = format!(
-                        "Bill requested to accept by {} at {} in {}",
-                        requester_to_accept_bill.name,
-                        time_of_request_to_accept,
-                        requester_to_accept_bill.postal_address
-                    );
-                }
-                OperationCode::Accept => {
-                    let block = self.get_block_by_id(block.id.clone());
-                    let time_of_accept = Utc.timestamp_opt(block.timestamp.clone(), 0).unwrap();
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let part_with_identity = block_data_decrypted
-                        .split("Accepted by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-                    let accepter_bill_u8 = hex::decode(part_with_identity).unwrap();
-                    let accepter_bill: IdentityPublicData =
-                        serde_json::from_slice(&accepter_bill_u8).unwrap();
-                    line = format!(
-                        "Bill accepted by {} at {} in {}",
-                        accepter_bill.name, time_of_accept, accepter_bill.postal_address
-                    );
+            let time = Utc.timestamp_opt(block.timestamp, 0).unwrap();
+            let line = match block.get_block_data() {
+                Ok(BlockData::Issue(bill)) => format!(
+                    "Bill issued by {} at {} in {}",
+                    bill.drawer.name, time, bill.place_of_drawing
+                ),
+                Ok(BlockData::Endorse { endorsee, endorser }) => format!(
+                    "Bill endorsed to {} at {} by {} in {}",
+                    endorsee.name, time, endorser.name, endorser.postal_address
+                ),
+                Ok(BlockData::RequestToAccept { requester }) => format!(
+                    "Bill requested to accept by {} at {} in {}",
+                    requester.name, time, requester.postal_address
+                ),
+                Ok(BlockData::Accept { accepter }) => format!(
+                    "Bill accepted by {} at {} in {}",
+                    accepter.name, time, accepter.postal_address
+                ),
+                Ok(BlockData::PartialAccept { accepter, amount }) => format!(
+                    "Bill accepted over {} by {} at {} in {}",
+                    display_amount(amount, &bill_currency_code),
+                    accepter.name,
+                    time,
+                    accepter.postal_address
+                ),
+                Ok(BlockData::RequestToPay { requester }) => format!(
+                    "Bill requested to pay by {} at {} in {}",
+                    requester.name, time, requester.postal_address
+                ),
+                Ok(BlockData::ProtestNonAcceptance { protest })
+                | Ok(BlockData::ProtestNonPayment { protest }) => {
+                    format!("{} Recorded at {}.", protest.text(), time)
                 }
-                OperationCode::RequestToPay => {
-                    let block = self.get_block_by_id(block.id.clone());
-                    let time_of_request_to_pay =
-                        Utc.timestamp_opt(block.timestamp.clone(), 0).unwrap();
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let part_with_identity = block_data_decrypted
-                        .split("Requested to pay by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-                    let requester_to_pay_bill_u8 = hex::decode(part_with_identity).unwrap();
-                    let requester_to_pay_bill: IdentityPublicData =
-                        serde_json::from_slice(&requester_to_pay_bill_u8).unwrap();
-                    line = format!(
-                        "Bill requested to pay by {} at {} in {}",
-                        requester_to_pay_bill.name,
-                        time_of_request_to_pay,
-                        requester_to_pay_bill.postal_address
-                    );
-                }
-            }
+                Ok(BlockData::Recourse { recourse }) => format!(
+                    "Bill recourse against {} over {} to {} by {} at {} in {}",
+                    recourse.recoursee.name,
+                    display_amount(recourse.total, &recourse.currency_code),
+                    recourse.payment_address,
+                    recourse.recourser.name,
+                    time,
+                    recourse.recourser.postal_address
+                ),
+                Ok(BlockData::RequestToAval {
+                    requester,
+                    guarantor,
+                }) => format!(
+                    "Bill requested to aval from {} at {} by {} in {}",
+                    guarantor.name, time, requester.name, requester.postal_address
+                ),
+                Ok(BlockData::Aval {
+                    guarantor,
+                    guaranteed,
+                }) => format!(
+                    "Bill avalized for {} at {} by {} in {}",
+                    guaranteed.name, time, guarantor.name, guarantor.postal_address
+                ),
+                Ok(BlockData::Paid {
+                    holder,
+                    amount,
+                    lightning: Some(payment),
+                    ..
+                }) => format!(
+                    "Bill paid {} over Lightning with preimage {} as confirmed by {} at {}",
+                    display_amount(amount, &bill_currency_code),
+                    payment.preimage,
+                    holder.name,
+                    time
+                ),
+                Ok(BlockData::Paid {
+                    holder,
+                    payment_address,
+                    amount,
+                    txids,
+                    lightning: None,
+                }) => format!(
+                    "Bill paid {} to {} in {} as confirmed by {} at {}",
+                    display_amount(amount, &bill_currency_code),
+                    payment_address,
+                    txids.join(", "),
+                    holder.name,
+                    time
+                ),
+                Ok(BlockData::RecoursePaid {
+                    recourser,
+                    recoursee,
+                    txids,
+                }) => format!(
+                    "Recourse against {} paid in {} as confirmed by {} at {}",
+                    recoursee.name,
+                    txids.join(", "),
+                    recourser.name,
+                    time
+                ),
+                Err(error) => format!("Block {} could not be read: {}", block.id, error),
+            };
             history.push(BlockForHistory {
-                id: block.id.clone(),
+                id: block.id,
                 text: line,
                 bill_name: block.bill_name.clone(),
             });
@@ -369,139 +624,22 @@ impl Chain {
 
     pub fn bill_contain_node(&self, request_node_id: String) -> bool {
         for block in &self.blocks {
-            let mut line = String::new();
-            match block.operation_code {
-                OperationCode::Issue => {
-                    let bill = self.get_first_version_bill();
-                    if bill.drawer.peer_id.eq(&request_node_id) {
-                        return true;
-                    } else if bill.drawee.peer_id.eq(&request_node_id) {
-                        return true;
-                    } else if bill.payee.peer_id.eq(&request_node_id) {
-                        return true;
-                    }
-                }
-                OperationCode::Endorse => {
-                    let block = self.get_block_by_id(block.id.clone());
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let mut part_with_endorsee = block_data_decrypted
-                        .split("Endorsed to ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-
-                    let part_with_endorsed_by = part_with_endorsee
-                        .clone()
-                        .split(" endorsed by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-
-                    part_with_endorsee = part_with_endorsee
-                        .split(" endorsed by ")
-                        .collect::<Vec<&str>>()
-                        .get(0)
-                        .unwrap()
-                        .to_string();
-
-                    let endorsee_bill_u8 = hex::decode(part_with_endorsee).unwrap();
-                    let endorsee_bill: IdentityPublicData =
-                        serde_json::from_slice(&endorsee_bill_u8).unwrap();
-
-                    let endorser_bill_u8 = hex::decode(part_with_endorsed_by).unwrap();
-                    let endorser_bill: IdentityPublicData =
-                        serde_json::from_slice(&endorser_bill_u8).unwrap();
-
-                    if endorsee_bill.peer_id.eq(&request_node_id) {
-                        return true;
-                    } else if endorser_bill.peer_id.eq(&request_node_id) {
-                        return true;
-                    }
-                }
-                OperationCode::RequestToAccept => {
-                    let block = self.get_block_by_id(block.id.clone());
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let part_with_identity = block_data_decrypted
-                        .split("Requested to accept by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-                    let requester_to_accept_bill_u8 = hex::decode(part_with_identity).unwrap();
-                    let requester_to_accept_bill: IdentityPublicData =
-                        serde_json::from_slice(&requester_to_accept_bill_u8).unwrap();
-
-                    if requester_to_accept_bill.peer_id.eq(&request_node_id) {
-                        return true;
-                    }
-                }
-                OperationCode::Accept => {
-                    let block = self.get_block_by_id(block.id.clone());
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let part_with_identity = block_data_decrypted
-                        .split("Accepted by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-                    let accepter_bill_u8 = hex::decode(part_with_identity).unwrap();
-                    let accepter_bill: IdentityPublicData =
-                        serde_json::from_slice(&accepter_bill_u8).unwrap();
-
-                    if accepter_bill.peer_id.eq(&request_node_id) {
-                        return true;
-                    }
-                }
-                OperationCode::RequestToPay => {
-                    let block = self.get_block_by_id(block.id.clone());
-
-                    let bill_keys = read_keys_from_bill_file(&block.bill_name);
-                    let key: Rsa<Private> =
-                        Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes()).unwrap();
-                    let bytes = hex::decode(block.data.clone()).unwrap();
-                    let decrypted_bytes = decrypt_bytes(&bytes, &key);
-                    let block_data_decrypted = String::from_utf8(decrypted_bytes).unwrap();
-
-                    let part_with_identity = block_data_decrypted
-                        .split("Requested to pay by ")
-                        .collect::<Vec<&str>>()
-                        .get(1)
-                        .unwrap()
-                        .to_string();
-                    let requester_to_pay_bill_u8 = hex::decode(part_with_identity).unwrap();
-                    let requester_to_pay_bill: IdentityPublicData =
-                        serde_json::from_slice(&requester_to_pay_bill_u8).unwrap();
-
-                    if requester_to_pay_bill.peer_id.eq(&request_node_id) {
-                        return true;
-                    }
+            let block_data = match block.get_block_data() {
+                Ok(block_data) => block_data,
+                Err(error) => {
+                    warn!("block with id: {} is skipped: {}", block.id, error);
+                    continue;
                 }
+            };
+            if block_data
+                .participants()
+                .iter()
+                .any(|participant| participant.peer_id.eq(&request_node_id))
+            {
+                return true;
             }
         }
-        return false;
+        false
     }
 }
 
@@ -512,6 +650,13 @@ pub enum OperationCode {
     Endorse,
     RequestToAccept,
     RequestToPay,
+    ProtestNonAcceptance,
+    ProtestNonPayment,
+    Recourse,
+    RequestToAval,
+    Aval,
+    Paid,
+    RecoursePaid,
 }
 
 impl OperationCode {
@@ -522,6 +667,13 @@ impl OperationCode {
             OperationCode::Endorse,
             OperationCode::RequestToAccept,
             OperationCode::RequestToPay,
+            OperationCode::ProtestNonAcceptance,
+            OperationCode::ProtestNonPayment,
+            OperationCode::Recourse,
+            OperationCode::RequestToAval,
+            OperationCode::Aval,
+            OperationCode::Paid,
+            OperationCode::RecoursePaid,
         ]
     }
 
@@ -532,10 +684,271 @@ impl OperationCode {
             OperationCode::Endorse => "Endorse".to_string(),
             OperationCode::RequestToAccept => "RequestToAccept".to_string(),
             OperationCode::RequestToPay => "RequestToPay".to_string(),
+            OperationCode::ProtestNonAcceptance => "ProtestNonAcceptance".to_string(),
+            OperationCode::ProtestNonPayment => "ProtestNonPayment".to_string(),
+            OperationCode::Recourse => "Recourse".to_string(),
+            OperationCode::RequestToAval => "RequestToAval".to_string(),
+            OperationCode::Aval => "Aval".to_string(),
+            OperationCode::Paid => "Paid".to_string(),
+            OperationCode::RecoursePaid => "RecoursePaid".to_string(),
         }
     }
 }
 
+/// Version tag written in front of every serialized [`BlockData`]. Version 1 bills
+/// have no [`Maturity`].
+pub const BLOCK_DATA_VERSION: u8 = 4;
+
+/// Typed payload of a block, one variant per [`OperationCode`].
+///
+/// It is serialized with Borsh behind a [`BLOCK_DATA_VERSION`] byte and encrypted with
+/// the bill key before it is stored in [`Block::data`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum BlockData {
+    Issue(BitcreditBill),
+    Accept {
+        accepter: IdentityPublicData,
+    },
+    Endorse {
+        endorsee: IdentityPublicData,
+        endorser: IdentityPublicData,
+    },
+    RequestToAccept {
+        requester: IdentityPublicData,
+    },
+    RequestToPay {
+        requester: IdentityPublicData,
+    },
+    ProtestNonAcceptance {
+        protest: ProtestDocument,
+    },
+    ProtestNonPayment {
+        protest: ProtestDocument,
+    },
+    Recourse {
+        recourse: RecourseClaim,
+    },
+    RequestToAval {
+        requester: IdentityPublicData,
+        guarantor: IdentityPublicData,
+    },
+    /// Guarantee of `guarantor` for the obligation of `guaranteed`, signed by the guarantor.
+    Aval {
+        guarantor: IdentityPublicData,
+        guaranteed: IdentityPublicData,
+    },
+    /// Acceptance of less than the bill amount. The rest can be protested for non-acceptance.
+    PartialAccept {
+        accepter: IdentityPublicData,
+        amount: u64,
+    },
+    /// Payments to `payment_address` that covered `amount` and reached the required depth,
+    /// or a settled Lightning invoice, recorded by the holder.
+    Paid {
+        holder: IdentityPublicData,
+        payment_address: String,
+        amount: u64,
+        txids: Vec<String>,
+        lightning: Option<LightningPayment>,
+    },
+    /// Payments `txids` that covered the open recourse against `recoursee` and reached
+    /// the required depth, recorded by the recourser.
+    RecoursePaid {
+        recourser: IdentityPublicData,
+        recoursee: IdentityPublicData,
+        txids: Vec<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum BlockDataError {
+    Empty,
+    Malformed(String),
+    /// The bill keys are missing or do not open the data.
+    Undecryptable(String),
+    OperationCodeMismatch {
+        expected: OperationCode,
+        found: OperationCode,
+    },
+}
+
+impl fmt::Display for BlockDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockDataError::Empty => write!(f, "block data is empty"),
+            BlockDataError::Malformed(reason) => write!(f, "block data is malformed: {}", reason),
+            BlockDataError::Undecryptable(reason) => {
+                write!(f, "block data can not be decrypted: {}", reason)
+            }
+            BlockDataError::OperationCodeMismatch { expected, found } => write!(
+                f,
+                "block data is for operation {:?}, but block has operation {:?}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl Error for BlockDataError {}
+
+impl BlockData {
+    pub fn operation_code(&self) -> OperationCode {
+        match self {
+            BlockData::Issue(_) => OperationCode::Issue,
+            BlockData::Accept { .. } => OperationCode::Accept,
+            BlockData::Endorse { .. } => OperationCode::Endorse,
+            BlockData::RequestToAccept { .. } => OperationCode::RequestToAccept,
+            BlockData::RequestToPay { .. } => OperationCode::RequestToPay,
+            BlockData::ProtestNonAcceptance { .. } => OperationCode::ProtestNonAcceptance,
+            BlockData::ProtestNonPayment { .. } => OperationCode::ProtestNonPayment,
+            BlockData::Recourse { .. } => OperationCode::Recourse,
+            BlockData::RequestToAval { .. } => OperationCode::RequestToAval,
+            BlockData::Aval { .. } => OperationCode::Aval,
+            BlockData::PartialAccept { .. } => OperationCode::Accept,
+            BlockData::Paid { .. } => OperationCode::Paid,
+            BlockData::RecoursePaid { .. } => OperationCode::RecoursePaid,
+        }
+    }
+
+    /// Everybody named in this block.
+    pub fn participants(&self) -> Vec<&IdentityPublicData> {
+        match self {
+            BlockData::Issue(bill) => vec![&bill.drawer, &bill.drawee, &bill.payee],
+            BlockData::Accept { accepter } => vec![accepter],
+            BlockData::Endorse { endorsee, endorser } => vec![endorsee, endorser],
+            BlockData::RequestToAccept { requester } => vec![requester],
+            BlockData::RequestToPay { requester } => vec![requester],
+            BlockData::ProtestNonAcceptance { protest } => vec![&protest.protester],
+            BlockData::ProtestNonPayment { protest } => vec![&protest.protester],
+            BlockData::Recourse { recourse } => vec![&recourse.recourser, &recourse.recoursee],
+            BlockData::RequestToAval {
+                requester,
+                guarantor,
+            } => vec![requester, guarantor],
+            BlockData::Aval {
+                guarantor,
+                guaranteed,
+            } => vec![guarantor, guaranteed],
+            BlockData::PartialAccept { accepter, .. } => vec![accepter],
+            BlockData::Paid { holder, .. } => vec![holder],
+            BlockData::RecoursePaid {
+                recourser,
+                recoursee,
+                ..
+            } => vec![recourser, recoursee],
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![BLOCK_DATA_VERSION];
+        bytes.append(&mut self.try_to_vec().expect("Failed to serialize block data"));
+        bytes
+    }
+
+    /// Parses decrypted block data and checks that it belongs to `operation_code`.
+    ///
+    /// Blocks written before [`BLOCK_DATA_VERSION`] existed are still understood.
+    pub fn from_bytes(
+        bytes: &[u8],
+        operation_code: &OperationCode,
+    ) -> Result<Self, BlockDataError> {
+        let block_data = match bytes.split_first() {
+            None => return Err(BlockDataError::Empty),
+            Some((&BLOCK_DATA_VERSION, rest)) => BlockData::try_from_slice(rest)
+                .map_err(|e| BlockDataError::Malformed(e.to_string()))?,
+            Some((&version @ (1 | 2), rest)) if *operation_code == OperationCode::Issue => {
+                BlockData::try_from_slice(&with_bill_defaults(rest, version))
+                    .map_err(|e| BlockDataError::Malformed(e.to_string()))?
+            }
+            // `Paid` blocks exist since version 2, the Lightning proof since version 4.
+            Some((&(2 | 3), rest)) if *operation_code == OperationCode::Paid => {
+                let mut bytes = rest.to_vec();
+                bytes.append(&mut None::<LightningPayment>.try_to_vec().unwrap());
+                BlockData::try_from_slice(&bytes)
+                    .map_err(|e| BlockDataError::Malformed(e.to_string()))?
+            }
+            Some((&(1..=3), rest)) => BlockData::try_from_slice(rest)
+                .map_err(|e| BlockDataError::Malformed(e.to_string()))?,
+            Some(_) => Self::from_legacy_bytes(bytes, operation_code)?,
+        };
+
+        if block_data.operation_code() != *operation_code {
+            return Err(BlockDataError::OperationCodeMismatch {
+                expected: operation_code.clone(),
+                found: block_data.operation_code(),
+            });
+        }
+        Ok(block_data)
+    }
+
+    fn from_legacy_bytes(
+        bytes: &[u8],
+        operation_code: &OperationCode,
+    ) -> Result<Self, BlockDataError> {
+        if *operation_code == OperationCode::Issue {
+            return BitcreditBill::try_from_slice(&with_bill_defaults(bytes, 0))
+                .map(BlockData::Issue)
+                .map_err(|e| BlockDataError::Malformed(e.to_string()));
+        }
+
+        let text =
+            std::str::from_utf8(bytes).map_err(|e| BlockDataError::Malformed(e.to_string()))?;
+        let strip = |prefix: &str| {
+            text.strip_prefix(prefix)
+                .ok_or_else(|| BlockDataError::Malformed(format!("expected prefix {:?}", prefix)))
+        };
+
+        match operation_code {
+            OperationCode::Endorse => {
+                let (endorsee, endorser) = strip("Endorsed to ")?
+                    .split_once(" endorsed by ")
+                    .ok_or_else(|| BlockDataError::Malformed("missing endorser".to_string()))?;
+                Ok(BlockData::Endorse {
+                    endorsee: legacy_identity(endorsee)?,
+                    endorser: legacy_identity(endorser)?,
+                })
+            }
+            OperationCode::RequestToAccept => Ok(BlockData::RequestToAccept {
+                requester: legacy_identity(strip("Requested to accept by ")?)?,
+            }),
+            OperationCode::RequestToPay => Ok(BlockData::RequestToPay {
+                requester: legacy_identity(strip("Requested to pay by ")?)?,
+            }),
+            OperationCode::Accept => Ok(BlockData::Accept {
+                accepter: legacy_identity(strip("Accepted by ")?)?,
+            }),
+            OperationCode::Issue => unreachable!("handled above"),
+            _ => Err(BlockDataError::Malformed(format!(
+                "{:?} has no legacy format",
+                operation_code
+            ))),
+        }
+    }
+}
+
+/// Bills written with block data `version` end where the later fields start. They fall
+/// due on their `maturity_date` and are paid to P2PKH addresses.
+fn with_bill_defaults(bill_bytes: &[u8], version: u8) -> Vec<u8> {
+    let mut bytes = bill_bytes.to_vec();
+    if version < 2 {
+        bytes.append(&mut Maturity::FixedDate.try_to_vec().unwrap());
+    }
+    bytes.append(&mut PaymentAddressType::P2pkh.try_to_vec().unwrap());
+    bytes
+}
+
+fn legacy_identity(hex_json: &str) -> Result<IdentityPublicData, BlockDataError> {
+    let bytes = hex::decode(hex_json).map_err(|e| BlockDataError::Malformed(e.to_string()))?;
+    serde_json::from_slice(&bytes).map_err(|e| BlockDataError::Malformed(e.to_string()))
+}
+
+/// Blocks signed with the RSA key of the identity, `public_key` is its PEM.
+pub const RSA_BLOCK_VERSION: u8 = 1;
+/// Blocks signed with BIP340 Schnorr over the bitcoin key of the identity, `public_key`
+/// is the hex of the compressed key and the version is part of the hash.
+pub const BLOCK_VERSION: u8 = 2;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Block {
     pub id: u64,
@@ -547,9 +960,21 @@ pub struct Block {
     pub signature: String,
     pub public_key: String,
     pub operation_code: OperationCode,
+    /// Missing in blocks written before versions existed.
+    #[serde(default = "rsa_block_version")]
+    pub version: u8,
+    /// Hex of an RFC 3161 token over [`Block::hash`], not part of the hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_token: Option<String>,
+}
+
+fn rsa_block_version() -> u8 {
+    RSA_BLOCK_VERSION
 }
 
 impl Block {
+    /// Signs the block with the bitcoin key of the identity, `private_key` in WIF, and
+    /// timestamps it when an authority is configured.
     pub fn new(
         id: u64,
         previous_hash: String,
@@ -560,43 +985,73 @@ impl Block {
         private_key: String,
     ) -> Self {
         let now = Utc::now();
-        let timestamp = now.timestamp();
-        let hash: String = mine_block(
-            &id,
-            &bill_name,
-            &previous_hash,
-            &data,
-            &timestamp,
-            &public_key,
-            &operation_code,
-        );
-        let signature = signature(hash.clone(), private_key.clone());
-
-        Self {
+        let mut block = Self {
             id,
             bill_name,
-            hash,
+            hash: String::new(),
             timestamp: now.timestamp(),
             previous_hash,
-            signature,
+            signature: String::new(),
             data,
             public_key,
             operation_code,
+            version: BLOCK_VERSION,
+            timestamp_token: None,
+        };
+        block.hash = mine_block(&block);
+        block.signature = schnorr_signature(&block.hash, &private_key);
+        block.timestamp_token = timestamp::request_token(&block.hash);
+        block
+    }
+
+    /// Time attested by the timestamp token, if a trusted authority issued it for this block,
+    /// or else by a confirmed anchor of the block.
+    pub fn attested_time(&self) -> Option<i64> {
+        self.timestamp_token
+            .as_ref()
+            .and_then(|token| timestamp::verify_token(token, &self.hash).ok())
+            .or_else(|| anchor::anchored_time(&self.bill_name, &self.hash))
+    }
+
+    /// A token issued for another hash or far from [`Block::timestamp`] makes the block
+    /// invalid, one from an authority that isn't trusted here is ignored.
+    fn has_valid_timestamp_token(&self) -> bool {
+        let token = match &self.timestamp_token {
+            Some(token) => token,
+            None => return true,
+        };
+        match timestamp::verify_token(token, &self.hash) {
+            Ok(time) => (time - self.timestamp).abs() <= TIMESTAMP_TOLERANCE_SECONDS,
+            Err(TimestampError::Untrusted(_)) => true,
+            Err(_) => false,
         }
     }
 
     pub fn verifier(&self) -> bool {
-        let public_key_bytes = self.public_key.as_bytes();
-        let public_key_rsa = public_key_from_pem_u8(&public_key_bytes.to_vec());
-        let verifier_key = PKey::from_rsa(public_key_rsa).unwrap();
-
-        let mut verifier = Verifier::new(MessageDigest::sha256(), verifier_key.as_ref()).unwrap();
+        match self.version {
+            RSA_BLOCK_VERSION => self.rsa_verifier(),
+            BLOCK_VERSION => {
+                verify_schnorr_signature(&self.hash, &self.signature, &self.public_key)
+            }
+            _ => false,
+        }
+    }
 
-        let data_to_check = self.hash.as_bytes();
-        verifier.update(data_to_check).unwrap();
+    fn rsa_verifier(&self) -> bool {
+        verify_rsa_signature(&self.hash, &self.signature, &self.public_key)
+    }
 
-        let signature_bytes = hex::decode(&self.signature).unwrap();
-        verifier.verify(signature_bytes.as_slice()).unwrap()
+    /// Decrypts [`Block::data`] with the bill key and parses it.
+    pub fn get_block_data(&self) -> Result<BlockData, BlockDataError> {
+        let bill_keys = read_keys_from_bill_file(&self.bill_name)
+            .map_err(|e| BlockDataError::Undecryptable(e.to_string()))?;
+        let key: Rsa<Private> = Rsa::private_key_from_pem(bill_keys.private_key_pem.as_bytes())
+            .map_err(|e| BlockDataError::Undecryptable(e.to_string()))?;
+        let bytes =
+            hex::decode(self.data.clone()).map_err(|e| BlockDataError::Malformed(e.to_string()))?;
+        let decrypted_bytes = decrypt_bytes(&bytes, &key)
+            .map_err(|e| BlockDataError::Undecryptable(e.to_string()))?;
+        BlockData::from_bytes(&decrypted_bytes, &self.operation_code)
     }
 }
 
@@ -616,9 +1071,8 @@ impl GossipsubEvent {
         bytes
     }
 
-    pub fn from_byte_array(bytes: &Vec<u8>) -> Self {
-        let event = Self::try_from_slice(bytes).expect("Failed to deserialize event");
-        event
+    pub fn from_byte_array(bytes: &Vec<u8>) -> std::io::Result<Self> {
+        Self::try_from_slice(bytes)
     }
 }
 
@@ -636,24 +1090,16 @@ pub struct BlockForHistory {
     bill_name: String,
 }
 
-fn mine_block(
-    id: &u64,
-    bill_name: &str,
-    previous_hash: &str,
-    data: &str,
-    timestamp: &i64,
-    public_key: &str,
-    operation_code: &OperationCode,
-) -> String {
-    let hash = calculate_hash(
-        id,
-        bill_name,
-        previous_hash,
-        data,
-        timestamp,
-        public_key,
-        operation_code,
-    );
+/// A given aval. The guarantor is liable in the same way as the guaranteed party.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Guarantee {
+    pub guarantor: IdentityPublicData,
+    pub guaranteed: IdentityPublicData,
+    pub liable_as: String,
+}
+
+fn mine_block(block: &Block) -> String {
+    let hash = calculate_hash(block);
     let binary_hash = hex::encode(&hash);
     info!(
         "mined! hash: {}, binary hash: {}",
@@ -663,29 +1109,385 @@ fn mine_block(
     hex::encode(hash)
 }
 
-fn calculate_hash(
-    id: &u64,
-    bill_name: &str,
-    previous_hash: &str,
-    data: &str,
-    timestamp: &i64,
-    public_key: &str,
-    operation_code: &OperationCode,
-) -> Vec<u8> {
-    let data = serde_json::json!({
-        "id": id,
-        "bill_name": bill_name,
-        "previous_hash": previous_hash,
-        "data": data,
-        "timestamp": timestamp,
-        "public_key": public_key,
-        "operation_code": operation_code,
+/// Hash of every field but the hash and the signature.
+fn calculate_hash(block: &Block) -> Vec<u8> {
+    let mut data = serde_json::json!({
+        "id": block.id,
+        "bill_name": block.bill_name,
+        "previous_hash": block.previous_hash,
+        "data": block.data,
+        "timestamp": block.timestamp,
+        "public_key": block.public_key,
+        "operation_code": block.operation_code,
     });
+    if block.version != RSA_BLOCK_VERSION {
+        data["version"] = block.version.into();
+    }
     let mut hasher = Sha256::new();
     hasher.update(data.to_string().as_bytes());
     hasher.finish().try_to_vec().unwrap()
 }
 
+/// Amount in its currency, or the raw numbers when the currency is unknown.
+pub fn display_amount(minor_units: u64, currency_code: &str) -> String {
+    match Amount::from_code(minor_units, currency_code) {
+        Ok(amount) => amount.to_string(),
+        Err(_) => format!("{} {}", minor_units, currency_code),
+    }
+}
+
+/// BIP340 signature of the block hash with the bitcoin key in WIF.
+pub fn schnorr_signature(hash: &str, private_key: &str) -> String {
+    let secp = Secp256k1::new();
+    let private_key = bitcoin::PrivateKey::from_str(private_key).unwrap();
+    let key_pair = KeyPair::from_secret_key(&secp, &private_key.inner);
+    let message = Message::from_slice(&hex::decode(hash).unwrap()).unwrap();
+    secp.sign_schnorr(&message, &key_pair).to_string()
+}
+
+/// Checks a [`schnorr_signature`] against the hex of a compressed bitcoin key.
+pub fn verify_schnorr_signature(hash: &str, signature: &str, public_key: &str) -> bool {
+    let verify = || -> Option<bool> {
+        let public_key = bitcoin::PublicKey::from_str(public_key).ok()?;
+        let signature = schnorr::Signature::from_slice(&hex::decode(signature).ok()?).ok()?;
+        let message = Message::from_slice(&hex::decode(hash).ok()?).ok()?;
+        let (x_only_public_key, _) = public_key.inner.x_only_public_key();
+        Some(
+            Secp256k1::verification_only()
+                .verify_schnorr(&signature, &message, &x_only_public_key)
+                .is_ok(),
+        )
+    };
+    verify().unwrap_or(false)
+}
+
+/// Checks a [`signature`] of `data` against an RSA public key in PEM.
+pub fn verify_rsa_signature(data: &str, signature: &str, public_key_pem: &str) -> bool {
+    let verify = || -> Option<bool> {
+        let public_key = Rsa::public_key_from_pem(public_key_pem.as_bytes()).ok()?;
+        let verifier_key = PKey::from_rsa(public_key).ok()?;
+        let mut verifier = Verifier::new(MessageDigest::sha256(), verifier_key.as_ref()).ok()?;
+        verifier.update(data.as_bytes()).ok()?;
+        verifier.verify(&hex::decode(signature).ok()?).ok()
+    };
+    verify().unwrap_or(false)
+}
+
+/// Whether `protest` of a block with `operation_code` of `bill_name` is signed by the
+/// holder with a key it had at `timestamp`.
+pub fn is_valid_protest(
+    protest: &ProtestDocument,
+    operation_code: &OperationCode,
+    bill_name: &str,
+    timestamp: i64,
+    holder: &IdentityPublicData,
+    rotations: &[KeyRotation],
+) -> bool {
+    let reason = match operation_code {
+        OperationCode::ProtestNonAcceptance => ProtestReason::NonAcceptance,
+        OperationCode::ProtestNonPayment => ProtestReason::NonPayment,
+        _ => return false,
+    };
+    protest.reason == reason
+        && protest.bill_name == bill_name
+        && protest.protester.peer_id == holder.peer_id
+        && is_key_of(
+            &protest.protester.rsa_public_key_pem,
+            timestamp,
+            holder,
+            rotations,
+        )
+        && protest.verify()
+}
+
+/// A `Paid` block settled over Lightning has to reveal the preimage of an invoice of
+/// `holder` for `bill_name` over its amount, one paid on chain has to name the payments.
+pub fn has_valid_payment_proof(
+    block_data: &BlockData,
+    bill_name: &str,
+    holder: &IdentityPublicData,
+) -> bool {
+    match block_data {
+        BlockData::Paid {
+            amount,
+            txids,
+            lightning: Some(payment),
+            ..
+        } => txids.is_empty() && payment.is_valid_for(bill_name, *amount, holder),
+        BlockData::Paid {
+            txids,
+            lightning: None,
+            ..
+        }
+        | BlockData::RecoursePaid { txids, .. } => are_valid_txids(txids),
+        _ => true,
+    }
+}
+
+/// Whether `txids` are distinct transaction ids, at least one.
+fn are_valid_txids(txids: &[String]) -> bool {
+    !txids.is_empty()
+        && txids.iter().enumerate().all(|(index, txid)| {
+            bitcoin::Txid::from_str(txid).is_ok() && !txids[..index].contains(txid)
+        })
+}
+
+/// Data of `block` if it may follow the blocks folded into `state`: its signer may perform
+/// the operation, a protest is made by the holder and a Lightning payment is proven.
+fn authorized_block_data(block: &Block, state: &ChainState) -> Option<BlockData> {
+    let block_data = match block.get_block_data() {
+        Ok(block_data) => block_data,
+        Err(error) => {
+            warn!("block with id: {} has invalid data: {}", block.id, error);
+            return None;
+        }
+    };
+    if !may_sign(&block_data, &block.public_key, block.timestamp, state) {
+        warn!(
+            "block with id: {} is signed by a party that may not {}",
+            block.id,
+            block
+                .operation_code
+                .clone()
+                .get_string_from_operation_code()
+        );
+        return None;
+    }
+    if let BlockData::ProtestNonAcceptance { protest } | BlockData::ProtestNonPayment { protest } =
+        &block_data
+    {
+        if !is_valid_protest(
+            protest,
+            &block.operation_code,
+            &block.bill_name,
+            block.timestamp,
+            &state.holder,
+            &state.rotations,
+        ) {
+            warn!("block with id: {} has an invalid protest", block.id);
+            return None;
+        }
+    }
+    if !has_valid_payment_proof(&block_data, &block.bill_name, &state.holder) {
+        warn!("block with id: {} has an invalid payment proof", block.id);
+        return None;
+    }
+    Some(block_data)
+}
+
+/// Parties of a chain whose keys sign its next block, folded block by block.
+pub struct ChainState {
+    pub drawee: IdentityPublicData,
+    pub holder: IdentityPublicData,
+    /// The drawee and everybody liable for the bill, they may ask for an aval.
+    pub obligors: Vec<IdentityPublicData>,
+    /// Requester and guarantor of every aval asked for and not given yet.
+    pub aval_requests: Vec<(IdentityPublicData, IdentityPublicData)>,
+    /// Amount of the bill as issued.
+    pub amount: u64,
+    /// Amount the drawee accepted, `None` while the bill is not accepted.
+    pub accepted_amount: Option<u64>,
+    /// Whether the holder protested non-acceptance, after which the drawee can't accept.
+    pub protested_non_acceptance: bool,
+    /// Sum of all protested amounts, `None` before the first protest.
+    pub protested_amount: Option<u64>,
+    /// Maturity date of the bill, interest of recourse claims accrues from it.
+    pub maturity_date: Option<NaiveDate>,
+    /// Every recourse taken, in chain order.
+    pub recourses: Vec<RecourseClaim>,
+    /// Whether the last recourse is not paid yet.
+    pub open_recourse: bool,
+    /// Rotations of all identities we know, read once for the whole chain.
+    pub rotations: Vec<KeyRotation>,
+}
+
+impl ChainState {
+    /// State after the Issue block of `bill`.
+    pub fn new(bill: &BitcreditBill, rotations: Vec<KeyRotation>) -> Self {
+        Self {
+            drawee: bill.drawee.clone(),
+            holder: bill.payee.clone(),
+            obligors: vec![bill.drawer.clone(), bill.drawee.clone()],
+            aval_requests: Vec::new(),
+            amount: bill.amount_numbers,
+            accepted_amount: None,
+            protested_non_acceptance: false,
+            protested_amount: None,
+            maturity_date: parse_maturity_date(&bill.maturity_date),
+            recourses: Vec::new(),
+            open_recourse: false,
+            rotations,
+        }
+    }
+
+    /// Takes the changes of a block with `block_data` appended to the chain.
+    pub fn apply(&mut self, block_data: &BlockData) {
+        match block_data {
+            BlockData::Endorse { endorsee, endorser } => {
+                self.add_obligor(endorser);
+                self.holder = endorsee.clone();
+            }
+            BlockData::RequestToAval {
+                requester,
+                guarantor,
+            } => self
+                .aval_requests
+                .push((requester.clone(), guarantor.clone())),
+            BlockData::Aval {
+                guarantor,
+                guaranteed,
+            } => {
+                self.aval_requests.retain(|(requester, asked)| {
+                    requester.peer_id != guaranteed.peer_id || asked.peer_id != guarantor.peer_id
+                });
+                self.add_obligor(guarantor);
+            }
+            BlockData::Accept { .. } => self.accepted_amount = Some(self.amount),
+            BlockData::PartialAccept { amount, .. } => self.accepted_amount = Some(*amount),
+            BlockData::ProtestNonAcceptance { protest } => {
+                self.protested_non_acceptance = true;
+                self.add_protested_amount(protest.amount_numbers);
+            }
+            BlockData::ProtestNonPayment { protest } => {
+                self.add_protested_amount(protest.amount_numbers)
+            }
+            BlockData::Recourse { recourse } => {
+                self.recourses.push(recourse.clone());
+                self.open_recourse = true;
+            }
+            BlockData::RecoursePaid { .. } => self.open_recourse = false,
+            _ => {}
+        }
+    }
+
+    fn add_protested_amount(&mut self, amount: u64) {
+        self.protested_amount = Some(self.protested_amount.unwrap_or(0).saturating_add(amount));
+    }
+
+    /// Whether the holder may take `recourse` on `date`: after a protest, against a liable
+    /// party nobody took recourse against yet, while no other recourse is open and over
+    /// the protested amount with the interest until its date.
+    pub fn may_take_recourse(&self, recourse: &RecourseClaim, date: NaiveDate) -> bool {
+        let recoursee = &recourse.recoursee.peer_id;
+        let is_liable = *recoursee != self.drawee.peer_id
+            && *recoursee != recourse.recourser.peer_id
+            && self
+                .obligors
+                .iter()
+                .any(|obligor| obligor.peer_id == *recoursee);
+        let is_claimed = self
+            .recourses
+            .iter()
+            .any(|claim| claim.recoursee.peer_id == *recoursee);
+        let is_dated = parse_maturity_date(&recourse.date_of_recourse)
+            .is_some_and(|date_of_recourse| date_of_recourse <= date);
+
+        !self.open_recourse
+            && is_liable
+            && !is_claimed
+            && is_dated
+            && self.protested_amount == Some(recourse.principal)
+            && recourse.has_consistent_amounts(self.maturity_date)
+    }
+
+    /// Whether the drawee may accept `amount`: once, for at most the bill amount and
+    /// not after acceptance was protested.
+    pub fn may_accept(&self, amount: u64) -> bool {
+        self.accepted_amount.is_none()
+            && !self.protested_non_acceptance
+            && (1..=self.amount).contains(&amount)
+    }
+
+    fn add_obligor(&mut self, party: &IdentityPublicData) {
+        if !self
+            .obligors
+            .iter()
+            .any(|obligor| obligor.peer_id == party.peer_id)
+        {
+            self.obligors.push(party.clone());
+        }
+    }
+}
+
+/// Whether `public_key` may sign a block with `block_data` at `timestamp`: only the
+/// drawee accepts and only once, only the holder endorses, requests, protests, takes
+/// recourse or records the payment, only the recourser records the payment of its
+/// recourse, only obligors ask for an aval and only the guarantor asked gives it.
+pub fn may_sign(
+    block_data: &BlockData,
+    public_key: &str,
+    timestamp: i64,
+    state: &ChainState,
+) -> bool {
+    let holder = &state.holder;
+    let is_key_of =
+        |party: &IdentityPublicData| is_key_of(public_key, timestamp, party, &state.rotations);
+    match block_data {
+        // Only the first block issues the bill.
+        BlockData::Issue(_) => false,
+        BlockData::Accept { .. } => state.may_accept(state.amount) && is_key_of(&state.drawee),
+        BlockData::PartialAccept { amount, .. } => {
+            state.may_accept(*amount) && is_key_of(&state.drawee)
+        }
+        BlockData::Endorse { .. }
+        | BlockData::RequestToAccept { .. }
+        | BlockData::RequestToPay { .. }
+        | BlockData::ProtestNonAcceptance { .. }
+        | BlockData::ProtestNonPayment { .. }
+        | BlockData::Paid { .. } => is_key_of(holder),
+        BlockData::Recourse { recourse } => {
+            recourse.recourser.peer_id == holder.peer_id
+                && NaiveDateTime::from_timestamp_opt(timestamp, 0)
+                    .is_some_and(|date_time| state.may_take_recourse(recourse, date_time.date()))
+                && is_key_of(holder)
+        }
+        BlockData::RecoursePaid {
+            recourser,
+            recoursee,
+            ..
+        } => match state.recourses.last() {
+            Some(recourse) => {
+                state.open_recourse
+                    && recourse.recourser.peer_id == recourser.peer_id
+                    && recourse.recoursee.peer_id == recoursee.peer_id
+                    && is_key_of(&recourse.recourser)
+            }
+            None => false,
+        },
+        BlockData::RequestToAval { requester, .. } => state
+            .obligors
+            .iter()
+            .filter(|obligor| obligor.peer_id == requester.peer_id)
+            .any(is_key_of),
+        BlockData::Aval {
+            guarantor,
+            guaranteed,
+        } => state
+            .aval_requests
+            .iter()
+            .filter(|(requester, asked)| {
+                requester.peer_id == guaranteed.peer_id && asked.peer_id == guarantor.peer_id
+            })
+            .any(|(_, asked)| is_key_of(asked)),
+    }
+}
+
+/// Whether `public_key` belongs to `party` and was not retired at `timestamp`, a key it
+/// had when it was named in the bill or one it rotated to since.
+fn is_key_of(
+    public_key: &str,
+    timestamp: i64,
+    party: &IdentityPublicData,
+    rotations: &[KeyRotation],
+) -> bool {
+    !public_key.is_empty()
+        && key_rotation::keys_of(party, rotations)
+            .iter()
+            .any(|key| key == public_key)
+        && key_rotation::is_key_valid_at(rotations, &party.peer_id, public_key, timestamp)
+}
+
+/// RSA signature with the identity key, used by protest documents and old blocks.
 pub fn signature(hash: String, private_key_pem: String) -> String {
     let private_key_bytes = private_key_pem.as_bytes();
     let private_key_rsa = private_key_from_pem_u8(&private_key_bytes.to_vec());
@@ -702,12 +1504,11 @@ pub fn signature(hash: String, private_key_pem: String) -> String {
     signature_readable
 }
 
-pub fn encrypted_hash_data_from_bill(bill: &BitcreditBill, private_key_pem: String) -> String {
-    let bytes = bill.try_to_vec().unwrap();
+pub fn encrypted_block_data(block_data: &BlockData, private_key_pem: String) -> String {
+    let bytes = block_data.to_bytes();
     let key: Rsa<Private> = Rsa::private_key_from_pem(private_key_pem.as_bytes()).unwrap();
     let encrypted_bytes = encrypt_bytes(&bytes, &key);
-    let data_from_bill_hash_readable = hex::encode(encrypted_bytes);
-    data_from_bill_hash_readable
+    hex::encode(encrypted_bytes)
 }
 
 pub fn start_blockchain_for_new_bill(
@@ -719,7 +1520,7 @@ pub fn start_blockchain_for_new_bill(
 ) {
     let genesis_hash: String = hex::encode("GENESIS".to_string().as_bytes());
 
-    let bill_data: String = encrypted_hash_data_from_bill(&bill, private_key_pem);
+    let bill_data: String = encrypted_block_data(&BlockData::Issue(bill.clone()), private_key_pem);
 
     let first_block = Block::new(
         1,
@@ -740,31 +1541,53 @@ pub fn start_blockchain_for_new_bill(
     .unwrap();
 }
 
+/// Whether the branch starting with `candidate` wins over the one starting with `current`:
+/// a block with an attested time beats one without, the earlier attested time wins and the
+/// lower hash breaks ties. The signer sets the block timestamp, so it doesn't count and
+/// nodes that know the same attestations pick the same branch.
+pub fn prefers_branch(candidate: &Block, current: &Block) -> bool {
+    let rank = |block: &Block| {
+        let attested_time = block.attested_time();
+        (
+            attested_time.is_none(),
+            attested_time.unwrap_or(0),
+            block.hash.clone(),
+        )
+    };
+    rank(candidate) < rank(current)
+}
+
 pub fn is_block_valid(block: &Block, previous_block: &Block) -> bool {
     if block.previous_hash != previous_block.hash {
         warn!("block with id: {} has wrong previous hash", block.id);
         return false;
+    } else if block.bill_name != previous_block.bill_name {
+        warn!("block with id: {} is for another bill", block.id);
+        return false;
     } else if block.id != &previous_block.id + 1 {
         warn!(
             "block with id: {} is not the next block after the latest: {}",
             block.id, previous_block.id
         );
         return false;
-    } else if hex::encode(calculate_hash(
-        &block.id,
-        &block.bill_name,
-        &block.previous_hash,
-        &block.data,
-        &block.timestamp,
-        &block.public_key,
-        &block.operation_code,
-    )) != block.hash
-    {
+    } else if hex::encode(calculate_hash(block)) != block.hash {
         warn!("block with id: {} has invalid hash", block.id);
         return false;
     } else if !block.verifier() {
         warn!("block with id: {} has invalid signature", block.id);
         return false;
+    } else if block.timestamp + TIMESTAMP_TOLERANCE_SECONDS < previous_block.timestamp {
+        warn!(
+            "block with id: {} is older than the previous block",
+            block.id
+        );
+        return false;
+    } else if block.timestamp > Utc::now().timestamp() + TIMESTAMP_TOLERANCE_SECONDS {
+        warn!("block with id: {} is timestamped in the future", block.id);
+        return false;
+    } else if !block.has_valid_timestamp_token() {
+        warn!("block with id: {} has an invalid timestamp token", block.id);
+        return false;
     }
     true
 }
@@ -0,0 +1,238 @@
+use std::error::Error;
+use std::fmt;
+
+use bech32::{u5, FromBase32, ToBase32, Variant};
+use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use bitcoin::Network;
+use openssl::sha::sha256;
+
+const PAYMENT_HASH_TAG: u8 = 1;
+const DESCRIPTION_TAG: u8 = 13;
+const PAYEE_TAG: u8 = 19;
+const EXPIRY_TAG: u8 = 6;
+/// Invoices expire after an hour unless they say otherwise.
+const DEFAULT_EXPIRY_SECONDS: u64 = 3600;
+const TIMESTAMP_LENGTH: usize = 7;
+/// 64 bytes of the compact signature and the recovery id.
+const SIGNATURE_LENGTH: usize = 104;
+
+/// The fields of a BOLT11 payment request that bills care about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bolt11Invoice {
+    pub network: Network,
+    pub amount_msat: Option<u64>,
+    pub timestamp: u64,
+    pub payment_hash: [u8; 32],
+    pub description: String,
+    pub expiry: u64,
+    /// Node the payment goes to, it signed the invoice.
+    pub payee: PublicKey,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Bolt11Error {
+    Bech32(String),
+    UnknownNetwork(String),
+    InvalidAmount(String),
+    MissingPaymentHash,
+    InvalidSignature,
+}
+
+impl fmt::Display for Bolt11Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bolt11Error::Bech32(error) => write!(f, "invoice is not bech32: {}", error),
+            Bolt11Error::UnknownNetwork(prefix) => {
+                write!(f, "invoice is for unknown network {:?}", prefix)
+            }
+            Bolt11Error::InvalidAmount(amount) => {
+                write!(f, "invoice amount {:?} is invalid", amount)
+            }
+            Bolt11Error::MissingPaymentHash => write!(f, "invoice has no payment hash"),
+            Bolt11Error::InvalidSignature => write!(f, "invoice signature is invalid"),
+        }
+    }
+}
+
+impl Error for Bolt11Error {}
+
+impl From<bech32::Error> for Bolt11Error {
+    fn from(error: bech32::Error) -> Self {
+        Bolt11Error::Bech32(error.to_string())
+    }
+}
+
+fn network_prefix(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "bc",
+        Network::Testnet => "tb",
+        Network::Signet => "tbs",
+        Network::Regtest => "bcrt",
+        _ => "",
+    }
+}
+
+impl Bolt11Invoice {
+    /// Parses `bolt11` and checks that the payee signed it.
+    pub fn decode(bolt11: &str) -> Result<Self, Bolt11Error> {
+        let (hrp, data, variant) = bech32::decode(bolt11)?;
+        if variant != Variant::Bech32 || data.len() < TIMESTAMP_LENGTH + SIGNATURE_LENGTH {
+            return Err(Bolt11Error::Bech32("invalid length".to_string()));
+        }
+        let (network, amount_msat) = parse_hrp(&hrp)?;
+        let (fields, signature) = data.split_at(data.len() - SIGNATURE_LENGTH);
+
+        let mut payment_hash = None;
+        let mut description = String::new();
+        let mut expiry = DEFAULT_EXPIRY_SECONDS;
+        let mut payee = None;
+        let mut rest = &fields[TIMESTAMP_LENGTH..];
+        while rest.len() >= 3 {
+            let tag = rest[0].to_u8();
+            let length = to_int(&rest[1..3]) as usize;
+            if rest.len() < 3 + length {
+                return Err(Bolt11Error::Bech32("truncated field".to_string()));
+            }
+            let value = &rest[3..3 + length];
+            rest = &rest[3 + length..];
+            // Fields of an unexpected length are skipped, as BOLT11 asks.
+            match (tag, length) {
+                (PAYMENT_HASH_TAG, 52) => {
+                    payment_hash = Vec::<u8>::from_base32(value)?.try_into().ok()
+                }
+                (PAYEE_TAG, 53) => {
+                    payee = PublicKey::from_slice(&Vec::<u8>::from_base32(value)?).ok()
+                }
+                (DESCRIPTION_TAG, _) => {
+                    description = String::from_utf8(Vec::<u8>::from_base32(value)?)
+                        .map_err(|error| Bolt11Error::Bech32(error.to_string()))?
+                }
+                (EXPIRY_TAG, _) => expiry = to_int(value),
+                _ => {}
+            }
+        }
+
+        let signature_bytes = Vec::<u8>::from_base32(signature)?;
+        let recovery_id = RecoveryId::from_i32(signature_bytes[64] as i32)
+            .map_err(|_| Bolt11Error::InvalidSignature)?;
+        let signature = RecoverableSignature::from_compact(&signature_bytes[..64], recovery_id)
+            .map_err(|_| Bolt11Error::InvalidSignature)?;
+        let message = signed_message(&hrp, fields);
+        let secp = Secp256k1::verification_only();
+        let payee = match payee {
+            Some(payee) => secp
+                .verify_ecdsa(&message, &signature.to_standard(), &payee)
+                .map(|_| payee),
+            None => secp.recover_ecdsa(&message, &signature),
+        }
+        .map_err(|_| Bolt11Error::InvalidSignature)?;
+
+        Ok(Self {
+            network,
+            amount_msat,
+            timestamp: to_int(&fields[..TIMESTAMP_LENGTH]),
+            payment_hash: payment_hash.ok_or(Bolt11Error::MissingPaymentHash)?,
+            description,
+            expiry,
+            payee,
+        })
+    }
+
+    /// Payment request signed with `node_key`, which has to be the key of the payee.
+    pub fn encode(&self, node_key: &SecretKey) -> String {
+        let mut hrp = format!("ln{}", network_prefix(self.network));
+        if let Some(amount_msat) = self.amount_msat {
+            // PicoBTC are tenths of a msat.
+            hrp.push_str(&format!("{}p", amount_msat * 10));
+        }
+
+        let mut data = from_int(self.timestamp, TIMESTAMP_LENGTH);
+        let mut push_field = |tag: u8, value: Vec<u5>| {
+            data.push(u5::try_from_u8(tag).unwrap());
+            data.append(&mut from_int(value.len() as u64, 2));
+            data.extend(value);
+        };
+        push_field(PAYMENT_HASH_TAG, self.payment_hash.to_base32());
+        push_field(DESCRIPTION_TAG, self.description.as_bytes().to_base32());
+        push_field(EXPIRY_TAG, from_int(self.expiry, 4));
+        push_field(PAYEE_TAG, self.payee.serialize().to_base32());
+
+        let message = signed_message(&hrp, &data);
+        let (recovery_id, signature) = Secp256k1::signing_only()
+            .sign_ecdsa_recoverable(&message, node_key)
+            .serialize_compact();
+        let mut signature = signature.to_vec();
+        signature.push(recovery_id.to_i32() as u8);
+        data.extend(signature.to_base32());
+
+        bech32::encode(&hrp, data, Variant::Bech32).unwrap()
+    }
+
+    /// Whether the invoice can't be paid anymore at the unix time `now`.
+    pub fn is_expired(&self, now: i64) -> bool {
+        now < 0 || self.timestamp.saturating_add(self.expiry) < now as u64
+    }
+}
+
+/// Network and amount in msat of a human readable part like `lntb2500u`.
+fn parse_hrp(hrp: &str) -> Result<(Network, Option<u64>), Bolt11Error> {
+    let rest = hrp
+        .strip_prefix("ln")
+        .ok_or_else(|| Bolt11Error::UnknownNetwork(hrp.to_string()))?;
+    let amount_start = rest
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (prefix, amount) = rest.split_at(amount_start);
+    let network = [
+        Network::Bitcoin,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ]
+    .into_iter()
+    .find(|network| network_prefix(*network) == prefix)
+    .ok_or_else(|| Bolt11Error::UnknownNetwork(prefix.to_string()))?;
+    if amount.is_empty() {
+        return Ok((network, None));
+    }
+
+    let invalid = || Bolt11Error::InvalidAmount(amount.to_string());
+    let (digits, multiplier) = match amount.char_indices().last() {
+        Some((index, multiplier)) if !multiplier.is_ascii_digit() => {
+            (&amount[..index], Some(multiplier))
+        }
+        _ => (amount, None),
+    };
+    let value: u64 = digits.parse().map_err(|_| invalid())?;
+    let amount_msat = match multiplier {
+        None => value.checked_mul(100_000_000_000),
+        Some('m') => value.checked_mul(100_000_000),
+        Some('u') => value.checked_mul(100_000),
+        Some('n') => value.checked_mul(100),
+        Some('p') if value.is_multiple_of(10) => Some(value / 10),
+        Some(_) => None,
+    };
+    amount_msat
+        .map(|amount_msat| (network, Some(amount_msat)))
+        .ok_or_else(invalid)
+}
+
+/// The payee signs the SHA-256 of the human readable part and the data in bytes.
+fn signed_message(hrp: &str, data: &[u5]) -> Message {
+    let mut bytes = hrp.as_bytes().to_vec();
+    bytes.append(&mut bech32::convert_bits(data, 5, 8, true).unwrap());
+    Message::from_slice(&sha256(&bytes)).unwrap()
+}
+
+fn to_int(data: &[u5]) -> u64 {
+    data.iter()
+        .fold(0, |value, group| value << 5 | group.to_u8() as u64)
+}
+
+fn from_int(value: u64, length: usize) -> Vec<u5> {
+    (0..length)
+        .rev()
+        .map(|index| u5::try_from_u8((value >> (5 * index) & 31) as u8).unwrap())
+        .collect()
+}
@@ -12,19 +12,63 @@ pub const BOOTSTRAP_FOLDER_PATH: &str = "bootstrap";
 pub const IDENTITY_FILE_PATH: &str = "identity/identity";
 pub const IDENTITY_PEER_ID_FILE_PATH: &str = "identity/peer_id";
 pub const IDENTITY_ED_25529_KEYS_FILE_PATH: &str = "identity/ed25519_keys";
+pub const IDENTITY_SEED_FILE_PATH: &str = "identity/seed";
+pub const IDENTITY_KEY_ROTATIONS_FILE_PATH: &str = "identity/key_rotations";
+pub const IDENTITY_RETIRED_KEYS_FILE_PATH: &str = "identity/retired_keys";
+pub const KEYSTORE_FILE_PATH: &str = "identity/keystore";
+pub const PASSPHRASE_ENV_VAR: &str = "BITCREDIT_PASSPHRASE";
+pub const TSA_URL_ENV_VAR: &str = "BITCREDIT_TSA_URL";
+pub const CHAIN_BACKEND_ENV_VAR: &str = "BITCREDIT_CHAIN_BACKEND";
+pub const ESPLORA_URL_ENV_VAR: &str = "BITCREDIT_ESPLORA_URL";
+pub const ELECTRUM_URL_ENV_VAR: &str = "BITCREDIT_ELECTRUM_URL";
+pub const BITCOIND_URL_ENV_VAR: &str = "BITCREDIT_BITCOIND_URL";
+pub const BITCOIND_USER_ENV_VAR: &str = "BITCREDIT_BITCOIND_USER";
+pub const BITCOIND_PASSWORD_ENV_VAR: &str = "BITCREDIT_BITCOIND_PASSWORD";
+pub const BITCOIND_WALLET_ENV_VAR: &str = "BITCREDIT_BITCOIND_WALLET";
+pub const ANCHOR_INTERVAL_ENV_VAR: &str = "BITCREDIT_ANCHOR_INTERVAL_SECONDS";
+pub const BILL_ANCHORS_FOLDER_PATH: &str = "bill_anchors";
+/// Fee rate of anchoring transactions in sat/vB.
+pub const ANCHOR_FEE_RATE: u64 = 2;
+pub const LIGHTNING_NODE_ENV_VAR: &str = "BITCREDIT_LIGHTNING_NODE";
+pub const LND_URL_ENV_VAR: &str = "BITCREDIT_LND_URL";
+pub const LND_MACAROON_ENV_VAR: &str = "BITCREDIT_LND_MACAROON";
+pub const LND_CERT_ENV_VAR: &str = "BITCREDIT_LND_CERT";
+pub const CLN_URL_ENV_VAR: &str = "BITCREDIT_CLN_URL";
+pub const CLN_RUNE_ENV_VAR: &str = "BITCREDIT_CLN_RUNE";
+pub const BILL_INVOICES_FOLDER_PATH: &str = "bill_invoices";
+pub const LIGHTNING_INVOICE_EXPIRY_SECONDS: u64 = 3600;
+pub const PAYMENT_ADDRESS_TYPE_ENV_VAR: &str = "BITCREDIT_PAYMENT_ADDRESS_TYPE";
+pub const PAYMENT_CONFIRMATIONS_ENV_VAR: &str = "BITCREDIT_PAYMENT_CONFIRMATIONS";
+/// Depth a payment needs before the holder records the bill as paid.
+pub const PAYMENT_CONFIRMATIONS: u64 = 6;
+pub const PAYMENT_WATCH_INTERVAL_SECONDS: u64 = 60;
+/// Blocks within which a payout should confirm, for the fee estimate.
+pub const PAYOUT_CONFIRMATION_TARGET: u16 = 6;
+pub const TIMESTAMP_AUTHORITIES_FOLDER_PATH: &str = "timestamp_authorities";
+/// Clock skew tolerated between block timestamps.
+pub const TIMESTAMP_TOLERANCE_SECONDS: i64 = 300;
 pub const CONTACT_MAP_FILE_PATH: &str = "contacts/contacts";
 pub const BOOTSTRAP_NODES_FILE_PATH: &str = "bootstrap/bootstrap_nodes.json";
+pub const RSA_KEY_BITS: u32 = 2048;
+pub const ED25519_DERIVATION_PATH: &str = "m/1908'/0'";
+pub const RSA_DERIVATION_PATH: &str = "m/1908'/1'";
 pub const BTC: &str = "BTC";
 pub const mBTC: &str = "mBTC";
 pub const SATOSHI: &str = "sats";
 pub const BILLS_PREFIX: &str = "BILLS";
+pub const KEY_ROTATIONS_PREFIX: &str = "ROTATION";
 pub const TESTNET: Network = Network::Testnet;
 pub const MAINNET: Network = Network::Bitcoin;
 pub const USEDNET: Network = TESTNET;
 pub const BILL_VALIDITY_PERIOD: u64 = 90;
+pub const PROTEST_NON_PAYMENT_PERIOD_DAYS: u64 = 2;
+pub const RECOURSE_INTEREST_RATE_PERCENT: u64 = 6;
+pub const DAYS_IN_YEAR: u64 = 365;
+pub const BASIS_POINTS_IN_ONE: u64 = 10000;
+// Highest yearly interest rate of a bill, 100%.
+pub const MAX_INTEREST_RATE_BASIS_POINTS: u64 = BASIS_POINTS_IN_ONE;
 pub const NUMBER_SATOSHI_IN_BTC: u64 = 100000000;
 pub const NUMBER_SATOSHI_IN_mBTC: u64 = 100000;
-pub const COMPOUNDING_INTEREST_RATE_ZERO: u64 = 0;
 pub const TCP_PORT_TO_LISTEN: u16 = 1908;
 //NODE ONE /ip4/45.147.248.87/tcp/1908/p2p/12D3KooWFvRxAazxdKVB7SsTtcLTnvmF8brtW2kQRhceohtgcJv2
 pub const RELAY_BOOTSTRAP_NODE_ONE_IP: Ipv4Addr = Ipv4Addr::new(45, 147, 248, 87);
@@ -61,9 +61,11 @@ pub mod network {
     use crate::constants::{
         BILLS_FOLDER_PATH, BILLS_KEYS_FOLDER_PATH, BILLS_PREFIX, BOOTSTRAP_NODES_FILE_PATH,
         IDENTITY_ED_25529_KEYS_FILE_PATH, IDENTITY_FILE_PATH, IDENTITY_PEER_ID_FILE_PATH,
-        RELAY_BOOTSTRAP_NODE_ONE_IP, RELAY_BOOTSTRAP_NODE_ONE_PEER_ID,
-        RELAY_BOOTSTRAP_NODE_ONE_TCP, TCP_PORT_TO_LISTEN,
+        IDENTITY_SEED_FILE_PATH, KEY_ROTATIONS_PREFIX, RELAY_BOOTSTRAP_NODE_ONE_IP,
+        RELAY_BOOTSTRAP_NODE_ONE_PEER_ID, RELAY_BOOTSTRAP_NODE_ONE_TCP, TCP_PORT_TO_LISTEN,
     };
+    use crate::key_rotation::{self, KeyRotation};
+    use crate::keystore;
     use crate::{
         decrypt_bytes_with_private_key, encrypt_bytes_with_public_key, generate_dht_logic,
         get_bills, get_whole_identity, read_ed25519_keypair_from_file, read_peer_id_from_file,
@@ -73,8 +75,11 @@ pub mod network {
     use super::*;
 
     pub async fn new() -> Result<(Client, Receiver<Event>, EventLoop), Box<dyn Error>> {
+        // Keys generated before seed phrases are replaced while no identity uses them.
         if !Path::new(IDENTITY_PEER_ID_FILE_PATH).exists()
             && !Path::new(IDENTITY_ED_25529_KEYS_FILE_PATH).exists()
+            || !Path::new(IDENTITY_FILE_PATH).exists()
+                && !Path::new(IDENTITY_SEED_FILE_PATH).exists()
         {
             generate_dht_logic();
         }
@@ -291,11 +296,19 @@ pub mod network {
                         if !key_bytes.is_empty() {
                             let pr_key = get_whole_identity().identity.private_key_pem;
 
-                            let key_bytes_decrypted =
-                                decrypt_bytes_with_private_key(&key_bytes, pr_key);
-
-                            let path = BILLS_KEYS_FOLDER_PATH.to_string() + "/" + bill_id + ".json";
-                            fs::write(path, key_bytes_decrypted).expect("Can't write file.");
+                            match decrypt_bytes_with_private_key(&key_bytes, pr_key) {
+                                Ok(key_bytes_decrypted) => {
+                                    let path = BILLS_KEYS_FOLDER_PATH.to_string()
+                                        + "/"
+                                        + bill_id
+                                        + ".json";
+                                    keystore::write_file(&path, &key_bytes_decrypted)
+                                        .expect("Can't write file.");
+                                }
+                                Err(error) => {
+                                    println!("Can't decrypt keys of bill {bill_id}: {error}")
+                                }
+                            }
                         }
 
                         self.sender
@@ -437,9 +450,37 @@ pub mod network {
             let identity_public_data: IdentityPublicData =
                 serde_json::from_str(&current_info_string).unwrap();
 
+            let rotations = self.get_key_rotations_from_dht(peer_id.clone()).await;
+            key_rotation::add_key_rotations(rotations, &identity_public_data);
+
             identity_public_data
         }
 
+        /// Publishes the history of our key rotations.
+        pub async fn put_key_rotations_in_dht(&mut self) {
+            let peer_id = read_peer_id_from_file().to_string();
+            let rotations = key_rotation::rotations_of(&peer_id);
+            if !rotations.is_empty() {
+                let key = KEY_ROTATIONS_PREFIX.to_string() + &peer_id;
+                let value = serde_json::to_string(&rotations).unwrap();
+                self.put_record(key, value).await;
+            }
+        }
+
+        pub async fn get_key_rotations_from_dht(&mut self, peer_id: String) -> Vec<KeyRotation> {
+            let key = KEY_ROTATIONS_PREFIX.to_string() + &peer_id;
+            let value = self.get_record(key).await.value;
+            if value.is_empty() {
+                return Vec::new();
+            }
+            // Anyone can put a record under the key, only the rotations of the peer count.
+            let rotations: Vec<KeyRotation> = serde_json::from_slice(&value).unwrap_or_default();
+            rotations
+                .into_iter()
+                .filter(|rotation| rotation.peer_id == peer_id)
+                .collect()
+        }
+
         pub async fn add_bill_to_dht_for_node(&mut self, bill_name: &String, node_id: &String) {
             let node_request = BILLS_PREFIX.to_string() + node_id;
             let mut record_for_saving_in_dht = String::new();
@@ -650,7 +691,7 @@ pub mod network {
                                     request.splitn(2, "KEY_").collect::<Vec<&str>>()[1].to_string();
                                 let path_to_key =
                                     BILLS_KEYS_FOLDER_PATH.to_string() + "/" + &key_name + ".json";
-                                let file = std::fs::read(&path_to_key).unwrap();
+                                let file = keystore::read_file(&path_to_key).unwrap();
                                 //TODO: encrypt key file
 
                                 let file_encrypted =
@@ -1092,19 +1133,39 @@ pub mod network {
                     println!(
                         "Got message with id: {id} from peer: {peer_id} in topic: {bill_name}",
                     );
-                    let event = GossipsubEvent::from_byte_array(&message.data);
+                    let event = match GossipsubEvent::from_byte_array(&message.data) {
+                        Ok(event) => event,
+                        Err(error) => {
+                            println!("Invalid message with id: {id} from peer: {peer_id}: {error}");
+                            return;
+                        }
+                    };
 
                     if event.id.eq(&GossipsubEventId::Block) {
-                        let block: Block =
-                            serde_json::from_slice(&event.message).expect("Block are not valid.");
+                        let block: Block = match serde_json::from_slice(&event.message) {
+                            Ok(block) => block,
+                            Err(error) => {
+                                println!("Invalid block from peer: {peer_id}: {error}");
+                                return;
+                            }
+                        };
                         let mut chain: Chain = Chain::read_chain_from_file(&bill_name);
-                        chain.try_add_block(block);
-                        if chain.is_chain_valid() {
-                            chain.write_chain_to_file(&bill_name);
+                        if let Some(branch) = chain.branch_with(&block) {
+                            chain.compare_chain(branch, &bill_name);
+                        } else {
+                            chain.try_add_block(block);
+                            if chain.is_chain_valid() {
+                                chain.write_chain_to_file(&bill_name);
+                            }
                         }
                     } else if event.id.eq(&GossipsubEventId::Chain) {
-                        let receive_chain: Chain =
-                            serde_json::from_slice(&event.message).expect("Chain are not valid.");
+                        let receive_chain: Chain = match serde_json::from_slice(&event.message) {
+                            Ok(chain) => chain,
+                            Err(error) => {
+                                println!("Invalid chain from peer: {peer_id}: {error}");
+                                return;
+                            }
+                        };
                         let mut local_chain = Chain::read_chain_from_file(&bill_name);
                         local_chain.compare_chain(receive_chain, &bill_name);
                     } else if event.id.eq(&GossipsubEventId::CommandGetChain) {
@@ -0,0 +1,145 @@
+use std::error::Error;
+use std::fmt;
+
+use openssl::error::ErrorStack;
+use openssl::pkey::{HasPrivate, HasPublic};
+use openssl::rand::rand_bytes;
+use openssl::rsa::{Padding, Rsa};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+/// Start of every envelope, followed by [`ENVELOPE_VERSION`].
+const ENVELOPE_MAGIC: &[u8; 4] = b"BCE\x00";
+pub const ENVELOPE_VERSION: u8 = 1;
+const CONTENT_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    Malformed,
+    UnsupportedVersion(u8),
+    Crypto(ErrorStack),
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::Malformed => write!(f, "encrypted data is malformed"),
+            EncryptionError::UnsupportedVersion(version) => {
+                write!(f, "envelope version {} is not supported", version)
+            }
+            EncryptionError::Crypto(error) => write!(f, "decryption failed: {}", error),
+        }
+    }
+}
+
+impl Error for EncryptionError {}
+
+impl From<ErrorStack> for EncryptionError {
+    fn from(error: ErrorStack) -> Self {
+        EncryptionError::Crypto(error)
+    }
+}
+
+/// Encrypts `plaintext` with a random AES-256-GCM content key wrapped with RSA-OAEP.
+///
+/// Layout: magic, version, wrapped key length (u16 big endian), wrapped key, nonce,
+/// ciphertext, tag. Magic and version are authenticated as additional data.
+pub fn seal<T: HasPublic>(plaintext: &[u8], key: &Rsa<T>) -> Result<Vec<u8>, ErrorStack> {
+    let mut content_key = [0; CONTENT_KEY_LEN];
+    rand_bytes(&mut content_key)?;
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+
+    let mut wrapped_key = vec![0; key.size() as usize];
+    let wrapped_len = key.public_encrypt(&content_key, &mut wrapped_key, Padding::PKCS1_OAEP)?;
+    wrapped_key.truncate(wrapped_len);
+
+    let header = header();
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &content_key,
+        Some(&nonce),
+        &header,
+        plaintext,
+        &mut tag,
+    )?;
+
+    let mut envelope = header;
+    envelope.extend_from_slice(&(wrapped_key.len() as u16).to_be_bytes());
+    envelope.extend_from_slice(&wrapped_key);
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+    envelope.extend_from_slice(&tag);
+    Ok(envelope)
+}
+
+/// Decrypts an envelope written by [`seal`]. Data without the envelope magic is read as
+/// the chunked RSA PKCS#1 format that older bills were written in.
+pub fn open<T: HasPrivate>(bytes: &[u8], key: &Rsa<T>) -> Result<Vec<u8>, EncryptionError> {
+    match bytes.strip_prefix(ENVELOPE_MAGIC) {
+        Some(rest) => match open_envelope(rest, key) {
+            // Old data may start with the magic by chance.
+            Err(error) => open_legacy(bytes, key).map_err(|_| error),
+            plaintext => plaintext,
+        },
+        None => open_legacy(bytes, key),
+    }
+}
+
+fn header() -> Vec<u8> {
+    let mut header = ENVELOPE_MAGIC.to_vec();
+    header.push(ENVELOPE_VERSION);
+    header
+}
+
+fn open_envelope<T: HasPrivate>(bytes: &[u8], key: &Rsa<T>) -> Result<Vec<u8>, EncryptionError> {
+    let (version, rest) = bytes.split_first().ok_or(EncryptionError::Malformed)?;
+    if *version != ENVELOPE_VERSION {
+        return Err(EncryptionError::UnsupportedVersion(*version));
+    }
+    if rest.len() < 2 {
+        return Err(EncryptionError::Malformed);
+    }
+    let (wrapped_len, rest) = rest.split_at(2);
+    let wrapped_len = u16::from_be_bytes([wrapped_len[0], wrapped_len[1]]) as usize;
+    if rest.len() < wrapped_len + NONCE_LEN + TAG_LEN {
+        return Err(EncryptionError::Malformed);
+    }
+    let (wrapped_key, rest) = rest.split_at(wrapped_len);
+    let (nonce, rest) = rest.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    let mut content_key = vec![0; key.size() as usize];
+    let content_key_len =
+        key.private_decrypt(wrapped_key, &mut content_key, Padding::PKCS1_OAEP)?;
+    if content_key_len != CONTENT_KEY_LEN {
+        return Err(EncryptionError::Malformed);
+    }
+
+    Ok(decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &content_key[..CONTENT_KEY_LEN],
+        Some(nonce),
+        &header(),
+        ciphertext,
+        tag,
+    )?)
+}
+
+/// Every chunk of the key size is decrypted on its own.
+fn open_legacy<T: HasPrivate>(bytes: &[u8], key: &Rsa<T>) -> Result<Vec<u8>, EncryptionError> {
+    let key_size = key.size() as usize;
+    if !bytes.len().is_multiple_of(key_size) {
+        return Err(EncryptionError::Malformed);
+    }
+
+    let mut plaintext = Vec::new();
+    let mut buffer = vec![0; key_size];
+    for chunk in bytes.chunks(key_size) {
+        let len = key.private_decrypt(chunk, &mut buffer, Padding::PKCS1)?;
+        plaintext.extend_from_slice(&buffer[..len]);
+    }
+    Ok(plaintext)
+}
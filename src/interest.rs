@@ -0,0 +1,60 @@
+use chrono::NaiveDate;
+
+use crate::amount::AmountError;
+use crate::constants::{BASIS_POINTS_IN_ONE, DAYS_IN_YEAR};
+use crate::protest::parse_maturity_date;
+use crate::BitcreditBill;
+
+/// Amount due on `payment_date` for `principal` of `bill`. Interest accrues from the
+/// date of issue to the maturity date, or up to the payment date when it is paid late.
+pub fn amount_due(
+    bill: &BitcreditBill,
+    principal: u64,
+    payment_date: NaiveDate,
+) -> Result<u64, AmountError> {
+    if bill.compounding_interest_rate == 0 {
+        return Ok(principal);
+    }
+    let date_of_issue = match parse_maturity_date(&bill.date_of_issue) {
+        Some(date_of_issue) => date_of_issue,
+        None => return Ok(principal),
+    };
+    // A sight bill that was not presented yet falls due when it is paid.
+    let maturity_date = parse_maturity_date(&bill.maturity_date).unwrap_or(payment_date);
+
+    let days = (maturity_date.max(payment_date) - date_of_issue)
+        .num_days()
+        .max(0) as u64;
+    let interest = interest(
+        principal,
+        bill.compounding_interest_rate,
+        bill.type_of_interest_calculation,
+        days,
+    )?;
+    principal.checked_add(interest).ok_or(AmountError::Overflow)
+}
+
+/// Interest on `principal` for `days` at a yearly `rate_basis_points`, simple or
+/// compounded once a year. Interest that does not fit the amount is an overflow.
+pub fn interest(
+    principal: u64,
+    rate_basis_points: u64,
+    compound: bool,
+    days: u64,
+) -> Result<u64, AmountError> {
+    if compound {
+        let rate = rate_basis_points as f64 / BASIS_POINTS_IN_ONE as f64;
+        let years = days as f64 / DAYS_IN_YEAR as f64;
+        let interest = (principal as f64 * ((1.0 + rate).powf(years) - 1.0)).floor();
+        // Casts saturate, so the largest amounts are out of range too.
+        if interest.is_finite() && interest < u64::MAX as f64 {
+            Ok(interest as u64)
+        } else {
+            Err(AmountError::Overflow)
+        }
+    } else {
+        let interest = principal as u128 * rate_basis_points as u128 * days as u128
+            / (BASIS_POINTS_IN_ONE * DAYS_IN_YEAR) as u128;
+        u64::try_from(interest).map_err(|_| AmountError::Overflow)
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::Path;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::Utc;
+use libp2p::identity::{Keypair, PublicKey};
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::{schnorr_signature, verify_schnorr_signature};
+use crate::constants::IDENTITY_KEY_ROTATIONS_FILE_PATH;
+use crate::{Identity, IdentityPublicData};
+
+/// Links the old RSA and bitcoin keys of an identity to new ones. It is signed with the
+/// peer key, which does not rotate, and with the new bitcoin key to show it is held.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyRotation {
+    pub peer_id: String,
+    pub peer_public_key: String,
+    pub old_bitcoin_public_key: String,
+    pub new_bitcoin_public_key: String,
+    pub old_rsa_public_key_pem: String,
+    pub new_rsa_public_key_pem: String,
+    pub timestamp: i64,
+    /// Signatures of the old keys from this time on are rejected. It is before
+    /// `timestamp` when the old keys were revoked because they leaked.
+    pub valid_until: i64,
+    pub revoked: bool,
+    pub peer_signature: String,
+    pub new_key_signature: String,
+}
+
+impl KeyRotation {
+    pub fn new(
+        peer_key: &Keypair,
+        old_identity: &Identity,
+        new_identity: &Identity,
+        compromised_since: Option<i64>,
+    ) -> Self {
+        let timestamp = Utc::now().timestamp();
+        let mut rotation = Self {
+            peer_id: peer_key.public().to_peer_id().to_string(),
+            peer_public_key: hex::encode(peer_key.public().encode_protobuf()),
+            old_bitcoin_public_key: old_identity.bitcoin_public_key.clone(),
+            new_bitcoin_public_key: new_identity.bitcoin_public_key.clone(),
+            old_rsa_public_key_pem: old_identity.public_key_pem.clone(),
+            new_rsa_public_key_pem: new_identity.public_key_pem.clone(),
+            timestamp,
+            valid_until: compromised_since.map_or(timestamp, |since| since.min(timestamp)),
+            revoked: compromised_since.is_some(),
+            peer_signature: String::new(),
+            new_key_signature: String::new(),
+        };
+        let digest = rotation.digest();
+        rotation.peer_signature = hex::encode(
+            peer_key
+                .sign(&hex::decode(&digest).unwrap())
+                .expect("Unable to sign key rotation"),
+        );
+        rotation.new_key_signature = schnorr_signature(&digest, &new_identity.bitcoin_private_key);
+        rotation
+    }
+
+    /// Hex encoded sha256 of the rotation without its signatures.
+    fn digest(&self) -> String {
+        let mut unsigned = self.clone();
+        unsigned.peer_signature = String::new();
+        unsigned.new_key_signature = String::new();
+        hex::encode(sha256(&unsigned.try_to_vec().unwrap()))
+    }
+
+    pub fn verify(&self) -> bool {
+        let peer_public_key = match hex::decode(&self.peer_public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::try_decode_protobuf(&bytes).ok())
+        {
+            Some(public_key) => public_key,
+            None => return false,
+        };
+        let peer_signature = hex::decode(&self.peer_signature).unwrap_or_default();
+        let digest = self.digest();
+
+        peer_public_key.to_peer_id().to_string() == self.peer_id
+            && self.valid_until <= self.timestamp
+            && peer_public_key.verify(&hex::decode(&digest).unwrap(), &peer_signature)
+            && verify_schnorr_signature(
+                &digest,
+                &self.new_key_signature,
+                &self.new_bitcoin_public_key,
+            )
+    }
+
+    /// Whether a signature of `public_key` made at `timestamp` is accepted after this
+    /// rotation.
+    pub fn accepts(&self, public_key: &str, timestamp: i64) -> bool {
+        let retired =
+            self.old_bitcoin_public_key == public_key || self.old_rsa_public_key_pem == public_key;
+        !retired || timestamp < self.valid_until
+    }
+}
+
+/// Rotations of all identities we know, ours and the ones read from the DHT.
+pub fn read_key_rotations() -> Vec<KeyRotation> {
+    if !Path::new(IDENTITY_KEY_ROTATIONS_FILE_PATH).exists() {
+        return Vec::new();
+    }
+    let data = fs::read(IDENTITY_KEY_ROTATIONS_FILE_PATH).expect("Unable to read key rotations");
+    serde_json::from_slice(&data).expect("Unable to parse key rotations")
+}
+
+fn write_key_rotations(rotations: &Vec<KeyRotation>) {
+    fs::write(
+        IDENTITY_KEY_ROTATIONS_FILE_PATH,
+        serde_json::to_string_pretty(rotations).unwrap(),
+    )
+    .expect("Unable to write key rotations");
+}
+
+/// Rotations of `peer_id`, oldest first.
+pub fn rotations_of(peer_id: &str) -> Vec<KeyRotation> {
+    read_key_rotations()
+        .into_iter()
+        .filter(|rotation| rotation.peer_id == peer_id)
+        .collect()
+}
+
+/// Adds the rotations of `party` that are not known yet.
+pub fn add_key_rotations(new_rotations: Vec<KeyRotation>, party: &IdentityPublicData) {
+    let known = read_key_rotations();
+    let rotations = with_rotations_of(known.clone(), new_rotations, party);
+    if rotations.len() != known.len() {
+        write_key_rotations(&rotations);
+    }
+}
+
+/// `rotations` with the new rotations of `party` it takes. A rotation is only taken when
+/// it is signed correctly by the peer of `party` and starts from the keys the last known
+/// rotation of the peer ended with. The first one has to start from the keys of the
+/// identity record of `party`, or lead to them when the record was published after
+/// rotating.
+pub fn with_rotations_of(
+    mut rotations: Vec<KeyRotation>,
+    mut new_rotations: Vec<KeyRotation>,
+    party: &IdentityPublicData,
+) -> Vec<KeyRotation> {
+    new_rotations.retain(|rotation| rotation.peer_id == party.peer_id && rotation.verify());
+    new_rotations.sort_by_key(|rotation| rotation.timestamp);
+    let record_rotated_to = new_rotations.iter().any(|rotation| {
+        rotation.new_bitcoin_public_key == party.bitcoin_public_key
+            && rotation.new_rsa_public_key_pem == party.rsa_public_key_pem
+    });
+
+    for rotation in new_rotations {
+        if rotations.contains(&rotation) {
+            continue;
+        }
+        let follows_last = match rotations
+            .iter()
+            .rev()
+            .find(|known| known.peer_id == rotation.peer_id)
+        {
+            Some(last) => {
+                last.new_bitcoin_public_key == rotation.old_bitcoin_public_key
+                    && last.new_rsa_public_key_pem == rotation.old_rsa_public_key_pem
+                    && last.timestamp <= rotation.timestamp
+            }
+            None => {
+                record_rotated_to
+                    || (rotation.old_bitcoin_public_key == party.bitcoin_public_key
+                        && rotation.old_rsa_public_key_pem == party.rsa_public_key_pem)
+            }
+        };
+        if follows_last {
+            rotations.push(rotation);
+        }
+    }
+    rotations
+}
+
+/// Bitcoin and RSA keys of the party, the ones it is named with and the ones linked to
+/// them by its rotations among `rotations`.
+pub fn keys_of(party: &IdentityPublicData, rotations: &[KeyRotation]) -> Vec<String> {
+    let mut keys = vec![
+        party.bitcoin_public_key.clone(),
+        party.rsa_public_key_pem.clone(),
+    ];
+    for rotation in rotations
+        .iter()
+        .filter(|rotation| rotation.peer_id == party.peer_id)
+    {
+        keys.extend([
+            rotation.old_bitcoin_public_key.clone(),
+            rotation.new_bitcoin_public_key.clone(),
+            rotation.old_rsa_public_key_pem.clone(),
+            rotation.new_rsa_public_key_pem.clone(),
+        ]);
+    }
+    keys
+}
+
+/// Whether a signature of `public_key` made at `timestamp` is accepted from `peer_id`.
+/// Only the rotations of that peer among `rotations` can retire its keys.
+pub fn is_key_valid_at(
+    rotations: &[KeyRotation],
+    peer_id: &str,
+    public_key: &str,
+    timestamp: i64,
+) -> bool {
+    rotations
+        .iter()
+        .filter(|rotation| rotation.peer_id == peer_id)
+        .all(|rotation| rotation.accepts(public_key, timestamp))
+}
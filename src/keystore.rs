@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::RwLock;
+use std::{fmt, fs, io};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use openssl::error::ErrorStack;
+use openssl::pkcs5::scrypt;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use crate::constants::{
+    BILLS_KEYS_FOLDER_PATH, IDENTITY_ED_25529_KEYS_FILE_PATH, IDENTITY_FILE_PATH,
+    IDENTITY_RETIRED_KEYS_FILE_PATH, IDENTITY_SEED_FILE_PATH, KEYSTORE_FILE_PATH,
+    TIMESTAMP_AUTHORITIES_FOLDER_PATH,
+};
+use crate::timestamp::LOCAL_KEY_FILE;
+
+/// Start of every file encrypted with the data key, followed by [`KEYSTORE_VERSION`].
+const ENCRYPTED_FILE_MAGIC: &[u8; 4] = b"BCK\x00";
+pub const KEYSTORE_VERSION: u8 = 1;
+pub const DATA_KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const SCRYPT_LOG_N: u8 = 15;
+/// Highest cost read from a keystore file, the memory limit stops it well before.
+const SCRYPT_MAX_LOG_N: u8 = 20;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const SCRYPT_MAX_MEMORY: u64 = 64 * 1024 * 1024;
+
+/// Key the identity and bill key files are encrypted with while the keystore is unlocked.
+static DATA_KEY: RwLock<Option<[u8; DATA_KEY_LEN]>> = RwLock::new(None);
+
+/// Random data key, encrypted with a key derived from the passphrase with scrypt.
+/// Changing the passphrase only encrypts the data key again.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct KeystoreFile {
+    version: u8,
+    salt: Vec<u8>,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    nonce: Vec<u8>,
+    wrapped_key: Vec<u8>,
+}
+
+impl KeystoreFile {
+    pub fn new(data_key: &[u8], passphrase: &str) -> Result<Self, KeystoreError> {
+        let mut salt = vec![0; SALT_LEN];
+        rand_bytes(&mut salt)?;
+        let mut nonce = vec![0; NONCE_LEN];
+        rand_bytes(&mut nonce)?;
+        let key = passphrase_key(passphrase, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+
+        let mut tag = [0; TAG_LEN];
+        let mut wrapped_key = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&nonce),
+            &[KEYSTORE_VERSION],
+            data_key,
+            &mut tag,
+        )?;
+        wrapped_key.extend_from_slice(&tag);
+
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            salt,
+            log_n: SCRYPT_LOG_N,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            nonce,
+            wrapped_key,
+        })
+    }
+
+    pub fn data_key(&self, passphrase: &str) -> Result<[u8; DATA_KEY_LEN], KeystoreError> {
+        if self.wrapped_key.len() != DATA_KEY_LEN + TAG_LEN || self.log_n > SCRYPT_MAX_LOG_N {
+            return Err(KeystoreError::Malformed);
+        }
+        let key = passphrase_key(passphrase, &self.salt, self.log_n, self.r, self.p)?;
+        let (ciphertext, tag) = self.wrapped_key.split_at(DATA_KEY_LEN);
+        let data_key = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&self.nonce),
+            &[self.version],
+            ciphertext,
+            tag,
+        )
+        .map_err(|_| KeystoreError::WrongPassphrase)?;
+
+        let mut unwrapped = [0; DATA_KEY_LEN];
+        unwrapped.copy_from_slice(&data_key);
+        Ok(unwrapped)
+    }
+}
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    WrongPassphrase,
+    EmptyPassphrase,
+    Locked,
+    Malformed,
+    Unencrypted,
+    Crypto(ErrorStack),
+    Io(io::Error),
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeystoreError::WrongPassphrase => write!(f, "passphrase is wrong"),
+            KeystoreError::EmptyPassphrase => write!(f, "passphrase must not be empty"),
+            KeystoreError::Locked => write!(f, "keystore is locked"),
+            KeystoreError::Malformed => write!(f, "encrypted file is malformed"),
+            KeystoreError::Unencrypted => {
+                write!(f, "file is not encrypted although a passphrase is set")
+            }
+            KeystoreError::Crypto(error) => write!(f, "encryption failed: {}", error),
+            KeystoreError::Io(error) => write!(f, "keystore file error: {}", error),
+        }
+    }
+}
+
+impl Error for KeystoreError {}
+
+impl From<ErrorStack> for KeystoreError {
+    fn from(error: ErrorStack) -> Self {
+        KeystoreError::Crypto(error)
+    }
+}
+
+impl From<io::Error> for KeystoreError {
+    fn from(error: io::Error) -> Self {
+        KeystoreError::Io(error)
+    }
+}
+
+/// Whether the key files are protected with a passphrase.
+pub fn is_protected() -> bool {
+    Path::new(KEYSTORE_FILE_PATH).exists()
+}
+
+pub fn is_locked() -> bool {
+    is_protected() && DATA_KEY.read().unwrap().is_none()
+}
+
+pub fn unlock(passphrase: &str) -> Result<(), KeystoreError> {
+    let keystore = read_keystore_file()?;
+    let data_key = keystore.data_key(passphrase)?;
+    *DATA_KEY.write().unwrap() = Some(data_key);
+    Ok(())
+}
+
+/// Protects the key files with `new_passphrase`. Files written before are encrypted
+/// the first time a passphrase is set, later only the passphrase changes.
+pub fn set_passphrase(current_passphrase: &str, new_passphrase: &str) -> Result<(), KeystoreError> {
+    if new_passphrase.is_empty() {
+        return Err(KeystoreError::EmptyPassphrase);
+    }
+    if is_protected() {
+        let data_key = read_keystore_file()?.data_key(current_passphrase)?;
+        write_keystore_file(&data_key, new_passphrase)?;
+        *DATA_KEY.write().unwrap() = Some(data_key);
+        return Ok(());
+    }
+
+    let mut data_key = [0; DATA_KEY_LEN];
+    rand_bytes(&mut data_key)?;
+    write_keystore_file(&data_key, new_passphrase)?;
+    *DATA_KEY.write().unwrap() = Some(data_key);
+    encrypt_existing_files()
+}
+
+/// Reads a key file, decrypting it if it was written while a passphrase was set. Once a
+/// passphrase is set every key file has to be encrypted.
+pub fn read_file(path: &str) -> Result<Vec<u8>, KeystoreError> {
+    let bytes = fs::read(path)?;
+    match bytes.strip_prefix(ENCRYPTED_FILE_MAGIC) {
+        Some(rest) => decrypt_file(rest),
+        None if is_protected() => Err(KeystoreError::Unencrypted),
+        None => Ok(bytes),
+    }
+}
+
+/// Writes a key file, encrypted with the data key if a passphrase is set.
+pub fn write_file(path: &str, bytes: &[u8]) -> Result<(), KeystoreError> {
+    if !is_protected() {
+        return Ok(fs::write(path, bytes)?);
+    }
+    let data_key = DATA_KEY.read().unwrap().ok_or(KeystoreError::Locked)?;
+
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+    let header = header();
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &data_key,
+        Some(&nonce),
+        &header,
+        bytes,
+        &mut tag,
+    )?;
+
+    let mut file = header;
+    file.extend_from_slice(&nonce);
+    file.extend_from_slice(&ciphertext);
+    file.extend_from_slice(&tag);
+    Ok(fs::write(path, file)?)
+}
+
+fn header() -> Vec<u8> {
+    let mut header = ENCRYPTED_FILE_MAGIC.to_vec();
+    header.push(KEYSTORE_VERSION);
+    header
+}
+
+fn decrypt_file(bytes: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+    let data_key = DATA_KEY.read().unwrap().ok_or(KeystoreError::Locked)?;
+    match bytes.split_first() {
+        Some((&KEYSTORE_VERSION, rest)) if rest.len() >= NONCE_LEN + TAG_LEN => {
+            let (nonce, rest) = rest.split_at(NONCE_LEN);
+            let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+            Ok(decrypt_aead(
+                Cipher::aes_256_gcm(),
+                &data_key,
+                Some(nonce),
+                &header(),
+                ciphertext,
+                tag,
+            )?)
+        }
+        _ => Err(KeystoreError::Malformed),
+    }
+}
+
+fn passphrase_key(
+    passphrase: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<[u8; DATA_KEY_LEN], ErrorStack> {
+    let mut key = [0; DATA_KEY_LEN];
+    scrypt(
+        passphrase.as_bytes(),
+        salt,
+        1 << log_n,
+        r as u64,
+        p as u64,
+        SCRYPT_MAX_MEMORY,
+        &mut key,
+    )?;
+    Ok(key)
+}
+
+fn read_keystore_file() -> Result<KeystoreFile, KeystoreError> {
+    let bytes = fs::read(KEYSTORE_FILE_PATH)?;
+    KeystoreFile::try_from_slice(&bytes).map_err(|_| KeystoreError::Malformed)
+}
+
+fn write_keystore_file(data_key: &[u8], passphrase: &str) -> Result<(), KeystoreError> {
+    let keystore = KeystoreFile::new(data_key, passphrase)?;
+    Ok(fs::write(KEYSTORE_FILE_PATH, keystore.try_to_vec()?)?)
+}
+
+fn encrypt_existing_files() -> Result<(), KeystoreError> {
+    let mut paths = Vec::new();
+    for path in [
+        IDENTITY_FILE_PATH,
+        IDENTITY_SEED_FILE_PATH,
+        IDENTITY_ED_25529_KEYS_FILE_PATH,
+        IDENTITY_RETIRED_KEYS_FILE_PATH,
+    ] {
+        if Path::new(path).exists() {
+            paths.push(path.to_string());
+        }
+    }
+    let authority_key = Path::new(TIMESTAMP_AUTHORITIES_FOLDER_PATH).join(LOCAL_KEY_FILE);
+    if authority_key.exists() {
+        paths.push(authority_key.to_string_lossy().to_string());
+    }
+    if Path::new(BILLS_KEYS_FOLDER_PATH).exists() {
+        for file in fs::read_dir(BILLS_KEYS_FOLDER_PATH)? {
+            paths.push(file?.path().to_string_lossy().to_string());
+        }
+    }
+
+    for path in paths {
+        let bytes = fs::read(&path)?;
+        if !bytes.starts_with(ENCRYPTED_FILE_MAGIC) {
+            write_file(&path, &bytes)?;
+        }
+    }
+    Ok(())
+}
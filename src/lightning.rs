@@ -0,0 +1,664 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::{env, fmt, fs};
+
+use async_trait::async_trait;
+use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::Utc;
+use log::warn;
+use openssl::base64;
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::blockchain::{schnorr_signature, verify_schnorr_signature, Chain};
+use crate::bolt11::Bolt11Invoice;
+use crate::constants::{
+    BILL_INVOICES_FOLDER_PATH, CLN_RUNE_ENV_VAR, CLN_URL_ENV_VAR, LIGHTNING_INVOICE_EXPIRY_SECONDS,
+    LIGHTNING_NODE_ENV_VAR, LND_CERT_ENV_VAR, LND_MACAROON_ENV_VAR, LND_URL_ENV_VAR, USEDNET,
+};
+use crate::web::get_bill_payment_balance;
+use crate::{
+    bill_holder, bitcoin_private_key_for, get_whole_identity, is_current_holder,
+    read_bill_from_file, read_peer_id_from_file, read_retired_bitcoin_keys, IdentityPublicData,
+};
+
+/// Invoice the holder issued for the amount due on a bill.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LightningInvoice {
+    pub bolt11: String,
+    pub payment_hash: String,
+    pub amount_msat: u64,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Proof of a Lightning payment recorded in a `Paid` block.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightningPayment {
+    pub invoice: String,
+    pub payment_hash: String,
+    pub preimage: String,
+}
+
+impl LightningPayment {
+    /// Whether `preimage` is the preimage of `payment_hash`.
+    pub fn is_valid(&self) -> bool {
+        hex::decode(&self.preimage).is_ok_and(|preimage| {
+            hex::encode(sha256(&preimage)).eq_ignore_ascii_case(&self.payment_hash)
+        })
+    }
+
+    /// Whether this proves the payment of `amount` sat to `holder` for `bill_name`: the
+    /// preimage is valid and the invoice is one of the holder for the bill over the
+    /// amount with the same payment hash.
+    pub fn is_valid_for(&self, bill_name: &str, amount: u64, holder: &IdentityPublicData) -> bool {
+        let Ok(invoice) = Bolt11Invoice::decode(&self.invoice) else {
+            return false;
+        };
+        self.is_valid()
+            && hex::encode(invoice.payment_hash).eq_ignore_ascii_case(&self.payment_hash)
+            && invoice.amount_msat.is_some()
+            && invoice.amount_msat == amount.checked_mul(1000)
+            && check_bill_invoice(&invoice, bill_name, holder).is_ok()
+    }
+}
+
+/// Description of an invoice of the holder for `bill_name`, with the signature of the
+/// payment hash under the key the bill names the holder with.
+pub fn invoice_description(
+    bill_name: &str,
+    payment_hash: &[u8; 32],
+    holder_private_key: &str,
+) -> String {
+    format!(
+        "Bill {} {}",
+        bill_name,
+        schnorr_signature(&invoice_digest(bill_name, payment_hash), holder_private_key)
+    )
+}
+
+fn invoice_digest(bill_name: &str, payment_hash: &[u8; 32]) -> String {
+    hex::encode(sha256(
+        format!(
+            "bitcredit invoice {} {}",
+            bill_name,
+            hex::encode(payment_hash)
+        )
+        .as_bytes(),
+    ))
+}
+
+/// Checks that `invoice` is on our network and that `holder` issued it for `bill_name`.
+pub fn check_bill_invoice(
+    invoice: &Bolt11Invoice,
+    bill_name: &str,
+    holder: &IdentityPublicData,
+) -> Result<(), LightningError> {
+    if invoice.network != USEDNET {
+        return Err(LightningError::InvalidInvoice(format!(
+            "invoice is for {}",
+            invoice.network
+        )));
+    }
+    let signature = invoice
+        .description
+        .strip_prefix(&format!("Bill {} ", bill_name))
+        .ok_or_else(|| LightningError::InvalidInvoice("invoice is for another bill".to_string()))?;
+    if !verify_schnorr_signature(
+        &invoice_digest(bill_name, &invoice.payment_hash),
+        signature,
+        &holder.bitcoin_public_key,
+    ) {
+        return Err(LightningError::InvalidInvoice(
+            "invoice is not from the holder".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvoiceState {
+    Open,
+    Settled { amount_msat: u64 },
+    Canceled,
+}
+
+#[derive(Debug, Clone)]
+pub enum LightningError {
+    Disabled,
+    /// [`LIGHTNING_NODE_ENV_VAR`] names an unknown node or its credentials are missing.
+    Misconfigured(String),
+    InvalidInvoice(String),
+    NotEntitled,
+    AlreadyPaid,
+    /// Fiat bills are not paid in bitcoin.
+    NotPayable,
+    Request(String),
+    Rejected(String),
+    Malformed(String),
+}
+
+impl fmt::Display for LightningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LightningError::Disabled => write!(f, "no Lightning node is configured"),
+            LightningError::Misconfigured(error) => {
+                write!(f, "Lightning node is misconfigured: {}", error)
+            }
+            LightningError::InvalidInvoice(error) => write!(f, "invalid invoice: {}", error),
+            LightningError::NotEntitled => write!(f, "not allowed for this bill"),
+            LightningError::AlreadyPaid => write!(f, "bill is paid already"),
+            LightningError::NotPayable => write!(f, "bill is not payable in bitcoin"),
+            LightningError::Request(error) => write!(f, "Lightning node request failed: {}", error),
+            LightningError::Rejected(error) => write!(f, "Lightning node rejected: {}", error),
+            LightningError::Malformed(error) => {
+                write!(f, "malformed Lightning node response: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for LightningError {}
+
+impl From<reqwest::Error> for LightningError {
+    fn from(error: reqwest::Error) -> Self {
+        LightningError::Request(error.to_string())
+    }
+}
+
+/// Lightning node that receives and sends the payments of bills.
+#[async_trait]
+pub trait LightningNode: Send + Sync {
+    /// BOLT11 invoice over `amount_msat` that is settled by revealing `preimage`.
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        preimage: &[u8; 32],
+        expiry: u64,
+    ) -> Result<String, LightningError>;
+
+    async fn invoice_state(&self, payment_hash: &[u8; 32]) -> Result<InvoiceState, LightningError>;
+
+    /// Pays `bolt11` and returns the preimage as the receipt.
+    async fn pay(&self, bolt11: &str) -> Result<[u8; 32], LightningError>;
+}
+
+/// The node selected with [`LIGHTNING_NODE_ENV_VAR`], [`LightningError::Disabled`] when
+/// Lightning is off.
+pub fn node() -> Result<&'static dyn LightningNode, LightningError> {
+    static NODE: OnceLock<Result<Box<dyn LightningNode>, LightningError>> = OnceLock::new();
+    NODE.get_or_init(|| {
+        let missing = |name: &str| LightningError::Misconfigured(format!("{} is not set", name));
+        let node: Box<dyn LightningNode> = match env::var(LIGHTNING_NODE_ENV_VAR)
+            .map_err(|_| LightningError::Disabled)?
+            .as_str()
+        {
+            "lnd" => Box::new(LndNode::new(
+                env::var(LND_URL_ENV_VAR).unwrap_or("https://127.0.0.1:8080".to_string()),
+                env::var(LND_MACAROON_ENV_VAR).map_err(|_| missing(LND_MACAROON_ENV_VAR))?,
+                env::var(LND_CERT_ENV_VAR).ok(),
+            )?),
+            "cln" => Box::new(ClnNode::new(
+                env::var(CLN_URL_ENV_VAR).unwrap_or("https://127.0.0.1:3010".to_string()),
+                env::var(CLN_RUNE_ENV_VAR).map_err(|_| missing(CLN_RUNE_ENV_VAR))?,
+            )),
+            "mock" => Box::new(MockLightningNode::default()),
+            other => {
+                return Err(LightningError::Misconfigured(format!(
+                    "unknown node {:?}",
+                    other
+                )))
+            }
+        };
+        Ok(node)
+    })
+    .as_ref()
+    .map(Box::as_ref)
+    .map_err(Clone::clone)
+}
+
+fn to_hash(bytes: &[u8]) -> Result<[u8; 32], LightningError> {
+    bytes
+        .try_into()
+        .map_err(|_| LightningError::Malformed(format!("{} bytes instead of 32", bytes.len())))
+}
+
+fn decode_hex(value: &Value) -> Result<[u8; 32], LightningError> {
+    let bytes = hex::decode(value.as_str().unwrap_or_default())
+        .map_err(|error| LightningError::Malformed(error.to_string()))?;
+    to_hash(&bytes)
+}
+
+/// Amounts in msat, which LND writes as strings.
+fn msat(value: &Value) -> u64 {
+    match value {
+        Value::String(amount) => amount.parse().unwrap_or(0),
+        amount => amount.as_u64().unwrap_or(0),
+    }
+}
+
+/// LND over its REST interface, authenticated with a hex encoded macaroon.
+pub struct LndNode {
+    url: String,
+    macaroon: String,
+    client: reqwest::Client,
+}
+
+impl LndNode {
+    /// `cert_path` is the `tls.cert` of the node, which is usually self-signed.
+    pub fn new(
+        url: String,
+        macaroon: String,
+        cert_path: Option<String>,
+    ) -> Result<Self, LightningError> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(cert_path) = cert_path {
+            let cert = fs::read(&cert_path).map_err(|error| {
+                LightningError::Misconfigured(format!("can't read {}: {}", cert_path, error))
+            })?;
+            builder = builder.add_root_certificate(
+                reqwest::Certificate::from_pem(&cert)
+                    .map_err(|error| LightningError::Misconfigured(error.to_string()))?,
+            );
+        }
+        Ok(Self {
+            url,
+            macaroon,
+            client: builder
+                .build()
+                .map_err(|error| LightningError::Misconfigured(error.to_string()))?,
+        })
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, LightningError> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.url, path))
+            .header("Grpc-Metadata-macaroon", &self.macaroon);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response: Value = request.send().await?.json().await?;
+        match response.get("message").or(response.get("error")) {
+            Some(error) if !error.is_null() => Err(LightningError::Rejected(error.to_string())),
+            _ => Ok(response),
+        }
+    }
+}
+
+#[async_trait]
+impl LightningNode for LndNode {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        preimage: &[u8; 32],
+        expiry: u64,
+    ) -> Result<String, LightningError> {
+        let invoice = self
+            .request(
+                reqwest::Method::POST,
+                "/v1/invoices",
+                Some(json!({
+                    "value_msat": amount_msat.to_string(),
+                    "memo": description,
+                    "r_preimage": base64::encode_block(preimage),
+                    "expiry": expiry.to_string(),
+                })),
+            )
+            .await?;
+        invoice["payment_request"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| LightningError::Malformed(invoice.to_string()))
+    }
+
+    async fn invoice_state(&self, payment_hash: &[u8; 32]) -> Result<InvoiceState, LightningError> {
+        let invoice = self
+            .request(
+                reqwest::Method::GET,
+                &format!("/v1/invoice/{}", hex::encode(payment_hash)),
+                None,
+            )
+            .await?;
+        match invoice["state"].as_str() {
+            Some("SETTLED") => Ok(InvoiceState::Settled {
+                amount_msat: msat(&invoice["amt_paid_msat"]),
+            }),
+            Some("CANCELED") => Ok(InvoiceState::Canceled),
+            Some(_) => Ok(InvoiceState::Open),
+            None => Err(LightningError::Malformed(invoice.to_string())),
+        }
+    }
+
+    async fn pay(&self, bolt11: &str) -> Result<[u8; 32], LightningError> {
+        let payment = self
+            .request(
+                reqwest::Method::POST,
+                "/v1/channels/transactions",
+                Some(json!({ "payment_request": bolt11 })),
+            )
+            .await?;
+        match payment["payment_error"].as_str() {
+            Some(error) if !error.is_empty() => Err(LightningError::Rejected(error.to_string())),
+            _ => {
+                let preimage =
+                    base64::decode_block(payment["payment_preimage"].as_str().unwrap_or_default())
+                        .map_err(|error| LightningError::Malformed(error.to_string()))?;
+                to_hash(&preimage)
+            }
+        }
+    }
+}
+
+/// Core Lightning over `clnrest`, authenticated with a rune.
+pub struct ClnNode {
+    url: String,
+    rune: String,
+    client: reqwest::Client,
+}
+
+impl ClnNode {
+    pub fn new(url: String, rune: String) -> Self {
+        Self {
+            url,
+            rune,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, LightningError> {
+        let response = self
+            .client
+            .post(format!("{}/v1/{}", self.url, method))
+            .header("Rune", &self.rune)
+            .json(&params)
+            .send()
+            .await?;
+        let status = response.status();
+        let result: Value = response.json().await?;
+        if status.is_success() {
+            Ok(result)
+        } else {
+            Err(LightningError::Rejected(result.to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl LightningNode for ClnNode {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        preimage: &[u8; 32],
+        expiry: u64,
+    ) -> Result<String, LightningError> {
+        let payment_hash = hex::encode(sha256(preimage));
+        let invoice = self
+            .call(
+                "invoice",
+                json!({
+                    "amount_msat": amount_msat,
+                    "label": format!("bitcredit-{}", payment_hash),
+                    "description": description,
+                    "preimage": hex::encode(preimage),
+                    "expiry": expiry,
+                }),
+            )
+            .await?;
+        invoice["bolt11"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| LightningError::Malformed(invoice.to_string()))
+    }
+
+    async fn invoice_state(&self, payment_hash: &[u8; 32]) -> Result<InvoiceState, LightningError> {
+        let invoices = self
+            .call(
+                "listinvoices",
+                json!({ "payment_hash": hex::encode(payment_hash) }),
+            )
+            .await?;
+        let invoice = &invoices["invoices"][0];
+        match invoice["status"].as_str() {
+            Some("paid") => Ok(InvoiceState::Settled {
+                amount_msat: msat(&invoice["amount_received_msat"]),
+            }),
+            Some("expired") => Ok(InvoiceState::Canceled),
+            Some(_) => Ok(InvoiceState::Open),
+            None => Err(LightningError::Malformed(invoices.to_string())),
+        }
+    }
+
+    async fn pay(&self, bolt11: &str) -> Result<[u8; 32], LightningError> {
+        let payment = self.call("pay", json!({ "bolt11": bolt11 })).await?;
+        match payment["status"].as_str() {
+            Some("complete") => decode_hex(&payment["payment_preimage"]),
+            _ => Err(LightningError::Rejected(payment.to_string())),
+        }
+    }
+}
+
+struct MockInvoice {
+    bolt11: String,
+    preimage: [u8; 32],
+    amount_msat: u64,
+    paid: bool,
+}
+
+/// Node without channels that settles the invoices it issued itself, for tests and demos.
+pub struct MockLightningNode {
+    node_key: SecretKey,
+    invoices: Mutex<HashMap<[u8; 32], MockInvoice>>,
+}
+
+impl Default for MockLightningNode {
+    fn default() -> Self {
+        Self {
+            node_key: SecretKey::new(&mut bitcoin::secp256k1::rand::thread_rng()),
+            invoices: Mutex::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LightningNode for MockLightningNode {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        description: &str,
+        preimage: &[u8; 32],
+        expiry: u64,
+    ) -> Result<String, LightningError> {
+        let payment_hash = sha256(preimage);
+        let bolt11 = Bolt11Invoice {
+            network: USEDNET,
+            amount_msat: Some(amount_msat),
+            timestamp: Utc::now().timestamp() as u64,
+            payment_hash,
+            description: description.to_string(),
+            expiry,
+            payee: self.node_key.public_key(&Secp256k1::signing_only()),
+        }
+        .encode(&self.node_key);
+        self.invoices.lock().unwrap().insert(
+            payment_hash,
+            MockInvoice {
+                bolt11: bolt11.clone(),
+                preimage: *preimage,
+                amount_msat,
+                paid: false,
+            },
+        );
+        Ok(bolt11)
+    }
+
+    async fn invoice_state(&self, payment_hash: &[u8; 32]) -> Result<InvoiceState, LightningError> {
+        match self.invoices.lock().unwrap().get(payment_hash) {
+            Some(invoice) if invoice.paid => Ok(InvoiceState::Settled {
+                amount_msat: invoice.amount_msat,
+            }),
+            Some(_) => Ok(InvoiceState::Open),
+            None => Err(LightningError::Rejected("unknown invoice".to_string())),
+        }
+    }
+
+    async fn pay(&self, bolt11: &str) -> Result<[u8; 32], LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+        let invoice = invoices
+            .values_mut()
+            .find(|invoice| invoice.bolt11 == bolt11)
+            .ok_or_else(|| LightningError::Rejected("no route".to_string()))?;
+        invoice.paid = true;
+        Ok(invoice.preimage)
+    }
+}
+
+/// Preimage of the `index`th invoice of the bill. Only the holder can derive it from
+/// their key, which ties the payment hash to the bill.
+pub fn invoice_preimage(holder_private_key: &str, bill_name: &str, index: usize) -> [u8; 32] {
+    sha256(
+        format!(
+            "bitcredit invoice {} {} {}",
+            holder_private_key, bill_name, index
+        )
+        .as_bytes(),
+    )
+}
+
+pub fn read_invoices(bill_name: &str) -> Vec<LightningInvoice> {
+    let path = format!("{}/{}.json", BILL_INVOICES_FOLDER_PATH, bill_name);
+    if !Path::new(&path).exists() {
+        return Vec::new();
+    }
+    serde_json::from_slice(&fs::read(path).unwrap()).expect("Invoices file is malformed")
+}
+
+fn write_invoices(bill_name: &str, invoices: &[LightningInvoice]) {
+    let path = format!("{}/{}.json", BILL_INVOICES_FOLDER_PATH, bill_name);
+    fs::write(path, serde_json::to_string_pretty(invoices).unwrap()).unwrap();
+}
+
+/// Issues an invoice over what is left to pay on the bill, only the holder can.
+pub async fn create_bill_invoice(bill_name: &String) -> Result<LightningInvoice, LightningError> {
+    let node = node()?;
+    let identity = get_whole_identity();
+    let bill = read_bill_from_file(bill_name);
+    let chain = Chain::read_chain_from_file(bill_name);
+    if !is_current_holder(&bill, &chain, &identity.peer_id.to_string()) {
+        return Err(LightningError::NotEntitled);
+    }
+    if chain.is_paid() {
+        return Err(LightningError::AlreadyPaid);
+    }
+    let balance = get_bill_payment_balance(&bill, &chain)
+        .await
+        .map_err(|error| LightningError::Request(error.to_string()))?;
+    if !balance.on_chain {
+        return Err(LightningError::NotPayable);
+    }
+    if balance.remaining == 0 {
+        return Err(LightningError::AlreadyPaid);
+    }
+
+    let holder_private_key = bitcoin_private_key_for(
+        &identity.identity,
+        &read_retired_bitcoin_keys(),
+        &bill_holder(&bill).bitcoin_public_key,
+    )
+    .ok_or(LightningError::NotEntitled)?;
+    let mut invoices = read_invoices(bill_name);
+    let preimage = invoice_preimage(&holder_private_key, bill_name, invoices.len());
+    let amount_msat = balance.remaining * 1000;
+    let bolt11 = node
+        .create_invoice(
+            amount_msat,
+            &invoice_description(bill_name, &sha256(&preimage), &holder_private_key),
+            &preimage,
+            LIGHTNING_INVOICE_EXPIRY_SECONDS,
+        )
+        .await?;
+    let created_at = Utc::now().timestamp();
+    let invoice = LightningInvoice {
+        bolt11,
+        payment_hash: hex::encode(sha256(&preimage)),
+        amount_msat,
+        created_at,
+        expires_at: created_at + LIGHTNING_INVOICE_EXPIRY_SECONDS as i64,
+    };
+    invoices.push(invoice.clone());
+    write_invoices(bill_name, &invoices);
+    Ok(invoice)
+}
+
+/// Pays an invoice of the holder for the bill, only the drawee can. Returns the preimage.
+pub async fn pay_bill_invoice(bill_name: &String, bolt11: &str) -> Result<String, LightningError> {
+    let node = node()?;
+    let bill = read_bill_from_file(bill_name);
+    if !read_peer_id_from_file()
+        .to_string()
+        .eq(&bill.drawee.peer_id)
+    {
+        return Err(LightningError::NotEntitled);
+    }
+    let chain = Chain::read_chain_from_file(bill_name);
+    if chain.is_paid() {
+        return Err(LightningError::AlreadyPaid);
+    }
+
+    let invoice = Bolt11Invoice::decode(bolt11)
+        .map_err(|error| LightningError::InvalidInvoice(error.to_string()))?;
+    check_bill_invoice(&invoice, bill_name, bill_holder(&bill))?;
+    let amount_msat = invoice.amount_msat.unwrap_or(0);
+    if amount_msat == 0 || amount_msat > chain.get_amount_to_pay().saturating_mul(1000) {
+        return Err(LightningError::InvalidInvoice(format!(
+            "invoice is over {} msat",
+            amount_msat
+        )));
+    }
+    if invoice.is_expired(Utc::now().timestamp()) {
+        return Err(LightningError::InvalidInvoice(
+            "invoice expired".to_string(),
+        ));
+    }
+    Ok(hex::encode(node.pay(bolt11).await?))
+}
+
+/// First invoice of the bill that was paid in full, with the proof of payment.
+pub async fn settled_invoice(bill_name: &str) -> Option<(LightningInvoice, LightningPayment)> {
+    let node = node().ok()?;
+    // Invoices are made with the key the bill names the holder with, which may have been
+    // rotated since.
+    let holder_private_key = bitcoin_private_key_for(
+        &get_whole_identity().identity,
+        &read_retired_bitcoin_keys(),
+        &bill_holder(&read_bill_from_file(&bill_name.to_string())).bitcoin_public_key,
+    )?;
+    for (index, invoice) in read_invoices(bill_name).into_iter().enumerate() {
+        let preimage = invoice_preimage(&holder_private_key, bill_name, index);
+        match node.invoice_state(&sha256(&preimage)).await {
+            Ok(InvoiceState::Settled { amount_msat }) if amount_msat >= invoice.amount_msat => {
+                let payment = LightningPayment {
+                    invoice: invoice.bolt11.clone(),
+                    payment_hash: invoice.payment_hash.clone(),
+                    preimage: hex::encode(preimage),
+                };
+                return Some((invoice, payment));
+            }
+            Ok(_) => {}
+            Err(error) => warn!("can not check invoice of bill {}: {}", bill_name, error),
+        }
+    }
+    None
+}
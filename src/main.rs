@@ -4,38 +4,74 @@ extern crate rocket;
 
 use bitcoin::PublicKey;
 use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
 use std::path::Path;
-use std::{env, fs, mem, path};
+use std::{env, fs, path};
 
 use borsh::{self, BorshDeserialize, BorshSerialize};
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
 use libp2p::identity::Keypair;
 use libp2p::PeerId;
-use openssl::pkey::{Private, Public};
+use openssl::pkey::Private;
 use openssl::rsa;
-use openssl::rsa::{Padding, Rsa};
+use openssl::rsa::Rsa;
 use openssl::sha::sha256;
 use rocket::fs::FileServer;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::{Build, Rocket};
 use rocket_dyn_templates::Template;
 
-use crate::blockchain::{start_blockchain_for_new_bill, Block, Chain, OperationCode};
+use crate::amount::{Amount, AmountError};
+use crate::blockchain::{
+    encrypted_block_data, start_blockchain_for_new_bill, Block, BlockData, Chain, OperationCode,
+};
 use crate::constants::{
-    BILLS_FOLDER_PATH, BILLS_KEYS_FOLDER_PATH, BILL_VALIDITY_PERIOD, BOOTSTRAP_FOLDER_PATH,
-    COMPOUNDING_INTEREST_RATE_ZERO, CONTACT_MAP_FILE_PATH, CONTACT_MAP_FOLDER_PATH,
+    BILLS_FOLDER_PATH, BILLS_KEYS_FOLDER_PATH, BILL_ANCHORS_FOLDER_PATH, BILL_INVOICES_FOLDER_PATH,
+    BILL_VALIDITY_PERIOD, BOOTSTRAP_FOLDER_PATH, CONTACT_MAP_FILE_PATH, CONTACT_MAP_FOLDER_PATH,
     CSS_FOLDER_PATH, IDENTITY_ED_25529_KEYS_FILE_PATH, IDENTITY_FILE_PATH, IDENTITY_FOLDER_PATH,
-    IDENTITY_PEER_ID_FILE_PATH, IMAGE_FOLDER_PATH, SATOSHI, TEMPLATES_FOLDER_PATH, USEDNET,
+    IDENTITY_PEER_ID_FILE_PATH, IDENTITY_RETIRED_KEYS_FILE_PATH, IDENTITY_SEED_FILE_PATH,
+    IMAGE_FOLDER_PATH, PASSPHRASE_ENV_VAR, RSA_KEY_BITS, TEMPLATES_FOLDER_PATH, USEDNET,
+};
+use crate::encryption::EncryptionError;
+use crate::key_rotation::KeyRotation;
+use crate::keystore::KeystoreError;
+use crate::lightning::{LightningError, LightningPayment};
+use crate::maturity::Maturity;
+use crate::mnemonic::Mnemonic;
+use crate::numbers_to_words::encode_amount;
+use crate::payment_address::PaymentAddressType;
+use crate::protest::{
+    is_within_non_acceptance_deadline, is_within_non_payment_deadline, parse_maturity_date,
+    ProtestDocument, ProtestReason,
 };
-use crate::numbers_to_words::encode;
+use crate::recourse::{has_open_recourse, remaining_recoursees, RecourseClaim};
 
+mod amount;
+mod anchor;
 mod api;
 mod blockchain;
+mod bolt11;
 mod constants;
 mod dht;
+mod encryption;
+mod interest;
+mod key_rotation;
+mod keystore;
+mod lightning;
+mod maturity;
+mod mnemonic;
 mod numbers_to_words;
+mod payment;
+mod payment_address;
+mod payout;
+mod protest;
+mod recourse;
+mod seed;
 mod test;
+mod timestamp;
 mod web;
+mod web_api;
 
 // MAIN
 // #[rocket::main]
@@ -46,6 +82,8 @@ async fn main() {
     env_logger::init();
 
     init_folders();
+    unlock_keystore().await;
+    migrate_dht_logic_files();
 
     let mut dht = dht::dht_main().await.expect("DHT failed to start");
 
@@ -56,12 +94,70 @@ async fn main() {
     dht.start_provide().await;
     dht.receive_updates_for_all_bills_topics().await;
     dht.put_identity_public_data_in_dht().await;
+    dht.put_key_rotations_in_dht().await;
+    api::check_backend_network().await;
+    // Reports an unknown payment address type now rather than when the first bill is issued.
+    PaymentAddressType::configured();
+    if let Err(error @ LightningError::Misconfigured(_)) = lightning::node() {
+        warn!("Lightning is off: {}", error);
+    }
+    anchor::start_anchoring_service();
+    payment::start_payment_watcher(dht.clone());
     let _rocket = rocket_main(dht).launch().await.unwrap();
 }
 
+/// Asks for the passphrase before anything reads the key files. It is taken from
+/// [`PASSPHRASE_ENV_VAR`], the terminal, or the browser when there is no terminal.
+async fn unlock_keystore() {
+    if !keystore::is_locked() {
+        return;
+    }
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV_VAR) {
+        if let Err(error) = keystore::unlock(&passphrase) {
+            println!(
+                "Can't unlock keystore with {}: {}",
+                PASSPHRASE_ENV_VAR, error
+            );
+        }
+    }
+
+    if io::stdin().is_terminal() {
+        while keystore::is_locked() {
+            print!("Passphrase: ");
+            io::stdout().flush().unwrap();
+            let mut passphrase = String::new();
+            if io::stdin().read_line(&mut passphrase).unwrap_or(0) == 0 {
+                break;
+            }
+            if let Err(error) = keystore::unlock(passphrase.trim_end_matches(['\r', '\n'])) {
+                println!("Can't unlock keystore: {}", error);
+            }
+        }
+    }
+
+    if keystore::is_locked() {
+        let _rocket = unlock_rocket().launch().await.unwrap();
+    }
+}
+
+/// Only serves the unlock page, it shuts down once the keystore is unlocked.
+fn unlock_rocket() -> Rocket<Build> {
+    let rocket = rocket::build()
+        .mount("/image", FileServer::from(IMAGE_FOLDER_PATH))
+        .mount("/css", FileServer::from(CSS_FOLDER_PATH))
+        .mount("/", routes![web::unlock_page])
+        .mount("/keystore", routes![web::unlock_keystore])
+        .attach(Template::fairing());
+
+    open::that("http://127.0.0.1:8000").expect("Can't open browser.");
+
+    rocket
+}
+
 fn rocket_main(dht: dht::network::Client) -> Rocket<Build> {
     let rocket = rocket::build()
         .register("/", catchers![web::not_found])
+        .register("/api/v1", catchers![web_api::default_catcher])
         .manage(dht)
         .mount("/image", FileServer::from(IMAGE_FOLDER_PATH))
         .mount("/css", FileServer::from(CSS_FOLDER_PATH))
@@ -69,10 +165,22 @@ fn rocket_main(dht: dht::network::Client) -> Rocket<Build> {
         .mount("/exit", routes![web::exit])
         .mount(
             "/identity",
-            routes![web::get_identity, web::create_identity,],
+            routes![
+                web::get_identity,
+                web::create_identity,
+                web::seed_phrase,
+                web::restore_identity_page,
+                web::restore_identity,
+                web::identity_keys,
+                web::rotate_keys,
+            ],
         )
         .mount("/bills", routes![web::bills_list])
         .mount("/info", routes![web::info])
+        .mount(
+            "/keystore",
+            routes![web::passphrase, web::change_passphrase],
+        )
         .mount(
             "/contacts",
             routes![web::add_contact, web::new_contact, web::contacts],
@@ -91,6 +199,54 @@ fn rocket_main(dht: dht::network::Client) -> Rocket<Build> {
                 web::get_bill_history,
                 web::get_bill_chain,
                 web::get_block,
+                web::protest_non_acceptance_bill,
+                web::protest_non_payment_bill,
+                web::recourse_bill,
+                web::request_to_aval_bill,
+                web::aval_bill_form,
+                web::sweep_bill,
+                web::create_bill_invoice,
+                web::pay_bill_invoice,
+            ],
+        )
+        .mount(
+            "/api/v1",
+            routes![
+                web_api::get_identity,
+                web_api::create_identity,
+                web_api::restore_identity,
+                web_api::get_key_rotations,
+                web_api::rotate_keys,
+                web_api::contacts,
+                web_api::new_contact,
+                web_api::bills_list,
+                web_api::get_bill,
+                web_api::get_bill_chain,
+                web_api::get_block,
+                web_api::get_bill_history,
+                web_api::get_bill_forks,
+                web_api::get_bill_anchors,
+                web_api::verify_block_anchor,
+                web_api::anchor_chain_heads,
+                web_api::issue_bill,
+                web_api::endorse_bill,
+                web_api::request_to_accept_bill,
+                web_api::request_to_pay_bill,
+                web_api::accept_bill_request,
+                web_api::protest_non_acceptance_bill,
+                web_api::protest_non_payment_bill,
+                web_api::get_bill_protests,
+                web_api::get_bill_payments,
+                web_api::recourse_bill,
+                web_api::get_bill_recourses,
+                web_api::sweep_bill,
+                web_api::create_bill_invoice,
+                web_api::get_bill_invoices,
+                web_api::pay_bill_invoice,
+                web_api::request_to_aval_bill,
+                web_api::aval_bill_request,
+                web_api::get_bill_avals,
+                web_api::change_passphrase,
             ],
         )
         .attach(Template::custom(|engines| {
@@ -115,6 +271,12 @@ fn init_folders() {
     if !Path::new(BILLS_KEYS_FOLDER_PATH).exists() {
         fs::create_dir(BILLS_KEYS_FOLDER_PATH).expect("Can't create folder bills_keys.");
     }
+    if !Path::new(BILL_ANCHORS_FOLDER_PATH).exists() {
+        fs::create_dir(BILL_ANCHORS_FOLDER_PATH).expect("Can't create folder bill_anchors.");
+    }
+    if !Path::new(BILL_INVOICES_FOLDER_PATH).exists() {
+        fs::create_dir(BILL_INVOICES_FOLDER_PATH).expect("Can't create folder bill_invoices.");
+    }
     if !Path::new(CSS_FOLDER_PATH).exists() {
         fs::create_dir(CSS_FOLDER_PATH).expect("Can't create folder css.");
     }
@@ -170,7 +332,7 @@ fn get_contact_from_map(name: &String) -> String {
 
 //-------------------------RSA----------------------------------
 fn generation_rsa_key() -> Rsa<Private> {
-    Rsa::generate(2048).unwrap()
+    Rsa::generate(RSA_KEY_BITS).unwrap()
 }
 
 fn pem_private_key_from_rsa(rsa: &Rsa<Private>) -> String {
@@ -187,214 +349,28 @@ fn private_key_from_pem_u8(private_key_u8: &Vec<u8>) -> Rsa<Private> {
     rsa::Rsa::private_key_from_pem(private_key_u8).unwrap()
 }
 
-fn public_key_from_pem_u8(public_key_u8: &Vec<u8>) -> Rsa<Public> {
-    rsa::Rsa::public_key_from_pem(public_key_u8).unwrap()
-}
 //--------------------------------------------------------------
 
 //-------------------------Bytes common-------------------------
 fn encrypt_bytes_with_public_key(bytes: &Vec<u8>, public_key: String) -> Vec<u8> {
     let public_key = Rsa::public_key_from_pem(public_key.as_bytes()).unwrap();
-
-    let key_size: usize = (public_key.size() / 2) as usize; //128
-
-    let mut whole_encrypted_buff: Vec<u8> = Vec::new();
-    let mut temp_buff: Vec<u8> = vec![0; key_size];
-    let mut temp_buff_encrypted: Vec<u8> = vec![0; public_key.size() as usize];
-
-    let number_of_key_size_in_whole_bill: usize = bytes.len() / key_size;
-    let remainder: usize = bytes.len() - key_size * number_of_key_size_in_whole_bill;
-
-    for i in 0..number_of_key_size_in_whole_bill {
-        for j in 0..key_size {
-            let byte_number: usize = key_size * i + j;
-            temp_buff[j] = bytes[byte_number];
-        }
-
-        let _encrypted_len: usize = public_key
-            .public_encrypt(&temp_buff, &mut temp_buff_encrypted, Padding::PKCS1)
-            .unwrap();
-
-        whole_encrypted_buff.append(&mut temp_buff_encrypted);
-        temp_buff = vec![0; key_size];
-        temp_buff_encrypted = vec![0; public_key.size() as usize];
-    }
-
-    if remainder != 0 {
-        temp_buff = vec![0; remainder];
-
-        let position: usize = key_size * number_of_key_size_in_whole_bill;
-        let mut index_in_temp_buff: usize = 0;
-
-        for i in position..bytes.len() {
-            temp_buff[index_in_temp_buff] = bytes[i];
-            index_in_temp_buff += 1;
-        }
-
-        index_in_temp_buff = 0;
-
-        let _encrypted_len: usize = public_key
-            .public_encrypt(&temp_buff, &mut temp_buff_encrypted, Padding::PKCS1)
-            .unwrap();
-
-        whole_encrypted_buff.append(&mut temp_buff_encrypted);
-        temp_buff.clear();
-        temp_buff_encrypted.clear();
-    }
-
-    whole_encrypted_buff
+    encryption::seal(bytes, &public_key).expect("Failed to encrypt bytes")
 }
 
-fn decrypt_bytes_with_private_key(bytes: &Vec<u8>, private_key: String) -> Vec<u8> {
+fn decrypt_bytes_with_private_key(
+    bytes: &Vec<u8>,
+    private_key: String,
+) -> Result<Vec<u8>, EncryptionError> {
     let private_key = Rsa::private_key_from_pem(private_key.as_bytes()).unwrap();
-
-    let key_size: usize = private_key.size() as usize; //256
-
-    let mut whole_decrypted_buff: Vec<u8> = Vec::new();
-    let mut temp_buff: Vec<u8> = vec![0; private_key.size() as usize];
-    let mut temp_buff_decrypted: Vec<u8> = vec![0; private_key.size() as usize];
-
-    let number_of_key_size_in_whole_bill: usize = bytes.len() / key_size;
-    // let remainder = bill_bytes.len() - key_size * number_of_key_size_in_whole_bill;
-
-    for i in 0..number_of_key_size_in_whole_bill {
-        for j in 0..key_size {
-            let byte_number = key_size * i + j;
-            temp_buff[j] = bytes[byte_number];
-        }
-
-        let decrypted_len: usize = private_key
-            .private_decrypt(&temp_buff, &mut temp_buff_decrypted, Padding::PKCS1)
-            .unwrap();
-
-        whole_decrypted_buff.append(&mut temp_buff_decrypted[0..decrypted_len].to_vec());
-        temp_buff = vec![0; private_key.size() as usize];
-        temp_buff_decrypted = vec![0; private_key.size() as usize];
-    }
-
-    // if remainder != 0 {
-    //     let position = key_size * number_of_key_size_in_whole_bill;
-    //     let mut index_in_temp_buff = 0;
-    //
-    //     for i in position..bill_bytes.len() {
-    //         temp_buff[index_in_temp_buff] = bill_bytes[i];
-    //         index_in_temp_buff = index_in_temp_buff + 1;
-    //     }
-    //
-    //     index_in_temp_buff = 0;
-    //
-    //     let decrypted_len = rsa_key
-    //         .public_decrypt(&*temp_buff, &mut temp_buff_decrypted, Padding::PKCS1)
-    //         .unwrap();
-    //
-    //     whole_decrypted_buff.append(&mut temp_buff_decrypted);
-    //     temp_buff.clear();
-    //     temp_buff_decrypted.clear();
-    // }
-
-    whole_decrypted_buff
+    encryption::open(bytes, &private_key)
 }
 
 fn encrypt_bytes(bytes: &Vec<u8>, rsa_key: &Rsa<Private>) -> Vec<u8> {
-    let key_size: usize = (rsa_key.size() / 2) as usize; //128
-
-    let mut whole_encrypted_buff: Vec<u8> = Vec::new();
-    let mut temp_buff: Vec<u8> = vec![0; key_size];
-    let mut temp_buff_encrypted: Vec<u8> = vec![0; rsa_key.size() as usize];
-
-    let number_of_key_size_in_whole_bill: usize = bytes.len() / key_size;
-    let remainder: usize = bytes.len() - key_size * number_of_key_size_in_whole_bill;
-
-    for i in 0..number_of_key_size_in_whole_bill {
-        for j in 0..key_size {
-            let byte_number: usize = key_size * i + j;
-            temp_buff[j] = bytes[byte_number];
-        }
-
-        let _encrypted_len: usize = rsa_key
-            .public_encrypt(&temp_buff, &mut temp_buff_encrypted, Padding::PKCS1)
-            .unwrap();
-
-        whole_encrypted_buff.append(&mut temp_buff_encrypted);
-        temp_buff = vec![0; key_size];
-        temp_buff_encrypted = vec![0; rsa_key.size() as usize];
-    }
-
-    if remainder != 0 {
-        temp_buff = vec![0; remainder];
-
-        let position: usize = key_size * number_of_key_size_in_whole_bill;
-        let mut index_in_temp_buff: usize = 0;
-
-        for i in position..bytes.len() {
-            temp_buff[index_in_temp_buff] = bytes[i];
-            index_in_temp_buff += 1;
-        }
-
-        index_in_temp_buff = 0;
-
-        let _encrypted_len: usize = rsa_key
-            .public_encrypt(&temp_buff, &mut temp_buff_encrypted, Padding::PKCS1)
-            .unwrap();
-
-        whole_encrypted_buff.append(&mut temp_buff_encrypted);
-        temp_buff.clear();
-        temp_buff_encrypted.clear();
-    }
-
-    whole_encrypted_buff
+    encryption::seal(bytes, rsa_key).expect("Failed to encrypt bytes")
 }
 
-fn decrypt_bytes(bytes: &Vec<u8>, rsa_key: &Rsa<Private>) -> Vec<u8> {
-    let key_size: usize = rsa_key.size() as usize; //256
-
-    let mut whole_decrypted_buff: Vec<u8> = Vec::new();
-    let mut temp_buff: Vec<u8> = vec![0; rsa_key.size() as usize];
-    let mut temp_buff_decrypted: Vec<u8> = vec![0; rsa_key.size() as usize];
-
-    let number_of_key_size_in_whole_bill: usize = bytes.len() / key_size;
-    // let remainder = bill_bytes.len() - key_size * number_of_key_size_in_whole_bill;
-
-    for i in 0..number_of_key_size_in_whole_bill {
-        for j in 0..key_size {
-            let byte_number = key_size * i + j;
-            temp_buff[j] = bytes[byte_number];
-        }
-
-        let decrypted_len: usize = rsa_key
-            .private_decrypt(&temp_buff, &mut temp_buff_decrypted, Padding::PKCS1)
-            .unwrap();
-
-        whole_decrypted_buff.append(&mut temp_buff_decrypted[0..decrypted_len].to_vec());
-        temp_buff = vec![0; rsa_key.size() as usize];
-        temp_buff_decrypted = vec![0; rsa_key.size() as usize];
-    }
-
-    // if remainder != 0 {
-    //     let position = key_size * number_of_key_size_in_whole_bill;
-    //     let mut index_in_temp_buff = 0;
-    //
-    //     for i in position..bill_bytes.len() {
-    //         temp_buff[index_in_temp_buff] = bill_bytes[i];
-    //         index_in_temp_buff = index_in_temp_buff + 1;
-    //     }
-    //
-    //     index_in_temp_buff = 0;
-    //
-    //     let decrypted_len = rsa_key
-    //         .public_decrypt(&*temp_buff, &mut temp_buff_decrypted, Padding::PKCS1)
-    //         .unwrap();
-    //
-    //     whole_decrypted_buff.append(&mut temp_buff_decrypted);
-    //     temp_buff.clear();
-    //     temp_buff_decrypted.clear();
-    // }
-
-    whole_decrypted_buff
-}
-
-unsafe fn structure_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-    ::std::slice::from_raw_parts((p as *const T) as *const u8, ::std::mem::size_of::<T>())
+fn decrypt_bytes(bytes: &Vec<u8>, rsa_key: &Rsa<Private>) -> Result<Vec<u8>, EncryptionError> {
+    encryption::open(bytes, rsa_key)
 }
 //--------------------------------------------------------------
 
@@ -485,8 +461,62 @@ pub fn create_whole_identity(
     }
 }
 
+/// Restores the identity keys and peer id from the seed phrase. Name and the other
+/// details are not part of the seed and are given again.
+pub fn restore_whole_identity(
+    mnemonic: &Mnemonic,
+    form: RestoreIdentityForm,
+    rotations: Vec<KeyRotation>,
+) -> IdentityWithAll {
+    write_mnemonic_to_file(mnemonic);
+    let ed25519_keys = seed::ed25519_keypair(&mnemonic.to_seed(""));
+    let peer_id = ed25519_keys.public().to_peer_id();
+    write_dht_logic(&peer_id, &ed25519_keys);
+
+    let mut identity = create_new_identity(
+        form.name,
+        form.date_of_birth,
+        form.city_of_birth,
+        form.country_of_birth,
+        form.email,
+        form.postal_address,
+    );
+    // Rotations read from the DHT tell which keys are current. The first one starts from
+    // the first keys of the seed.
+    key_rotation::add_key_rotations(
+        rotations,
+        &IdentityPublicData::new(identity.clone(), peer_id.to_string()),
+    );
+    let rotations = key_rotation::rotations_of(&peer_id.to_string()).len() as u32;
+    if rotations > 0 {
+        let mut retired_keys = read_retired_bitcoin_keys();
+        for index in 0..rotations {
+            let mut retired = identity.clone();
+            set_identity_keys(&mut retired, index);
+            retire_bitcoin_key(&mut retired_keys, &retired);
+        }
+        write_retired_bitcoin_keys(&retired_keys);
+        set_identity_keys(&mut identity, rotations);
+    }
+    write_identity_to_file(&identity);
+
+    IdentityWithAll {
+        identity,
+        peer_id,
+        key_pair: ed25519_keys,
+    }
+}
+
+/// Keys of a new node come from a new seed phrase. Identities created before seed
+/// phrases keep their random keys.
 pub fn generate_dht_logic() {
-    let ed25519_keys = Keypair::generate_ed25519();
+    if !Path::new(IDENTITY_FILE_PATH).exists() && read_mnemonic_from_file().is_none() {
+        write_mnemonic_to_file(&Mnemonic::generate());
+    }
+    let ed25519_keys = match read_mnemonic_from_file() {
+        Some(mnemonic) => seed::ed25519_keypair(&mnemonic.to_seed("")),
+        None => Keypair::generate_ed25519(),
+    };
     let peer_id = ed25519_keys.public().to_peer_id();
 
     write_dht_logic(&peer_id, &ed25519_keys);
@@ -505,91 +535,213 @@ fn create_new_identity(
     email: String,
     postal_address: String,
 ) -> Identity {
-    let rsa: Rsa<Private> = generation_rsa_key();
-    let private_key_pem: String = pem_private_key_from_rsa(&rsa);
-    let public_key_pem: String = pem_public_key_from_rsa(&rsa);
-
-    let s = bitcoin::secp256k1::Secp256k1::new();
-    let private_key = bitcoin::PrivateKey::new(
-        s.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng())
-            .0,
-        USEDNET,
-    );
-    let public_key = private_key.public_key(&s).to_string();
-    let private_key = private_key.to_string();
-
-    Identity {
+    let mut identity = Identity {
         name,
         date_of_birth,
         city_of_birth,
         country_of_birth,
         email,
         postal_address,
-        public_key_pem,
-        private_key_pem,
-        bitcoin_public_key: public_key,
-        bitcoin_private_key: private_key.clone(),
+        public_key_pem: String::new(),
+        private_key_pem: String::new(),
+        bitcoin_public_key: String::new(),
+        bitcoin_private_key: String::new(),
+    };
+    set_identity_keys(&mut identity, 0);
+    identity
+}
+
+/// Puts the `index`th RSA and bitcoin keys of the seed phrase in the identity, or random
+/// keys for identities created before seed phrases. The index counts key rotations.
+fn set_identity_keys(identity: &mut Identity, index: u32) {
+    let s = bitcoin::secp256k1::Secp256k1::new();
+    let (rsa, private_key) = match read_mnemonic_from_file() {
+        Some(mnemonic) => {
+            let seed = mnemonic.to_seed("");
+            (
+                seed::rsa_key(&seed, index),
+                seed::bitcoin_private_key(&seed, index),
+            )
+        }
+        None => (
+            generation_rsa_key(),
+            bitcoin::PrivateKey::new(
+                s.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng())
+                    .0,
+                USEDNET,
+            ),
+        ),
+    };
+
+    identity.private_key_pem = pem_private_key_from_rsa(&rsa);
+    identity.public_key_pem = pem_public_key_from_rsa(&rsa);
+    identity.bitcoin_public_key = private_key.public_key(&s).to_string();
+    identity.bitcoin_private_key = private_key.to_string();
+}
+
+/// Replaces the RSA and bitcoin keys of the identity and records the rotation in the
+/// local history. With `compromised_since` the old keys are revoked from that time on.
+pub fn rotate_identity_keys(compromised_since: Option<i64>) -> KeyRotation {
+    let identity: IdentityWithAll = get_whole_identity();
+    let index = key_rotation::rotations_of(&identity.peer_id.to_string()).len() as u32 + 1;
+    let mut new_identity = identity.identity.clone();
+    set_identity_keys(&mut new_identity, index);
+
+    let rotation = KeyRotation::new(
+        &identity.key_pair,
+        &identity.identity,
+        &new_identity,
+        compromised_since,
+    );
+    key_rotation::add_key_rotations(
+        vec![rotation.clone()],
+        &IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string()),
+    );
+    let mut retired_keys = read_retired_bitcoin_keys();
+    retire_bitcoin_key(&mut retired_keys, &identity.identity);
+    write_retired_bitcoin_keys(&retired_keys);
+    write_identity_to_file(&new_identity);
+    rotation
+}
+
+/// Keeps the bitcoin key of `identity` in `retired_keys`. Bills name their parties with
+/// the key they had then, what was paid to them is still spent with that key.
+fn retire_bitcoin_key(retired_keys: &mut HashMap<String, String>, identity: &Identity) {
+    retired_keys.insert(
+        identity.bitcoin_public_key.clone(),
+        identity.bitcoin_private_key.clone(),
+    );
+}
+
+/// Bitcoin private keys in WIF the identity rotated away from, by their public key.
+pub fn read_retired_bitcoin_keys() -> HashMap<String, String> {
+    if !Path::new(IDENTITY_RETIRED_KEYS_FILE_PATH).exists() {
+        return HashMap::new();
     }
+    let data =
+        keystore::read_file(IDENTITY_RETIRED_KEYS_FILE_PATH).expect("Unable to read retired keys");
+    serde_json::from_slice(&data).expect("Unable to parse retired keys")
+}
+
+fn write_retired_bitcoin_keys(retired_keys: &HashMap<String, String>) {
+    keystore::write_file(
+        IDENTITY_RETIRED_KEYS_FILE_PATH,
+        serde_json::to_string_pretty(retired_keys)
+            .unwrap()
+            .as_bytes(),
+    )
+    .expect("Unable to write retired keys");
+}
+
+/// Our bitcoin private key in WIF for `public_key`, the current key of `identity` or one
+/// of the `retired_keys` it rotated away from.
+pub fn bitcoin_private_key_for(
+    identity: &Identity,
+    retired_keys: &HashMap<String, String>,
+    public_key: &str,
+) -> Option<String> {
+    if identity.bitcoin_public_key == public_key {
+        return Some(identity.bitcoin_private_key.clone());
+    }
+    retired_keys.get(public_key).cloned()
 }
 
 fn write_identity_to_file(identity: &Identity) {
     let data: Vec<u8> = identity_to_byte_array(identity);
-    fs::write(IDENTITY_FILE_PATH, data).expect("Unable to write file identity");
+    keystore::write_file(IDENTITY_FILE_PATH, &data).expect("Unable to write file identity");
 }
 
 fn write_ed25519_keypair_to_file(ed25519_keys: &Keypair) {
-    let data: &[u8] = unsafe { structure_as_u8_slice(ed25519_keys) };
-    let data_sized = byte_array_to_size_array_keypair(data);
-    fs::write(IDENTITY_ED_25529_KEYS_FILE_PATH, *data_sized)
+    let data: Vec<u8> = ed25519_keys
+        .to_protobuf_encoding()
+        .expect("Unable to encode keypair ed25519");
+    keystore::write_file(IDENTITY_ED_25529_KEYS_FILE_PATH, &data)
         .expect("Unable to write keypair ed25519 in file");
 }
 
 fn write_peer_id_to_file(peer_id: &PeerId) {
-    let data: &[u8] = unsafe { structure_as_u8_slice(peer_id) };
-    let data_sized = byte_array_to_size_array_peer_id(data);
-    fs::write(IDENTITY_PEER_ID_FILE_PATH, *data_sized).expect("Unable to write peer id in file");
+    fs::write(IDENTITY_PEER_ID_FILE_PATH, peer_id.to_bytes())
+        .expect("Unable to write peer id in file");
+}
+
+fn write_mnemonic_to_file(mnemonic: &Mnemonic) {
+    keystore::write_file(IDENTITY_SEED_FILE_PATH, mnemonic.entropy())
+        .expect("Unable to write file seed");
+}
+
+/// Seed phrase of the identity, identities created before seed phrases have none.
+pub fn read_mnemonic_from_file() -> Option<Mnemonic> {
+    if !Path::new(IDENTITY_SEED_FILE_PATH).exists() {
+        return None;
+    }
+    let data: Vec<u8> =
+        keystore::read_file(IDENTITY_SEED_FILE_PATH).expect("Unable to read file seed");
+    Some(Mnemonic::from_entropy(&data).expect("Unable to read seed"))
 }
 
 fn read_identity_from_file() -> Identity {
-    let data: Vec<u8> = fs::read(IDENTITY_FILE_PATH).expect("Unable to read file identity");
+    let data: Vec<u8> =
+        keystore::read_file(IDENTITY_FILE_PATH).expect("Unable to read file identity");
     identity_from_byte_array(&data)
 }
 
 fn read_ed25519_keypair_from_file() -> Keypair {
     let data: Vec<u8> =
-        fs::read(IDENTITY_ED_25529_KEYS_FILE_PATH).expect("Unable to read file keypair");
-    let key_pair_bytes_sized = byte_array_to_size_array_keypair(data.as_slice());
-    let key_pair: Keypair = unsafe { mem::transmute_copy(key_pair_bytes_sized) };
-    key_pair
+        keystore::read_file(IDENTITY_ED_25529_KEYS_FILE_PATH).expect("Unable to read file keypair");
+    Keypair::from_protobuf_encoding(&data).expect("Unable to decode keypair ed25519")
 }
 
 fn read_peer_id_from_file() -> PeerId {
     let data: Vec<u8> =
         fs::read(IDENTITY_PEER_ID_FILE_PATH).expect("Unable to read file with peer id");
-    let peer_id_bytes_sized = byte_array_to_size_array_peer_id(data.as_slice());
-    let peer_id: PeerId = unsafe { mem::transmute_copy(peer_id_bytes_sized) };
-    peer_id
+    PeerId::from_bytes(&data).expect("Unable to decode peer id")
 }
 
-fn identity_to_byte_array(identity: &Identity) -> Vec<u8> {
-    identity.try_to_vec().unwrap()
+/// Before the protobuf encoding the keypair and peer id files held the raw memory of
+/// `Keypair` and `PeerId`. They are rewritten once, the peer id derived from the keypair.
+fn migrate_dht_logic_files() {
+    if !Path::new(IDENTITY_ED_25529_KEYS_FILE_PATH).exists() {
+        return;
+    }
+    let data: Vec<u8> =
+        keystore::read_file(IDENTITY_ED_25529_KEYS_FILE_PATH).expect("Unable to read file keypair");
+    let peer_id_is_encoded = fs::read(IDENTITY_PEER_ID_FILE_PATH)
+        .is_ok_and(|peer_id| PeerId::from_bytes(&peer_id).is_ok());
+    let key_pair = match Keypair::from_protobuf_encoding(&data) {
+        Ok(_) if peer_id_is_encoded => return,
+        Ok(key_pair) => key_pair,
+        Err(_) => keypair_from_legacy_bytes(&data).expect("Unable to migrate keypair ed25519"),
+    };
+    write_dht_logic(&key_pair.public().to_peer_id(), &key_pair);
 }
 
-fn identity_from_byte_array(identity: &Vec<u8>) -> Identity {
-    Identity::try_from_slice(identity).unwrap()
+/// Length of the memory of an old `Keypair`, an ed25519-dalek 1 key pair: the compressed
+/// public key, its decompressed point and the secret key.
+const LEGACY_KEYPAIR_LEN: usize = 224;
+const LEGACY_PUBLIC_KEY: Range<usize> = 0..32;
+const LEGACY_SECRET_KEY: Range<usize> = 192..224;
+
+/// Reads the secret key out of the memory of an old `Keypair`, if its public key matches.
+fn keypair_from_legacy_bytes(data: &[u8]) -> Option<Keypair> {
+    if data.len() != LEGACY_KEYPAIR_LEN {
+        return None;
+    }
+    let key_pair = Keypair::ed25519_from_bytes(data[LEGACY_SECRET_KEY].to_vec()).ok()?;
+    let public_key = key_pair.public().try_into_ed25519().ok()?.to_bytes();
+    (public_key[..] == data[LEGACY_PUBLIC_KEY]).then_some(key_pair)
 }
 
-fn byte_array_to_size_array_keypair(array: &[u8]) -> &[u8; ::std::mem::size_of::<Keypair>()] {
-    array.try_into().expect("slice with incorrect length")
+fn identity_to_byte_array(identity: &Identity) -> Vec<u8> {
+    identity.try_to_vec().unwrap()
 }
 
-fn byte_array_to_size_array_peer_id(array: &[u8]) -> &[u8; ::std::mem::size_of::<PeerId>()] {
-    array.try_into().expect("slice with incorrect length")
+fn identity_from_byte_array(identity: &Vec<u8>) -> Identity {
+    Identity::try_from_slice(identity).unwrap()
 }
 //--------------------------------------------------------------
 
 //-------------------------Bill---------------------------------
-#[derive(BorshSerialize, BorshDeserialize, FromForm, Debug, Serialize, Deserialize, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Serialize, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct BitcreditBill {
     name: String,
@@ -605,18 +757,26 @@ pub struct BitcreditBill {
     endorsee: IdentityPublicData,
     place_of_drawing: String,
     currency_code: String,
-    //TODO: f64
+    // In satoshi for bitcoin currencies and in hundredths for fiat, see `Amount`.
     amount_numbers: u64,
     amounts_letters: String,
     maturity_date: String,
     date_of_issue: String,
+    // Yearly interest rate in basis points, 1% is 100.
     compounding_interest_rate: u64,
+    // Compound interest if true, simple interest otherwise.
     type_of_interest_calculation: bool,
     // Defaulting to the drawee’s id/ address.
     place_of_payment: String,
     public_key: String,
+    // Part of the payout key, never shown in pages or API responses.
+    #[serde(skip_serializing)]
     private_key: String,
     language: String,
+    // Empty `maturity_date` until a bill payable at or after sight is presented.
+    maturity: Maturity,
+    #[serde(default)]
+    payment_address_type: PaymentAddressType,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -625,14 +785,13 @@ pub struct BillKeys {
     public_key_pem: String,
 }
 
+/// Issues the bill of `bill_form`, whose amount and maturity were parsed and checked into
+/// `amount` and `maturity`.
 pub fn issue_new_bill(
-    bill_jurisdiction: String,
-    place_of_drawing: String,
-    amount_numbers: u64,
-    place_of_payment: String,
-    maturity_date: String,
+    bill_form: BitcreditBillForm,
+    amount: Amount,
+    maturity: Maturity,
     drawer: IdentityWithAll,
-    language: String,
     public_data_drawee: IdentityPublicData,
     public_data_payee: IdentityPublicData,
 ) -> BitcreditBill {
@@ -658,14 +817,22 @@ pub fn issue_new_bill(
         public_key_pem.clone(),
     );
 
-    let amount_letters: String = encode(&amount_numbers);
+    let amount_letters: String =
+        encode_amount(&amount, &bill_form.language).expect("Language of the bill is not supported");
 
     let public_data_drawer =
         IdentityPublicData::new(drawer.identity.clone(), drawer.peer_id.to_string().clone());
 
     let utc = Utc::now();
     let timestamp_at_drawing = utc.timestamp();
-    let date_of_issue = utc.naive_local().date().to_string();
+    let date_of_issue = utc.naive_local().date();
+    let maturity_date = match maturity {
+        Maturity::FixedDate => bill_form.maturity_date,
+        _ => maturity
+            .fixed_due_date(date_of_issue, &bill_form.maturity_date)
+            .map(|due_date| due_date.to_string())
+            .unwrap_or_default(),
+    };
     // let maturity_date = utc
     //     .checked_add_days(Days::new(BILL_VALIDITY_PERIOD))
     //     .unwrap()
@@ -676,20 +843,22 @@ pub fn issue_new_bill(
     let new_bill = BitcreditBill {
         name: bill_name.clone(),
         to_payee: false,
-        bill_jurisdiction,
+        bill_jurisdiction: bill_form.bill_jurisdiction,
         timestamp_at_drawing,
-        place_of_drawing,
-        currency_code: SATOSHI.to_string(),
-        amount_numbers,
+        place_of_drawing: bill_form.place_of_drawing,
+        currency_code: amount.currency().code(),
+        amount_numbers: amount.minor_units(),
         amounts_letters: amount_letters,
         maturity_date,
-        date_of_issue,
-        compounding_interest_rate: COMPOUNDING_INTEREST_RATE_ZERO,
-        type_of_interest_calculation: false,
-        place_of_payment,
+        date_of_issue: date_of_issue.to_string(),
+        compounding_interest_rate: bill_form.compounding_interest_rate,
+        type_of_interest_calculation: bill_form.type_of_interest_calculation,
+        place_of_payment: bill_form.place_of_payment,
         public_key: public_key_bitcoin,
         private_key: private_key_bitcoin,
-        language,
+        language: bill_form.language,
+        maturity,
+        payment_address_type: PaymentAddressType::configured(),
         drawee: public_data_drawee,
         drawer: public_data_drawer,
         payee: public_data_payee,
@@ -706,8 +875,8 @@ pub fn issue_new_bill(
     start_blockchain_for_new_bill(
         &new_bill,
         OperationCode::Issue,
-        drawer.identity.public_key_pem.clone(),
-        drawer.identity.private_key_pem.clone(),
+        drawer.identity.bitcoin_public_key.clone(),
+        drawer.identity.bitcoin_private_key.clone(),
         private_key_pem.clone(),
     );
 
@@ -721,9 +890,9 @@ fn write_bill_keys_to_file(bill_name: String, private_key: String, public_key: S
     };
 
     let output_path = BILLS_KEYS_FOLDER_PATH.to_string() + "/" + bill_name.as_str() + ".json";
-    std::fs::write(
-        output_path.clone(),
-        serde_json::to_string_pretty(&keys).unwrap(),
+    keystore::write_file(
+        &output_path,
+        serde_json::to_string_pretty(&keys).unwrap().as_bytes(),
     )
     .unwrap();
 }
@@ -758,54 +927,23 @@ pub fn get_bills() -> Vec<BitcreditBill> {
 
 pub fn endorse_bitcredit_bill(bill_name: &String, endorsee: IdentityPublicData) -> bool {
     let my_peer_id = read_peer_id_from_file().to_string();
-    let mut bill = read_bill_from_file(&bill_name);
-
-    let mut blockchain_from_file = Chain::read_chain_from_file(bill_name);
-    let last_block = blockchain_from_file.get_latest_block();
+    let bill = read_bill_from_file(bill_name);
 
-    let exist_block_with_code_endorse =
-        blockchain_from_file.exist_block_with_operation_code(OperationCode::Endorse);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
 
-    if (my_peer_id.eq(&bill.payee.peer_id) && !exist_block_with_code_endorse)
-        || (my_peer_id.eq(&bill.endorsee.peer_id))
-    {
+    if is_current_holder(&bill, &blockchain_from_file, &my_peer_id) {
         let identity = get_whole_identity();
-
         let my_identity_public =
             IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
-        let endorsed_by = serde_json::to_vec(&my_identity_public).unwrap();
-
-        let data_for_new_block_in_bytes = serde_json::to_vec(&endorsee).unwrap();
-        let data_for_new_block = "Endorsed to ".to_string()
-            + &hex::encode(data_for_new_block_in_bytes)
-            + " endorsed by "
-            + &hex::encode(endorsed_by);
-
-        let keys = read_keys_from_bill_file(&bill_name);
-        let key: Rsa<Private> = Rsa::private_key_from_pem(keys.private_key_pem.as_bytes()).unwrap();
-
-        let data_for_new_block_in_bytes = data_for_new_block.as_bytes().to_vec();
-        let data_for_new_block_encrypted = encrypt_bytes(&data_for_new_block_in_bytes, &key);
-        let data_for_new_block_encrypted_in_string_format =
-            hex::encode(data_for_new_block_encrypted);
-
-        let new_block = Block::new(
-            last_block.id + 1,
-            last_block.hash.clone(),
-            data_for_new_block_encrypted_in_string_format,
-            bill_name.clone(),
-            identity.identity.public_key_pem.clone(),
-            OperationCode::Endorse,
-            identity.identity.private_key_pem.clone(),
-        );
 
-        let try_add_block = blockchain_from_file.try_add_block(new_block.clone());
-        if try_add_block && blockchain_from_file.is_chain_valid() {
-            blockchain_from_file.write_chain_to_file(&bill.name);
-            true
-        } else {
-            false
-        }
+        append_block_to_bill(
+            bill_name,
+            BlockData::Endorse {
+                endorsee,
+                endorser: my_identity_public,
+            },
+            &identity,
+        )
     } else {
         false
     }
@@ -815,148 +953,422 @@ pub fn request_pay(bill_name: &String) -> bool {
     let my_peer_id = read_peer_id_from_file().to_string();
     let bill = read_bill_from_file(bill_name);
 
-    let mut blockchain_from_file = Chain::read_chain_from_file(bill_name);
-    let last_block = blockchain_from_file.get_latest_block();
-
-    let exist_block_with_code_endorse =
-        blockchain_from_file.exist_block_with_operation_code(OperationCode::Endorse);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
 
-    if (my_peer_id.eq(&bill.payee.peer_id) && !exist_block_with_code_endorse)
-        || (my_peer_id.eq(&bill.endorsee.peer_id))
-    {
+    if is_current_holder(&bill, &blockchain_from_file, &my_peer_id) {
         let identity = get_whole_identity();
+        let my_identity_public =
+            IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
 
+        append_block_to_bill(
+            bill_name,
+            BlockData::RequestToPay {
+                requester: my_identity_public,
+            },
+            &identity,
+        )
+    } else {
+        false
+    }
+}
+
+pub fn request_acceptance(bill_name: &String) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let bill = read_bill_from_file(bill_name);
+
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+
+    if is_current_holder(&bill, &blockchain_from_file, &my_peer_id) {
+        let identity = get_whole_identity();
         let my_identity_public =
             IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
 
-        let data_for_new_block_in_bytes = serde_json::to_vec(&my_identity_public).unwrap();
-        let data_for_new_block =
-            "Requested to pay by ".to_string() + &hex::encode(data_for_new_block_in_bytes);
-
-        let keys = read_keys_from_bill_file(&bill_name);
-        let key: Rsa<Private> = Rsa::private_key_from_pem(keys.private_key_pem.as_bytes()).unwrap();
-
-        let data_for_new_block_in_bytes = data_for_new_block.as_bytes().to_vec();
-        let data_for_new_block_encrypted = encrypt_bytes(&data_for_new_block_in_bytes, &key);
-        let data_for_new_block_encrypted_in_string_format =
-            hex::encode(data_for_new_block_encrypted);
-
-        let new_block = Block::new(
-            last_block.id + 1,
-            last_block.hash.clone(),
-            data_for_new_block_encrypted_in_string_format,
-            bill_name.clone(),
-            identity.identity.public_key_pem.clone(),
-            OperationCode::RequestToPay,
-            identity.identity.private_key_pem.clone(),
-        );
+        append_block_to_bill(
+            bill_name,
+            BlockData::RequestToAccept {
+                requester: my_identity_public,
+            },
+            &identity,
+        )
+    } else {
+        false
+    }
+}
+
+/// Accepts `amount` of the bill. Less than the bill amount is a partial acceptance.
+pub fn accept_bill(bill_name: &String, amount: u64) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let bill = read_bill_from_file(bill_name);
+    let may_accept = Chain::read_chain_from_file(bill_name)
+        .get_state()
+        .is_ok_and(|state| state.may_accept(amount));
+
+    if bill.drawee.peer_id.eq(&my_peer_id) && may_accept {
+        let identity = get_whole_identity();
+        let my_identity_public =
+            IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
 
-        let try_add_block = blockchain_from_file.try_add_block(new_block.clone());
-        if try_add_block && blockchain_from_file.is_chain_valid() {
-            blockchain_from_file.write_chain_to_file(&bill.name);
-            true
+        let block_data = if amount == bill.amount_numbers {
+            BlockData::Accept {
+                accepter: my_identity_public,
+            }
         } else {
-            false
-        }
+            BlockData::PartialAccept {
+                accepter: my_identity_public,
+                amount,
+            }
+        };
+
+        append_block_to_bill(bill_name, block_data, &identity)
     } else {
         false
     }
 }
 
-pub fn request_acceptance(bill_name: &String) -> bool {
+pub fn protest_non_acceptance(bill_name: &String) -> bool {
     let my_peer_id = read_peer_id_from_file().to_string();
     let bill = read_bill_from_file(bill_name);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+    let today = Utc::now().naive_local().date();
 
-    let mut blockchain_from_file = Chain::read_chain_from_file(bill_name);
-    let last_block = blockchain_from_file.get_latest_block();
+    if can_protest_non_acceptance(&bill, &blockchain_from_file, &my_peer_id, today) {
+        let identity = get_whole_identity();
+        let my_identity_public =
+            IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
+        let not_accepted_amount =
+            bill.amount_numbers - blockchain_from_file.get_accepted_amount().unwrap_or(0);
+        let protest = ProtestDocument::new(
+            &bill,
+            ProtestReason::NonAcceptance,
+            my_identity_public,
+            not_accepted_amount,
+            today,
+            identity.identity.private_key_pem.clone(),
+        );
 
-    let exist_block_with_code_endorse =
-        blockchain_from_file.exist_block_with_operation_code(OperationCode::Endorse);
+        append_block_to_bill(
+            bill_name,
+            BlockData::ProtestNonAcceptance { protest },
+            &identity,
+        )
+    } else {
+        false
+    }
+}
+
+/// Protests non-payment of `unpaid_amount`, which the caller takes from the payment balance.
+pub fn protest_non_payment(bill_name: &String, unpaid_amount: u64) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let bill = read_bill_from_file(bill_name);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+    let today = Utc::now().naive_local().date();
 
-    if (my_peer_id.eq(&bill.payee.peer_id) && !exist_block_with_code_endorse)
-        || (my_peer_id.eq(&bill.endorsee.peer_id))
+    if unpaid_amount > 0
+        && can_protest_non_payment(&bill, &blockchain_from_file, &my_peer_id, today)
     {
         let identity = get_whole_identity();
-
         let my_identity_public =
             IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
-
-        let data_for_new_block_in_bytes = serde_json::to_vec(&my_identity_public).unwrap();
-        let data_for_new_block =
-            "Requested to accept by ".to_string() + &hex::encode(data_for_new_block_in_bytes);
-
-        let keys = read_keys_from_bill_file(&bill_name);
-        let key: Rsa<Private> = Rsa::private_key_from_pem(keys.private_key_pem.as_bytes()).unwrap();
-
-        let data_for_new_block_in_bytes = data_for_new_block.as_bytes().to_vec();
-        let data_for_new_block_encrypted = encrypt_bytes(&data_for_new_block_in_bytes, &key);
-        let data_for_new_block_encrypted_in_string_format =
-            hex::encode(data_for_new_block_encrypted);
-
-        let new_block = Block::new(
-            last_block.id + 1,
-            last_block.hash.clone(),
-            data_for_new_block_encrypted_in_string_format,
-            bill_name.clone(),
-            identity.identity.public_key_pem.clone(),
-            OperationCode::RequestToAccept,
+        let protest = ProtestDocument::new(
+            &bill,
+            ProtestReason::NonPayment,
+            my_identity_public,
+            unpaid_amount,
+            today,
             identity.identity.private_key_pem.clone(),
         );
 
-        let try_add_block = blockchain_from_file.try_add_block(new_block.clone());
-        if try_add_block && blockchain_from_file.is_chain_valid() {
-            blockchain_from_file.write_chain_to_file(&bill.name);
-            true
-        } else {
-            false
-        }
+        append_block_to_bill(
+            bill_name,
+            BlockData::ProtestNonPayment { protest },
+            &identity,
+        )
     } else {
         false
     }
 }
 
-pub fn accept_bill(bill_name: &String) -> bool {
+/// Takes recourse against `recoursee_peer_id`, refused while `open_recourse`, the last
+/// recourse on the bill is not paid yet.
+pub fn recourse(
+    bill_name: &String,
+    recoursee_peer_id: &String,
+    costs: u64,
+    open_recourse: bool,
+) -> bool {
     let my_peer_id = read_peer_id_from_file().to_string();
     let bill = read_bill_from_file(bill_name);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+    let today = Utc::now().naive_local().date();
 
-    let mut blockchain_from_file = Chain::read_chain_from_file(bill_name);
-    let last_block = blockchain_from_file.get_latest_block();
+    let recoursee =
+        get_recourse_candidates(&bill, &blockchain_from_file, &my_peer_id, open_recourse)
+            .into_iter()
+            .find(|party| party.peer_id.eq(recoursee_peer_id));
 
-    if bill.drawee.peer_id.eq(&my_peer_id) {
+    if let Some(recoursee) = recoursee {
         let identity = get_whole_identity();
+        let my_identity_public =
+            IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
+        let protested_amount = blockchain_from_file
+            .get_protests()
+            .iter()
+            .try_fold(0u64, |sum, protest| sum.checked_add(protest.amount_numbers))
+            .ok_or(AmountError::Overflow);
+        let recourse = protested_amount.and_then(|protested_amount| {
+            RecourseClaim::new(
+                &bill,
+                my_identity_public,
+                recoursee,
+                protested_amount,
+                costs,
+                today,
+            )
+        });
+
+        match recourse {
+            Ok(recourse) => {
+                append_block_to_bill(bill_name, BlockData::Recourse { recourse }, &identity)
+            }
+            Err(error) => {
+                error!("can not take recourse on bill {}: {}", bill_name, error);
+                false
+            }
+        }
+    } else {
+        false
+    }
+}
+
+pub fn request_aval(bill_name: &String, guarantor: IdentityPublicData) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let bill = read_bill_from_file(bill_name);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
 
+    if can_request_aval(&bill, &blockchain_from_file, &my_peer_id)
+        && !guarantor.peer_id.is_empty()
+        && !guarantor.peer_id.eq(&my_peer_id)
+    {
+        let identity = get_whole_identity();
         let my_identity_public =
             IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
 
-        let data_for_new_block_in_bytes = serde_json::to_vec(&my_identity_public).unwrap();
-        let data_for_new_block =
-            "Accepted by ".to_string() + &hex::encode(data_for_new_block_in_bytes);
-
-        let keys = read_keys_from_bill_file(&bill_name);
-        let key: Rsa<Private> = Rsa::private_key_from_pem(keys.private_key_pem.as_bytes()).unwrap();
-
-        let data_for_new_block_in_bytes = data_for_new_block.as_bytes().to_vec();
-        let data_for_new_block_encrypted = encrypt_bytes(&data_for_new_block_in_bytes, &key);
-        let data_for_new_block_encrypted_in_string_format =
-            hex::encode(data_for_new_block_encrypted);
-
-        let new_block = Block::new(
-            last_block.id + 1,
-            last_block.hash.clone(),
-            data_for_new_block_encrypted_in_string_format,
-            bill_name.clone(),
-            identity.identity.public_key_pem.clone(),
-            OperationCode::Accept,
-            identity.identity.private_key_pem.clone(),
-        );
+        append_block_to_bill(
+            bill_name,
+            BlockData::RequestToAval {
+                requester: my_identity_public,
+                guarantor,
+            },
+            &identity,
+        )
+    } else {
+        false
+    }
+}
 
-        let try_add_block = blockchain_from_file.try_add_block(new_block.clone());
-        if try_add_block && blockchain_from_file.is_chain_valid() {
-            blockchain_from_file.write_chain_to_file(&bill.name);
-            true
-        } else {
-            false
+pub fn aval_bill(bill_name: &String) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+
+    if let Some(guaranteed) = blockchain_from_file.get_pending_aval_request(&my_peer_id) {
+        let identity = get_whole_identity();
+        let my_identity_public =
+            IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
+
+        append_block_to_bill(
+            bill_name,
+            BlockData::Aval {
+                guarantor: my_identity_public,
+                guaranteed,
+            },
+            &identity,
+        )
+    } else {
+        false
+    }
+}
+
+/// Records that the payments `txids` to `payment_address`, or the Lightning payment,
+/// covered `amount`. Only the holder of an unpaid bill can.
+pub fn record_payment(
+    bill_name: &String,
+    payment_address: String,
+    amount: u64,
+    txids: Vec<String>,
+    lightning: Option<LightningPayment>,
+) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let bill = read_bill_from_file(bill_name);
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+    if blockchain_from_file.is_paid()
+        || !is_current_holder(&bill, &blockchain_from_file, &my_peer_id)
+    {
+        return false;
+    }
+
+    let identity = get_whole_identity();
+    let my_identity_public =
+        IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
+    append_block_to_bill(
+        bill_name,
+        BlockData::Paid {
+            holder: my_identity_public,
+            payment_address,
+            amount,
+            txids,
+            lightning,
+        },
+        &identity,
+    )
+}
+
+/// Records that the payments `txids` covered our open recourse. Only the recourser can.
+pub fn record_recourse_payment(bill_name: &String, txids: Vec<String>) -> bool {
+    let my_peer_id = read_peer_id_from_file().to_string();
+    let blockchain_from_file = Chain::read_chain_from_file(bill_name);
+    let recourse = match blockchain_from_file.get_recourses().pop() {
+        Some(recourse)
+            if recourse.recourser.peer_id.eq(&my_peer_id)
+                && has_open_recourse(&blockchain_from_file) =>
+        {
+            recourse
+        }
+        _ => return false,
+    };
+
+    let identity = get_whole_identity();
+    let my_identity_public =
+        IdentityPublicData::new(identity.identity.clone(), identity.peer_id.to_string());
+    append_block_to_bill(
+        bill_name,
+        BlockData::RecoursePaid {
+            recourser: my_identity_public,
+            recoursee: recourse.recoursee,
+            txids,
+        },
+        &identity,
+    )
+}
+
+/// The drawee and everybody liable on the bill may ask for an aval for their obligation.
+pub fn can_request_aval(bill: &BitcreditBill, chain: &Chain, peer_id: &String) -> bool {
+    peer_id.eq(&bill.drawee.peer_id)
+        || chain
+            .get_liable_parties()
+            .iter()
+            .any(|party| party.peer_id.eq(peer_id))
+}
+
+/// After a protest the holder may take recourse against the drawer or a prior endorser,
+/// one at a time and once against each of them.
+pub fn get_recourse_candidates(
+    bill: &BitcreditBill,
+    chain: &Chain,
+    peer_id: &String,
+    open_recourse: bool,
+) -> Vec<IdentityPublicData> {
+    let protested = chain.exist_block_with_operation_code(OperationCode::ProtestNonAcceptance)
+        || chain.exist_block_with_operation_code(OperationCode::ProtestNonPayment);
+    if !protested || !is_current_holder(bill, chain, peer_id) {
+        return Vec::new();
+    }
+
+    remaining_recoursees(
+        chain.get_liable_parties(),
+        &chain.get_recourses(),
+        peer_id,
+        open_recourse,
+    )
+}
+
+/// The holder may protest non-acceptance once acceptance was requested and refused,
+/// fully or in part, up to the acceptance deadline.
+pub fn can_protest_non_acceptance(
+    bill: &BitcreditBill,
+    chain: &Chain,
+    peer_id: &String,
+    today: NaiveDate,
+) -> bool {
+    is_current_holder(bill, chain, peer_id)
+        && chain.exist_block_with_operation_code(OperationCode::RequestToAccept)
+        && chain.get_accepted_amount().unwrap_or(0) < bill.amount_numbers
+        && !chain.exist_block_with_operation_code(OperationCode::ProtestNonAcceptance)
+        && chain
+            .get_acceptance_deadline()
+            .is_some_and(|deadline| is_within_non_acceptance_deadline(deadline, today))
+}
+
+/// The holder may protest non-payment once payment was requested, in the days
+/// following the maturity date and as long as no payment was recorded. Payments that are
+/// not recorded yet are checked by the caller.
+pub fn can_protest_non_payment(
+    bill: &BitcreditBill,
+    chain: &Chain,
+    peer_id: &String,
+    today: NaiveDate,
+) -> bool {
+    is_current_holder(bill, chain, peer_id)
+        && chain.exist_block_with_operation_code(OperationCode::RequestToPay)
+        && !chain.exist_block_with_operation_code(OperationCode::ProtestNonPayment)
+        && !chain.is_paid()
+        // A bill protested for non-acceptance as a whole is not presented for payment.
+        && (chain.get_accepted_amount().is_some()
+            || !chain.exist_block_with_operation_code(OperationCode::ProtestNonAcceptance))
+        && parse_maturity_date(&bill.maturity_date)
+            .is_some_and(|maturity| is_within_non_payment_deadline(maturity, today))
+}
+
+/// Party the bill is paid to, the last endorsee or the payee.
+pub fn bill_holder(bill: &BitcreditBill) -> &IdentityPublicData {
+    if bill.endorsee.name.is_empty() {
+        &bill.payee
+    } else {
+        &bill.endorsee
+    }
+}
+
+fn is_current_holder(bill: &BitcreditBill, chain: &Chain, peer_id: &String) -> bool {
+    let exist_block_with_code_endorse =
+        chain.exist_block_with_operation_code(OperationCode::Endorse);
+
+    (peer_id.eq(&bill.payee.peer_id) && !exist_block_with_code_endorse)
+        || peer_id.eq(&bill.endorsee.peer_id)
+}
+
+/// Encrypts `block_data` with the bill key, signs a new block with our identity and
+/// appends it to the chain of `bill_name`.
+fn append_block_to_bill(
+    bill_name: &String,
+    block_data: BlockData,
+    identity: &IdentityWithAll,
+) -> bool {
+    let mut blockchain_from_file = Chain::read_chain_from_file(bill_name);
+    let last_block = blockchain_from_file.get_latest_block();
+
+    let keys = match read_keys_from_bill_file(bill_name) {
+        Ok(keys) => keys,
+        Err(error) => {
+            error!("can not read the keys of bill {}: {}", bill_name, error);
+            return false;
         }
+    };
+    let data_for_new_block_encrypted_in_string_format =
+        encrypted_block_data(&block_data, keys.private_key_pem);
+
+    let new_block = Block::new(
+        last_block.id + 1,
+        last_block.hash.clone(),
+        data_for_new_block_encrypted_in_string_format,
+        bill_name.clone(),
+        identity.identity.bitcoin_public_key.clone(),
+        block_data.operation_code(),
+        identity.identity.bitcoin_private_key.clone(),
+    );
+
+    let try_add_block = blockchain_from_file.try_add_block(new_block);
+    if try_add_block && blockchain_from_file.is_chain_valid() {
+        blockchain_from_file.write_chain_to_file(bill_name);
+        true
     } else {
         false
     }
@@ -972,14 +1384,10 @@ fn bill_to_byte_array(bill: &BitcreditBill) -> Vec<u8> {
     bill.try_to_vec().unwrap()
 }
 
-fn bill_from_byte_array(bill: &Vec<u8>) -> BitcreditBill {
-    BitcreditBill::try_from_slice(bill).unwrap()
-}
-
-fn read_keys_from_bill_file(bill_name: &String) -> BillKeys {
+fn read_keys_from_bill_file(bill_name: &String) -> Result<BillKeys, KeystoreError> {
     let input_path = BILLS_KEYS_FOLDER_PATH.to_string() + "/" + bill_name.as_str() + ".json";
-    let blockchain_from_file = std::fs::read(input_path.clone()).expect("file not found");
-    serde_json::from_slice(blockchain_from_file.as_slice()).unwrap()
+    let bill_keys_from_file = keystore::read_file(&input_path)?;
+    serde_json::from_slice(bill_keys_from_file.as_slice()).map_err(|_| KeystoreError::Malformed)
 }
 //--------------------------------------------------------------
 
@@ -989,12 +1397,24 @@ fn read_keys_from_bill_file(bill_name: &String) -> BillKeys {
 pub struct BitcreditBillForm {
     pub bill_jurisdiction: String,
     pub place_of_drawing: String,
-    pub amount_numbers: u64,
+    /// Decimal amount written in `currency_code`, like `1.5`.
+    pub amount: String,
+    pub currency_code: String,
     pub language: String,
     pub drawee_name: String,
     pub payee_name: String,
     pub place_of_payment: String,
+    #[serde(default)]
     pub maturity_date: String,
+    /// `date`, `sight`, `after_sight` or `after_date`, see `Maturity::from_form`.
+    #[serde(default)]
+    pub maturity_type: String,
+    #[serde(default)]
+    pub maturity_days: u32,
+    #[serde(default)]
+    pub compounding_interest_rate: u64,
+    #[serde(default)]
+    pub type_of_interest_calculation: bool,
 }
 
 #[derive(FromForm, Debug, Serialize, Deserialize)]
@@ -1016,11 +1436,77 @@ pub struct RequestToPayBitcreditBillForm {
     pub bill_name: String,
 }
 
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ProtestBitcreditBillForm {
+    pub bill_name: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RecourseBitcreditBillForm {
+    pub bill_name: String,
+    pub recoursee: String,
+    pub costs: u64,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RequestToAvalBitcreditBillForm {
+    pub guarantor: String,
+    pub bill_name: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AvalBitcreditBillForm {
+    pub bill_name: String,
+}
+
 #[derive(FromForm, Debug, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct AcceptBitcreditBillForm {
     pub bill_name: String,
     pub operation_code: String,
+    /// Decimal amount written in the bill currency.
+    pub amount: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SweepBitcreditBillForm {
+    pub bill_name: String,
+    pub destination: String,
+    /// Peer id of the recoursee to sweep the recourse payment of, empty for the bill.
+    pub recoursee: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InvoiceBitcreditBillForm {
+    pub bill_name: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PayInvoiceBitcreditBillForm {
+    pub bill_name: String,
+    pub invoice: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UnlockForm {
+    pub passphrase: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PassphraseForm {
+    /// Empty while no passphrase is set.
+    #[serde(default)]
+    pub current_passphrase: String,
+    pub new_passphrase: String,
 }
 
 #[derive(FromForm, Debug, Serialize, Deserialize)]
@@ -1034,6 +1520,43 @@ pub struct IdentityForm {
     postal_address: String,
 }
 
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RestoreIdentityForm {
+    pub mnemonic: String,
+    name: String,
+    date_of_birth: String,
+    city_of_birth: String,
+    country_of_birth: String,
+    email: String,
+    postal_address: String,
+}
+
+#[derive(FromForm, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RotateKeysForm {
+    /// The old keys leaked, signatures made with them from `compromised_since` on
+    /// are rejected.
+    #[serde(default)]
+    pub revoked: bool,
+    /// Date the keys leaked, now when left empty.
+    #[serde(default)]
+    pub compromised_since: String,
+}
+
+impl RotateKeysForm {
+    pub fn compromised_since(&self) -> Result<Option<i64>, chrono::ParseError> {
+        if !self.revoked {
+            return Ok(None);
+        }
+        if self.compromised_since.is_empty() {
+            return Ok(Some(Utc::now().timestamp()));
+        }
+        let date = NaiveDate::parse_from_str(&self.compromised_since, "%Y-%m-%d")?;
+        Ok(Some(date.and_hms_opt(0, 0, 0).unwrap().timestamp()))
+    }
+}
+
 #[derive(FromForm, Debug, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct NewContactForm {
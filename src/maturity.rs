@@ -0,0 +1,75 @@
+use std::fmt;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::DAYS_IN_YEAR;
+
+/// When a bill falls due.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Default,
+)]
+pub enum Maturity {
+    /// Due on the `maturity_date` of the bill.
+    #[default]
+    FixedDate,
+    /// Due when it is presented for payment.
+    AtSight,
+    /// Due `days` after it was accepted.
+    AfterSight { days: u32 },
+    /// Due `days` after the date of issue.
+    AfterDate { days: u32 },
+}
+
+impl Maturity {
+    /// Maturity chosen in the new bill form, `kind` is one of `date`, `sight`,
+    /// `after_sight` and `after_date`.
+    pub fn from_form(kind: &str, days: u32) -> Option<Self> {
+        match kind {
+            "" | "date" => Some(Maturity::FixedDate),
+            "sight" => Some(Maturity::AtSight),
+            "after_sight" if days > 0 => Some(Maturity::AfterSight { days }),
+            "after_date" if days > 0 => Some(Maturity::AfterDate { days }),
+            _ => None,
+        }
+    }
+
+    /// The due date if it does not depend on presentment.
+    pub fn fixed_due_date(
+        &self,
+        date_of_issue: NaiveDate,
+        maturity_date: &str,
+    ) -> Option<NaiveDate> {
+        match self {
+            Maturity::FixedDate => NaiveDate::parse_from_str(maturity_date, "%Y-%m-%d").ok(),
+            Maturity::AfterDate { days } => date_of_issue.checked_add_days(Days::new(*days as u64)),
+            Maturity::AtSight | Maturity::AfterSight { .. } => None,
+        }
+    }
+
+    /// Bills payable after sight have to be presented for acceptance within a year
+    /// of issue. Sight bills are presented for payment right away.
+    pub fn acceptance_deadline(
+        &self,
+        date_of_issue: NaiveDate,
+        maturity_date: &str,
+    ) -> Option<NaiveDate> {
+        match self {
+            Maturity::AtSight => None,
+            Maturity::AfterSight { .. } => date_of_issue.checked_add_days(Days::new(DAYS_IN_YEAR)),
+            _ => self.fixed_due_date(date_of_issue, maturity_date),
+        }
+    }
+}
+
+impl fmt::Display for Maturity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Maturity::FixedDate => write!(f, "at a fixed date"),
+            Maturity::AtSight => write!(f, "at sight"),
+            Maturity::AfterSight { days } => write!(f, "{} days after sight", days),
+            Maturity::AfterDate { days } => write!(f, "{} days after date", days),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fmt;
+
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+
+/// BIP39 English word list, sorted.
+const ENGLISH: &str = include_str!("english.txt");
+const BITS_PER_WORD: usize = 11;
+const PBKDF2_ROUNDS: usize = 2048;
+pub const SEED_LEN: usize = 64;
+/// New mnemonics have 24 words.
+const GENERATED_ENTROPY_LEN: usize = 32;
+
+/// BIP39 mnemonic, the entropy the words and the seed are made from.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MnemonicError {
+    EntropyLength(usize),
+    WordCount(usize),
+    UnknownWord(String),
+    Checksum,
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MnemonicError::EntropyLength(len) => {
+                write!(f, "entropy of {} bytes can't be written as words", len)
+            }
+            MnemonicError::WordCount(count) => write!(
+                f,
+                "seed phrase has {} words, it must have 12, 15, 18, 21 or 24",
+                count
+            ),
+            MnemonicError::UnknownWord(word) => {
+                write!(f, "'{}' is not a word of the seed phrase list", word)
+            }
+            MnemonicError::Checksum => write!(f, "seed phrase checksum is wrong"),
+        }
+    }
+}
+
+impl Error for MnemonicError {}
+
+impl Mnemonic {
+    pub fn generate() -> Self {
+        let mut entropy = vec![0; GENERATED_ENTROPY_LEN];
+        rand_bytes(&mut entropy).expect("Unable to generate entropy");
+        Self { entropy }
+    }
+
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, MnemonicError> {
+        if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err(MnemonicError::EntropyLength(entropy.len()));
+        }
+        Ok(Self {
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    /// Reads the words, separated by any whitespace and in any case.
+    pub fn from_phrase(phrase: &str) -> Result<Self, MnemonicError> {
+        let words: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+        if !matches!(words.len(), 12 | 15 | 18 | 21 | 24) {
+            return Err(MnemonicError::WordCount(words.len()));
+        }
+
+        let word_list = word_list();
+        let mut bits = Vec::with_capacity(words.len() * BITS_PER_WORD);
+        for word in words {
+            let index = word_list
+                .binary_search(&word.as_str())
+                .map_err(|_| MnemonicError::UnknownWord(word.clone()))?;
+            bits.extend((0..BITS_PER_WORD).rev().map(|bit| index >> bit & 1 == 1));
+        }
+
+        let entropy_bits = bits.len() / 33 * 32;
+        let entropy = bits[..entropy_bits]
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+            .collect();
+        let mnemonic = Self { entropy };
+        if mnemonic.bits() != bits {
+            return Err(MnemonicError::Checksum);
+        }
+        Ok(mnemonic)
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn phrase(&self) -> String {
+        let word_list = word_list();
+        self.bits()
+            .chunks(BITS_PER_WORD)
+            .map(|word| word_list[word.iter().fold(0, |acc, &bit| acc << 1 | bit as usize)])
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// BIP39 seed, PBKDF2-HMAC-SHA512 of the phrase. The passphrase is used as given,
+    /// without Unicode normalization.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; SEED_LEN] {
+        let salt = "mnemonic".to_string() + passphrase;
+        let mut seed = [0; SEED_LEN];
+        pbkdf2_hmac(
+            self.phrase().as_bytes(),
+            salt.as_bytes(),
+            PBKDF2_ROUNDS,
+            MessageDigest::sha512(),
+            &mut seed,
+        )
+        .expect("Unable to derive seed");
+        seed
+    }
+
+    /// Entropy bits followed by the checksum, the first bits of its SHA-256.
+    fn bits(&self) -> Vec<bool> {
+        let checksum = sha256(&self.entropy);
+        let checksum_bits = self.entropy.len() / 4;
+        let bytes_to_bits = |&byte: &u8| (0..8).rev().map(move |bit| byte >> bit & 1 == 1);
+        self.entropy
+            .iter()
+            .flat_map(bytes_to_bits)
+            .chain(checksum.iter().flat_map(bytes_to_bits).take(checksum_bits))
+            .collect()
+    }
+}
+
+fn word_list() -> Vec<&'static str> {
+    ENGLISH.lines().collect()
+}
@@ -1,5 +1,7 @@
 use std::iter::successors;
 
+use super::{CurrencyUnit, NumberWords};
+
 const ONES: [&str; 20] = [
     "zero",
     "one",
@@ -35,7 +37,7 @@ const ORDERS: [&str; 7] = [
     "quintillion",
 ];
 
-pub fn encode(num: &u64) -> String {
+fn encode(num: &u64) -> String {
     match num {
         0..=19 => ONES[*num as usize].to_string(),
         20..=99 => {
@@ -65,3 +67,33 @@ fn format_num(num: &u64, div: u64, order: &str) -> String {
         }
     }
 }
+
+pub struct English;
+
+impl NumberWords for English {
+    fn number(&self, num: u64) -> String {
+        encode(&num)
+    }
+
+    fn noun(&self, unit: &CurrencyUnit, count: u64) -> String {
+        let (singular, plural) = match unit {
+            CurrencyUnit::Bitcoin => ("bitcoin", "bitcoin"),
+            CurrencyUnit::MilliBitcoin => ("millibitcoin", "millibitcoin"),
+            CurrencyUnit::Satoshi => ("satoshi", "satoshi"),
+            CurrencyUnit::Fiat("EUR") => ("euro", "euros"),
+            CurrencyUnit::Fiat("USD") => ("dollar", "dollars"),
+            CurrencyUnit::Fiat("GBP") => ("pound", "pounds"),
+            CurrencyUnit::Fiat("CHF") => ("franc", "francs"),
+            CurrencyUnit::Fiat(code) => (*code, *code),
+            CurrencyUnit::FiatHundredth("GBP") => ("penny", "pence"),
+            CurrencyUnit::FiatHundredth("CHF") => ("centime", "centimes"),
+            CurrencyUnit::FiatHundredth("EUR" | "USD") => ("cent", "cents"),
+            CurrencyUnit::FiatHundredth(_) => ("hundredth", "hundredths"),
+        };
+        if count == 1 { singular } else { plural }.to_string()
+    }
+
+    fn and(&self) -> &'static str {
+        "and"
+    }
+}
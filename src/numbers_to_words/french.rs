@@ -0,0 +1,129 @@
+use super::{groups_of_thousand, CurrencyUnit, NumberWords};
+
+const ONES: [&str; 17] = [
+    "zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf", "dix", "onze",
+    "douze", "treize", "quatorze", "quinze", "seize",
+];
+const TENS: [&str; 7] = [
+    "zéro",
+    "dix",
+    "vingt",
+    "trente",
+    "quarante",
+    "cinquante",
+    "soixante",
+];
+/// Units from a million up, singular and plural.
+const ORDERS: [(&str, &str); 5] = [
+    ("million", "millions"),
+    ("milliard", "milliards"),
+    ("billion", "billions"),
+    ("billiard", "billiards"),
+    ("trillion", "trillions"),
+];
+
+fn below_hundred(num: u64) -> String {
+    match num {
+        0..=16 => ONES[num as usize].to_string(),
+        17..=19 => format!("dix-{}", ONES[(num - 10) as usize]),
+        20..=69 => match num % 10 {
+            0 => TENS[(num / 10) as usize].to_string(),
+            1 => format!("{} et un", TENS[(num / 10) as usize]),
+            lower => format!("{}-{}", TENS[(num / 10) as usize], ONES[lower as usize]),
+        },
+        71 => "soixante et onze".to_string(),
+        70..=79 => format!("soixante-{}", below_hundred(num - 60)),
+        80 => "quatre-vingts".to_string(),
+        _ => format!("quatre-vingt-{}", below_hundred(num - 80)),
+    }
+}
+
+fn below_thousand(num: u64) -> String {
+    match (num / 100, num % 100) {
+        (0, lower) => below_hundred(lower),
+        (1, 0) => "cent".to_string(),
+        (1, lower) => format!("cent {}", below_hundred(lower)),
+        (upper, 0) => format!("{} cents", ONES[upper as usize]),
+        (upper, lower) => format!("{} cent {}", ONES[upper as usize], below_hundred(lower)),
+    }
+}
+
+/// "vingts" and "cents" lose their plural in front of "mille".
+fn below_million(num: u64) -> String {
+    let thousands = match num / 1000 {
+        0 => None,
+        1 => Some("mille".to_string()),
+        upper => {
+            let mut upper = below_thousand(upper);
+            if upper.ends_with("vingts") || upper.ends_with("cents") {
+                upper.pop();
+            }
+            Some(format!("{} mille", upper))
+        }
+    };
+    match (thousands, num % 1000) {
+        (None, lower) => below_thousand(lower),
+        (Some(thousands), 0) => thousands,
+        (Some(thousands), lower) => format!("{} {}", thousands, below_thousand(lower)),
+    }
+}
+
+pub struct French;
+
+impl NumberWords for French {
+    fn number(&self, num: u64) -> String {
+        if num == 0 {
+            return ONES[0].to_string();
+        }
+        let groups = groups_of_thousand(num);
+        let mut words: Vec<String> = groups
+            .iter()
+            .enumerate()
+            .skip(2)
+            .rev()
+            .filter(|(_, group)| **group > 0)
+            .map(|(index, group)| {
+                let (singular, plural) = ORDERS[index - 2];
+                match group {
+                    1 => format!("un {}", singular),
+                    _ => format!("{} {}", below_thousand(*group), plural),
+                }
+            })
+            .collect();
+        let lower = num % 1_000_000;
+        if lower > 0 {
+            words.push(below_million(lower));
+        }
+        words.join(" ")
+    }
+
+    /// Nouns after a million or more take "de", like "un million d'euros".
+    fn noun(&self, unit: &CurrencyUnit, count: u64) -> String {
+        let (singular, plural) = match unit {
+            CurrencyUnit::Bitcoin => ("bitcoin", "bitcoins"),
+            CurrencyUnit::MilliBitcoin => ("millibitcoin", "millibitcoins"),
+            CurrencyUnit::Satoshi => ("satoshi", "satoshis"),
+            CurrencyUnit::Fiat("EUR") => ("euro", "euros"),
+            CurrencyUnit::Fiat("USD") => ("dollar", "dollars"),
+            CurrencyUnit::Fiat("GBP") => ("livre", "livres"),
+            CurrencyUnit::Fiat("CHF") => ("franc", "francs"),
+            CurrencyUnit::Fiat(code) => (*code, *code),
+            CurrencyUnit::FiatHundredth("GBP") => ("penny", "pence"),
+            CurrencyUnit::FiatHundredth("USD") => ("cent", "cents"),
+            CurrencyUnit::FiatHundredth("EUR" | "CHF") => ("centime", "centimes"),
+            CurrencyUnit::FiatHundredth(_) => ("centième", "centièmes"),
+        };
+        let noun = if count <= 1 { singular } else { plural };
+        if count == 0 || !count.is_multiple_of(1_000_000) {
+            noun.to_string()
+        } else if noun.starts_with(['a', 'e', 'i', 'o', 'u']) {
+            format!("d'{}", noun)
+        } else {
+            format!("de {}", noun)
+        }
+    }
+
+    fn and(&self) -> &'static str {
+        "et"
+    }
+}
@@ -0,0 +1,135 @@
+use super::{groups_of_thousand, CurrencyUnit, NumberWords};
+
+const ONES: [&str; 20] = [
+    "null",
+    "eins",
+    "zwei",
+    "drei",
+    "vier",
+    "fünf",
+    "sechs",
+    "sieben",
+    "acht",
+    "neun",
+    "zehn",
+    "elf",
+    "zwölf",
+    "dreizehn",
+    "vierzehn",
+    "fünfzehn",
+    "sechzehn",
+    "siebzehn",
+    "achtzehn",
+    "neunzehn",
+];
+const TENS: [&str; 10] = [
+    "null", "zehn", "zwanzig", "dreißig", "vierzig", "fünfzig", "sechzig", "siebzig", "achtzig",
+    "neunzig",
+];
+/// Units from a million up, singular and plural.
+const ORDERS: [(&str, &str); 5] = [
+    ("Million", "Millionen"),
+    ("Milliarde", "Milliarden"),
+    ("Billion", "Billionen"),
+    ("Billiarde", "Billiarden"),
+    ("Trillion", "Trillionen"),
+];
+
+/// "eins" is shortened to "ein" in front of another word.
+fn shorten(words: String) -> String {
+    match words.strip_suffix("eins") {
+        Some(stem) => format!("{}ein", stem),
+        None => words,
+    }
+}
+
+fn below_thousand(num: u64) -> String {
+    match num {
+        0..=19 => ONES[num as usize].to_string(),
+        20..=99 => match num % 10 {
+            0 => TENS[(num / 10) as usize].to_string(),
+            lower => format!(
+                "{}und{}",
+                shorten(ONES[lower as usize].to_string()),
+                TENS[(num / 10) as usize]
+            ),
+        },
+        _ => match num % 100 {
+            0 => format!("{}hundert", shorten(ONES[(num / 100) as usize].to_string())),
+            lower => format!(
+                "{}hundert{}",
+                shorten(ONES[(num / 100) as usize].to_string()),
+                below_thousand(lower)
+            ),
+        },
+    }
+}
+
+/// Numbers below a million are written as one word.
+fn below_million(num: u64) -> String {
+    match (num / 1000, num % 1000) {
+        (0, lower) => below_thousand(lower),
+        (upper, 0) => format!("{}tausend", shorten(below_thousand(upper))),
+        (upper, lower) => format!(
+            "{}tausend{}",
+            shorten(below_thousand(upper)),
+            below_thousand(lower)
+        ),
+    }
+}
+
+pub struct German;
+
+impl NumberWords for German {
+    fn number(&self, num: u64) -> String {
+        if num == 0 {
+            return ONES[0].to_string();
+        }
+        let groups = groups_of_thousand(num);
+        let mut words: Vec<String> = groups
+            .iter()
+            .enumerate()
+            .skip(2)
+            .rev()
+            .filter(|(_, group)| **group > 0)
+            .map(|(index, group)| {
+                let (singular, plural) = ORDERS[index - 2];
+                match group {
+                    1 => format!("eine {}", singular),
+                    _ => format!("{} {}", below_thousand(*group), plural),
+                }
+            })
+            .collect();
+        let lower = num % 1_000_000;
+        if lower > 0 {
+            words.push(below_million(lower));
+        }
+        words.join(" ")
+    }
+
+    fn number_before_noun(&self, num: u64) -> String {
+        shorten(self.number(num))
+    }
+
+    fn noun(&self, unit: &CurrencyUnit, count: u64) -> String {
+        let (singular, plural) = match unit {
+            CurrencyUnit::Bitcoin => ("Bitcoin", "Bitcoin"),
+            CurrencyUnit::MilliBitcoin => ("Millibitcoin", "Millibitcoin"),
+            CurrencyUnit::Satoshi => ("Satoshi", "Satoshi"),
+            CurrencyUnit::Fiat("EUR") => ("Euro", "Euro"),
+            CurrencyUnit::Fiat("USD") => ("Dollar", "Dollar"),
+            CurrencyUnit::Fiat("GBP") => ("Pfund", "Pfund"),
+            CurrencyUnit::Fiat("CHF") => ("Franken", "Franken"),
+            CurrencyUnit::Fiat(code) => (*code, *code),
+            CurrencyUnit::FiatHundredth("GBP") => ("Penny", "Pence"),
+            CurrencyUnit::FiatHundredth("CHF") => ("Rappen", "Rappen"),
+            CurrencyUnit::FiatHundredth("EUR" | "USD") => ("Cent", "Cent"),
+            CurrencyUnit::FiatHundredth(_) => ("Hundertstel", "Hundertstel"),
+        };
+        if count == 1 { singular } else { plural }.to_string()
+    }
+
+    fn and(&self) -> &'static str {
+        "und"
+    }
+}
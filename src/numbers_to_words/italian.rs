@@ -0,0 +1,159 @@
+use super::{groups_of_thousand, CurrencyUnit, NumberWords};
+
+const ONES: [&str; 20] = [
+    "zero",
+    "uno",
+    "due",
+    "tre",
+    "quattro",
+    "cinque",
+    "sei",
+    "sette",
+    "otto",
+    "nove",
+    "dieci",
+    "undici",
+    "dodici",
+    "tredici",
+    "quattordici",
+    "quindici",
+    "sedici",
+    "diciassette",
+    "diciotto",
+    "diciannove",
+];
+const TENS: [&str; 10] = [
+    "zero",
+    "dieci",
+    "venti",
+    "trenta",
+    "quaranta",
+    "cinquanta",
+    "sessanta",
+    "settanta",
+    "ottanta",
+    "novanta",
+];
+/// Units from a million up, singular and plural.
+const ORDERS: [(&str, &str); 5] = [
+    ("milione", "milioni"),
+    ("miliardo", "miliardi"),
+    ("bilione", "bilioni"),
+    ("biliardo", "biliardi"),
+    ("trilione", "trilioni"),
+];
+
+/// "uno" is shortened to "un" in front of a noun or "mila".
+fn shorten(words: String) -> String {
+    match words.strip_suffix("uno") {
+        Some(stem) => format!("{}un", stem),
+        None => words,
+    }
+}
+
+/// Joins two parts of a number, dropping the last vowel of `upper` when `lower` starts
+/// with one, like "ventotto" and "centottanta".
+fn join(upper: &str, lower: &str) -> String {
+    if lower.starts_with(['u', 'o']) {
+        format!("{}{}", &upper[..upper.len() - 1], lower)
+    } else {
+        format!("{}{}", upper, lower)
+    }
+}
+
+fn below_thousand(num: u64) -> String {
+    match num {
+        0..=19 => ONES[num as usize].to_string(),
+        20..=99 => match num % 10 {
+            0 => TENS[(num / 10) as usize].to_string(),
+            3 => format!("{}tré", TENS[(num / 10) as usize]),
+            lower => join(TENS[(num / 10) as usize], ONES[lower as usize]),
+        },
+        _ => {
+            let hundreds = match num / 100 {
+                1 => "cento".to_string(),
+                upper => format!("{}cento", ONES[upper as usize]),
+            };
+            match num % 100 {
+                0 => hundreds,
+                3 => format!("{}tré", hundreds),
+                lower if (80..=89).contains(&lower) || lower == 8 => {
+                    join(&hundreds, &below_thousand(lower))
+                }
+                lower => format!("{}{}", hundreds, below_thousand(lower)),
+            }
+        }
+    }
+}
+
+fn below_million(num: u64) -> String {
+    let thousands = match num / 1000 {
+        0 => String::new(),
+        1 => "mille".to_string(),
+        upper => format!("{}mila", shorten(below_thousand(upper))),
+    };
+    match num % 1000 {
+        0 => thousands,
+        lower => format!("{}{}", thousands, below_thousand(lower)),
+    }
+}
+
+pub struct Italian;
+
+impl NumberWords for Italian {
+    fn number(&self, num: u64) -> String {
+        if num == 0 {
+            return ONES[0].to_string();
+        }
+        let groups = groups_of_thousand(num);
+        let mut words: Vec<String> = groups
+            .iter()
+            .enumerate()
+            .skip(2)
+            .rev()
+            .filter(|(_, group)| **group > 0)
+            .map(|(index, group)| {
+                let (singular, plural) = ORDERS[index - 2];
+                match group {
+                    1 => format!("un {}", singular),
+                    _ => format!("{} {}", below_thousand(*group), plural),
+                }
+            })
+            .collect();
+        let lower = num % 1_000_000;
+        if lower > 0 {
+            words.push(below_million(lower));
+        }
+        words.join(" ")
+    }
+
+    fn number_before_noun(&self, num: u64) -> String {
+        shorten(self.number(num))
+    }
+
+    /// Nouns after a million or more take "di", like "un milione di euro".
+    fn noun(&self, unit: &CurrencyUnit, count: u64) -> String {
+        let (singular, plural) = match unit {
+            CurrencyUnit::Bitcoin => ("bitcoin", "bitcoin"),
+            CurrencyUnit::MilliBitcoin => ("millibitcoin", "millibitcoin"),
+            CurrencyUnit::Satoshi => ("satoshi", "satoshi"),
+            CurrencyUnit::Fiat("EUR") => ("euro", "euro"),
+            CurrencyUnit::Fiat("USD") => ("dollaro", "dollari"),
+            CurrencyUnit::Fiat("GBP") => ("sterlina", "sterline"),
+            CurrencyUnit::Fiat("CHF") => ("franco", "franchi"),
+            CurrencyUnit::Fiat(code) => (*code, *code),
+            CurrencyUnit::FiatHundredth("GBP") => ("penny", "pence"),
+            CurrencyUnit::FiatHundredth(_) => ("centesimo", "centesimi"),
+        };
+        let noun = if count == 1 { singular } else { plural };
+        if count == 0 || !count.is_multiple_of(1_000_000) {
+            noun.to_string()
+        } else {
+            format!("di {}", noun)
+        }
+    }
+
+    fn and(&self) -> &'static str {
+        "e"
+    }
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::amount::{Amount, Currency};
+use crate::constants::{NUMBER_SATOSHI_IN_mBTC, NUMBER_SATOSHI_IN_BTC};
+
+mod english;
+mod french;
+mod german;
+mod italian;
+mod spanish;
+
+/// Unit an amount is written in, see [`NumberWords::noun`].
+pub enum CurrencyUnit<'a> {
+    Bitcoin,
+    MilliBitcoin,
+    Satoshi,
+    /// The fiat currency with this code.
+    Fiat(&'a str),
+    /// The hundredth of the fiat currency with this code.
+    FiatHundredth(&'a str),
+}
+
+/// Rule set of one language.
+pub trait NumberWords {
+    fn number(&self, num: u64) -> String;
+
+    /// The number as written in front of a noun, like "ein" instead of "eins" in German.
+    fn number_before_noun(&self, num: u64) -> String {
+        self.number(num)
+    }
+
+    fn noun(&self, unit: &CurrencyUnit, count: u64) -> String;
+
+    /// Word between the last two parts of an amount.
+    fn and(&self) -> &'static str;
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnsupportedLanguage(pub String);
+
+impl fmt::Display for UnsupportedLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "amounts can not be written in words in language {:?}",
+            self.0
+        )
+    }
+}
+
+impl Error for UnsupportedLanguage {}
+
+/// Rule set for an ISO 639-1 language code of the bill.
+pub fn language(language: &str) -> Result<Box<dyn NumberWords>, UnsupportedLanguage> {
+    match language {
+        "en" => Ok(Box::new(english::English)),
+        "de" => Ok(Box::new(german::German)),
+        "fr" => Ok(Box::new(french::French)),
+        "es" => Ok(Box::new(spanish::Spanish)),
+        "it" => Ok(Box::new(italian::Italian)),
+        _ => Err(UnsupportedLanguage(language.to_string())),
+    }
+}
+
+pub fn encode(num: &u64, language_code: &str) -> Result<String, UnsupportedLanguage> {
+    Ok(language(language_code)?.number(*num))
+}
+
+/// Writes an amount in words, like "one bitcoin and fifty millibitcoin".
+pub fn encode_amount(amount: &Amount, language_code: &str) -> Result<String, UnsupportedLanguage> {
+    let words = language(language_code)?;
+    let minor_units = amount.minor_units();
+    let parts: Vec<(u64, CurrencyUnit)> = match amount.currency() {
+        Currency::Bitcoin => vec![
+            (minor_units / NUMBER_SATOSHI_IN_BTC, CurrencyUnit::Bitcoin),
+            (
+                minor_units % NUMBER_SATOSHI_IN_BTC / NUMBER_SATOSHI_IN_mBTC,
+                CurrencyUnit::MilliBitcoin,
+            ),
+            (minor_units % NUMBER_SATOSHI_IN_mBTC, CurrencyUnit::Satoshi),
+        ],
+        Currency::MilliBitcoin => vec![
+            (
+                minor_units / NUMBER_SATOSHI_IN_mBTC,
+                CurrencyUnit::MilliBitcoin,
+            ),
+            (minor_units % NUMBER_SATOSHI_IN_mBTC, CurrencyUnit::Satoshi),
+        ],
+        Currency::Satoshi => vec![(minor_units, CurrencyUnit::Satoshi)],
+        Currency::Fiat(code) => vec![
+            (minor_units / 100, CurrencyUnit::Fiat(code)),
+            (minor_units % 100, CurrencyUnit::FiatHundredth(code)),
+        ],
+    };
+
+    let mut written: Vec<String> = parts
+        .iter()
+        .filter(|(number, _)| *number > 0)
+        .map(|(number, unit)| {
+            format!(
+                "{} {}",
+                words.number_before_noun(*number),
+                words.noun(unit, *number)
+            )
+        })
+        .collect();
+    Ok(match written.len() {
+        0 => format!("{} {}", words.number(0), words.noun(&parts[0].1, 0)),
+        1 => written.remove(0),
+        _ => {
+            let last = written.pop().unwrap();
+            format!("{} {} {}", written.join(", "), words.and(), last)
+        }
+    })
+}
+
+/// Splits `num` in groups of three digits, the lowest group first.
+fn groups_of_thousand(mut num: u64) -> Vec<u64> {
+    let mut groups = Vec::new();
+    while num > 0 {
+        groups.push(num % 1000);
+        num /= 1000;
+    }
+    groups
+}
@@ -0,0 +1,167 @@
+use super::{CurrencyUnit, NumberWords};
+
+const ONES: [&str; 30] = [
+    "cero",
+    "uno",
+    "dos",
+    "tres",
+    "cuatro",
+    "cinco",
+    "seis",
+    "siete",
+    "ocho",
+    "nueve",
+    "diez",
+    "once",
+    "doce",
+    "trece",
+    "catorce",
+    "quince",
+    "dieciséis",
+    "diecisiete",
+    "dieciocho",
+    "diecinueve",
+    "veinte",
+    "veintiuno",
+    "veintidós",
+    "veintitrés",
+    "veinticuatro",
+    "veinticinco",
+    "veintiséis",
+    "veintisiete",
+    "veintiocho",
+    "veintinueve",
+];
+const TENS: [&str; 10] = [
+    "cero",
+    "diez",
+    "veinte",
+    "treinta",
+    "cuarenta",
+    "cincuenta",
+    "sesenta",
+    "setenta",
+    "ochenta",
+    "noventa",
+];
+const HUNDREDS: [&str; 10] = [
+    "cero",
+    "ciento",
+    "doscientos",
+    "trescientos",
+    "cuatrocientos",
+    "quinientos",
+    "seiscientos",
+    "setecientos",
+    "ochocientos",
+    "novecientos",
+];
+/// Long scale units, each a million times the one before, singular and plural.
+const ORDERS: [(&str, &str); 3] = [
+    ("millón", "millones"),
+    ("billón", "billones"),
+    ("trillón", "trillones"),
+];
+
+/// "uno" is shortened to "un" in front of a noun or "mil".
+fn shorten(words: String) -> String {
+    if let Some(stem) = words.strip_suffix("veintiuno") {
+        format!("{}veintiún", stem)
+    } else if let Some(stem) = words.strip_suffix("uno") {
+        format!("{}un", stem)
+    } else {
+        words
+    }
+}
+
+fn below_thousand(num: u64) -> String {
+    match num {
+        0..=29 => ONES[num as usize].to_string(),
+        30..=99 => match num % 10 {
+            0 => TENS[(num / 10) as usize].to_string(),
+            lower => format!("{} y {}", TENS[(num / 10) as usize], ONES[lower as usize]),
+        },
+        100 => "cien".to_string(),
+        _ => match num % 100 {
+            0 => HUNDREDS[(num / 100) as usize].to_string(),
+            lower => format!(
+                "{} {}",
+                HUNDREDS[(num / 100) as usize],
+                below_thousand(lower)
+            ),
+        },
+    }
+}
+
+fn below_million(num: u64) -> String {
+    match (num / 1000, num % 1000) {
+        (0, lower) => below_thousand(lower),
+        (1, 0) => "mil".to_string(),
+        (1, lower) => format!("mil {}", below_thousand(lower)),
+        (upper, 0) => format!("{} mil", shorten(below_thousand(upper))),
+        (upper, lower) => format!(
+            "{} mil {}",
+            shorten(below_thousand(upper)),
+            below_thousand(lower)
+        ),
+    }
+}
+
+pub struct Spanish;
+
+impl NumberWords for Spanish {
+    fn number(&self, num: u64) -> String {
+        if num == 0 {
+            return ONES[0].to_string();
+        }
+        let mut words: Vec<String> = ORDERS
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(index, (singular, plural))| {
+                match num / 1_000_000u64.pow(index as u32 + 1) % 1_000_000 {
+                    0 => None,
+                    1 => Some(format!("un {}", singular)),
+                    group => Some(format!("{} {}", shorten(below_million(group)), plural)),
+                }
+            })
+            .collect();
+        let lower = num % 1_000_000;
+        if lower > 0 {
+            words.push(below_million(lower));
+        }
+        words.join(" ")
+    }
+
+    fn number_before_noun(&self, num: u64) -> String {
+        shorten(self.number(num))
+    }
+
+    /// Nouns after a million or more take "de", like "un millón de euros".
+    fn noun(&self, unit: &CurrencyUnit, count: u64) -> String {
+        let (singular, plural) = match unit {
+            CurrencyUnit::Bitcoin => ("bitcoin", "bitcoins"),
+            CurrencyUnit::MilliBitcoin => ("milibitcoin", "milibitcoins"),
+            CurrencyUnit::Satoshi => ("satoshi", "satoshis"),
+            CurrencyUnit::Fiat("EUR") => ("euro", "euros"),
+            CurrencyUnit::Fiat("USD") => ("dólar", "dólares"),
+            CurrencyUnit::Fiat("GBP") => ("libra", "libras"),
+            CurrencyUnit::Fiat("CHF") => ("franco", "francos"),
+            CurrencyUnit::Fiat(code) => (*code, *code),
+            CurrencyUnit::FiatHundredth("GBP") => ("penique", "peniques"),
+            CurrencyUnit::FiatHundredth("USD") => ("centavo", "centavos"),
+            CurrencyUnit::FiatHundredth("EUR" | "CHF") => ("céntimo", "céntimos"),
+            CurrencyUnit::FiatHundredth(_) => ("centésimo", "centésimos"),
+        };
+        let noun = if count == 1 { singular } else { plural };
+        if count == 0 || !count.is_multiple_of(1_000_000) {
+            noun.to_string()
+        } else {
+            format!("de {}", noun)
+        }
+    }
+
+    fn and(&self) -> &'static str {
+        "y"
+    }
+}
@@ -0,0 +1,143 @@
+use std::env;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::api::{self, Transaction};
+use crate::blockchain::Chain;
+use crate::constants::{
+    PAYMENT_CONFIRMATIONS, PAYMENT_CONFIRMATIONS_ENV_VAR, PAYMENT_WATCH_INTERVAL_SECONDS,
+};
+use crate::dht::network::Client;
+use crate::lightning;
+use crate::recourse::has_open_recourse;
+use crate::web::{get_address_to_pay, get_bill_payment_balance};
+use crate::{
+    get_bills, is_current_holder, read_peer_id_from_file, record_payment, record_recourse_payment,
+    web_api,
+};
+
+/// Watches the payment address and the Lightning invoices of every unpaid bill we hold
+/// and records a `Paid` block once the payments are deep enough or an invoice settled.
+/// Payments of our open recourse get a `RecoursePaid` block the same way.
+pub fn start_payment_watcher(client: Client) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(PAYMENT_WATCH_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            // Failing lookups skip the bill, a pass of its own keeps anything unexpected
+            // from stopping the watcher.
+            let pass = tokio::spawn(check_payments(client.clone()));
+            if let Err(error) = pass.await {
+                warn!("can not check bill payments: {}", error);
+            }
+        }
+    });
+}
+
+pub fn required_confirmations() -> u64 {
+    env::var(PAYMENT_CONFIRMATIONS_ENV_VAR)
+        .ok()
+        .and_then(|confirmations| confirmations.parse().ok())
+        .unwrap_or(PAYMENT_CONFIRMATIONS)
+}
+
+async fn check_payments(mut client: Client) {
+    let peer_id = read_peer_id_from_file().to_string();
+    let depth = required_confirmations();
+    for bill in get_bills() {
+        let chain = Chain::read_chain_from_file(&bill.name);
+        if chain.is_paid() || !is_current_holder(&bill, &chain, &peer_id) {
+            continue;
+        }
+        check_recourse_payment(&mut client, &bill.name, &chain, &peer_id, depth).await;
+        if let Some((invoice, payment)) = lightning::settled_invoice(&bill.name).await {
+            let amount = invoice.amount_msat / 1000;
+            if record_payment(&bill.name, String::new(), amount, Vec::new(), Some(payment)) {
+                web_api::publish_latest_block(&mut client, &bill.name).await;
+                info!("recorded Lightning payment of bill {}", bill.name);
+            }
+            continue;
+        }
+        let balance = match get_bill_payment_balance(&bill, &chain).await {
+            Ok(balance) => balance,
+            Err(error) => {
+                warn!("can not check payment of bill {}: {}", bill.name, error);
+                continue;
+            }
+        };
+        if !balance.on_chain || balance.remaining > 0 {
+            continue;
+        }
+
+        let address = get_address_to_pay(bill.clone());
+        let backend = api::backend();
+        let (Ok(tip_height), Ok(transactions)) = (
+            backend.tip_height().await,
+            backend.address_transactions(&address).await,
+        ) else {
+            continue;
+        };
+        let (txids, confirmed) = confirmed_payments(&transactions, &address, tip_height, depth);
+        if confirmed < balance.amount {
+            continue;
+        }
+
+        if record_payment(&bill.name, address, balance.amount, txids, None) {
+            web_api::publish_latest_block(&mut client, &bill.name).await;
+            info!("recorded payment of bill {}", bill.name);
+        }
+    }
+}
+
+/// Records the payment of our open recourse once it reached `depth`.
+async fn check_recourse_payment(
+    client: &mut Client,
+    bill_name: &String,
+    chain: &Chain,
+    peer_id: &str,
+    depth: u64,
+) {
+    let Some(recourse) = chain.get_recourses().pop() else {
+        return;
+    };
+    if recourse.recourser.peer_id != peer_id || !has_open_recourse(chain) {
+        return;
+    }
+
+    let backend = api::backend();
+    let (Ok(tip_height), Ok(transactions)) = (
+        backend.tip_height().await,
+        backend
+            .address_transactions(&recourse.payment_address)
+            .await,
+    ) else {
+        return;
+    };
+    let (txids, confirmed) =
+        confirmed_payments(&transactions, &recourse.payment_address, tip_height, depth);
+    if confirmed >= recourse.total && record_recourse_payment(bill_name, txids) {
+        web_api::publish_latest_block(client, bill_name).await;
+        info!("recorded payment of recourse on bill {}", bill_name);
+    }
+}
+
+/// Payments to `address` with at least `depth` confirmations, oldest first, and their sum.
+pub fn confirmed_payments(
+    transactions: &[Transaction],
+    address: &str,
+    tip_height: u64,
+    depth: u64,
+) -> (Vec<String>, u64) {
+    let mut txids = Vec::new();
+    let mut amount = 0;
+    for transaction in transactions.iter().rev() {
+        let received = transaction.received_by(address);
+        if received > 0 && transaction.status.confirmations(tip_height) >= depth {
+            txids.push(transaction.txid.clone());
+            amount += received;
+        }
+    }
+    (txids, amount)
+}
@@ -0,0 +1,154 @@
+use std::env;
+use std::sync::OnceLock;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use bitcoin::Address;
+use borsh::{BorshDeserialize, BorshSerialize};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{PAYMENT_ADDRESS_TYPE_ENV_VAR, USEDNET};
+
+/// Kind of address a bill and its recourses are paid to, chosen when the bill is issued.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default,
+)]
+pub enum PaymentAddressType {
+    /// Legacy address of the sum of the keys, used by bills issued before the type existed.
+    #[default]
+    P2pkh,
+    /// Native segwit address of the sum of the keys.
+    P2wpkh,
+    /// Taproot key path address of the keys aggregated with the coefficients of BIP 327.
+    /// The holder knows every key and signs alone, there is no MuSig2 signing session.
+    P2tr,
+}
+
+impl PaymentAddressType {
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "p2pkh" => Some(PaymentAddressType::P2pkh),
+            "p2wpkh" => Some(PaymentAddressType::P2wpkh),
+            "p2tr" => Some(PaymentAddressType::P2tr),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            PaymentAddressType::P2pkh => "p2pkh",
+            PaymentAddressType::P2wpkh => "p2wpkh",
+            PaymentAddressType::P2tr => "p2tr",
+        }
+    }
+
+    /// Type of new bills from [`PAYMENT_ADDRESS_TYPE_ENV_VAR`], P2WPKH when it is not set
+    /// or not a known type. Read once, the first call warns about an unknown type.
+    pub fn configured() -> Self {
+        static CONFIGURED: OnceLock<PaymentAddressType> = OnceLock::new();
+        *CONFIGURED.get_or_init(|| match env::var(PAYMENT_ADDRESS_TYPE_ENV_VAR) {
+            Ok(code) => Self::from_code(&code.to_lowercase()).unwrap_or_else(|| {
+                warn!(
+                    "unknown payment address type {}, must be p2pkh, p2wpkh or p2tr, using p2wpkh",
+                    code
+                );
+                PaymentAddressType::P2wpkh
+            }),
+            Err(_) => PaymentAddressType::P2wpkh,
+        })
+    }
+
+    /// Virtual size of an input spending this type with a single signature.
+    pub fn input_vsize(&self) -> u64 {
+        match self {
+            PaymentAddressType::P2pkh => 148,
+            PaymentAddressType::P2wpkh => 68,
+            PaymentAddressType::P2tr => 58,
+        }
+    }
+
+    /// Combines the bill key and the key of the payee.
+    pub fn aggregate_public_key(&self, keys: &[PublicKey]) -> PublicKey {
+        match self {
+            PaymentAddressType::P2tr => {
+                let secp = Secp256k1::new();
+                let tweaked: Vec<PublicKey> = key_agg_coefficients(keys)
+                    .iter()
+                    .zip(keys)
+                    .map(|(coefficient, key)| key.mul_tweak(&secp, coefficient).unwrap())
+                    .collect();
+                PublicKey::combine_keys(&tweaked.iter().collect::<Vec<_>>()).unwrap()
+            }
+            _ => PublicKey::combine_keys(&keys.iter().collect::<Vec<_>>()).unwrap(),
+        }
+    }
+
+    /// Private key of [`Self::aggregate_public_key`], `keys` in the same order. This is
+    /// key aggregation for a single signer who holds all of `keys`, the weighted sum of
+    /// them. Parties with keys of their own would need MuSig2 nonces and partial signatures.
+    pub fn aggregate_private_key(&self, keys: &[SecretKey]) -> SecretKey {
+        let secp = Secp256k1::new();
+        let coefficients = match self {
+            PaymentAddressType::P2tr => {
+                let public_keys: Vec<PublicKey> =
+                    keys.iter().map(|key| key.public_key(&secp)).collect();
+                key_agg_coefficients(&public_keys)
+            }
+            _ => vec![Scalar::ONE; keys.len()],
+        };
+        let mut keys = keys
+            .iter()
+            .zip(coefficients)
+            .map(|(key, coefficient)| key.mul_tweak(&coefficient).unwrap());
+        let first = keys.next().expect("No keys to aggregate");
+        keys.fold(first, |sum, key| sum.add_tweak(&Scalar::from(key)).unwrap())
+    }
+
+    pub fn address(&self, public_key: &PublicKey) -> Address {
+        let public_key = bitcoin::PublicKey::new(*public_key);
+        match self {
+            PaymentAddressType::P2pkh => Address::p2pkh(&public_key, USEDNET),
+            PaymentAddressType::P2wpkh => Address::p2wpkh(&public_key, USEDNET).unwrap(),
+            PaymentAddressType::P2tr => Address::p2tr(
+                &Secp256k1::new(),
+                XOnlyPublicKey::from(public_key.inner),
+                None,
+                USEDNET,
+            ),
+        }
+    }
+}
+
+fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(data);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Key aggregation coefficients of BIP 327 over the sorted keys, in the order of `keys`.
+/// The second distinct key gets 1.
+pub fn key_agg_coefficients(keys: &[PublicKey]) -> Vec<Scalar> {
+    let mut sorted: Vec<[u8; 33]> = keys.iter().map(|key| key.serialize()).collect();
+    sorted.sort();
+    let list_hash = tagged_hash("KeyAgg list", &sorted.concat());
+    let second_key = sorted.iter().find(|key| **key != sorted[0]);
+
+    keys.iter()
+        .map(|key| {
+            let key = key.serialize();
+            if Some(&key) == second_key {
+                Scalar::ONE
+            } else {
+                let coefficient =
+                    tagged_hash("KeyAgg coefficient", &[&list_hash[..], &key].concat());
+                // Values above the curve order are as likely as finding a hash collision.
+                Scalar::from_be_bytes(coefficient).expect("Coefficient is out of range")
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,279 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::key::{KeyPair, TapTweak};
+use bitcoin::psbt::PartiallySignedTransaction;
+use bitcoin::script::{Builder, PushBytesBuf};
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::{
+    taproot, Address, OutPoint, PrivateKey, ScriptBuf, Sequence, TxIn, TxOut, Txid, Witness,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::anchor::DUST_LIMIT;
+use crate::api::{self, BackendError, Utxo};
+use crate::blockchain::Chain;
+use crate::constants::{PAYOUT_CONFIRMATION_TARGET, USEDNET};
+use crate::payment_address::PaymentAddressType;
+use crate::recourse::get_recourse_private_key;
+use crate::{
+    bill_holder, bitcoin_private_key_for, get_whole_identity, is_current_holder,
+    read_bill_from_file, read_retired_bitcoin_keys, BitcreditBill,
+};
+
+/// Transaction that moved the coins of a payment address to the holder.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Payout {
+    pub txid: String,
+    /// The signed PSBT, hex encoded.
+    pub psbt: String,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+#[derive(Debug)]
+pub enum PayoutError {
+    NotEntitled,
+    InvalidDestination(String),
+    NothingToSweep(String),
+    InsufficientFunds { total: u64, fee: u64 },
+    Request(BackendError),
+}
+
+impl fmt::Display for PayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayoutError::NotEntitled => write!(f, "only the payee can sweep this address"),
+            PayoutError::InvalidDestination(error) => {
+                write!(f, "invalid destination address: {}", error)
+            }
+            PayoutError::NothingToSweep(address) => {
+                write!(f, "no confirmed coins on {}", address)
+            }
+            PayoutError::InsufficientFunds { total, fee } => {
+                write!(f, "{} sats do not cover the fee of {} sats", total, fee)
+            }
+            PayoutError::Request(error) => write!(f, "chain backend failed: {}", error),
+        }
+    }
+}
+
+impl Error for PayoutError {}
+
+impl From<BackendError> for PayoutError {
+    fn from(error: BackendError) -> Self {
+        PayoutError::Request(error)
+    }
+}
+
+/// Key of the bill payment address, the bill key combined with `holder_private_key`, the
+/// key the bill names the holder with.
+pub fn payee_private_key(bill: &BitcreditBill, holder_private_key: &str) -> PrivateKey {
+    let private_key_bill = PrivateKey::from_str(&bill.private_key).unwrap();
+    let private_key_holder = PrivateKey::from_str(holder_private_key).unwrap();
+
+    let private_key = bill
+        .payment_address_type
+        .aggregate_private_key(&[private_key_bill.inner, private_key_holder.inner]);
+    PrivateKey::new(private_key, USEDNET)
+}
+
+/// Sends everything paid for the bill to `destination`, only the holder can.
+pub async fn sweep_bill(bill_name: &String, destination: &str) -> Result<Payout, PayoutError> {
+    let identity = get_whole_identity();
+    let bill = read_bill_from_file(bill_name);
+    let chain = Chain::read_chain_from_file(bill_name);
+    if !is_current_holder(&bill, &chain, &identity.peer_id.to_string()) {
+        return Err(PayoutError::NotEntitled);
+    }
+    let holder_private_key = bitcoin_private_key_for(
+        &identity.identity,
+        &read_retired_bitcoin_keys(),
+        &bill_holder(&bill).bitcoin_public_key,
+    )
+    .ok_or(PayoutError::NotEntitled)?;
+    let private_key = payee_private_key(&bill, &holder_private_key);
+    sweep(bill.payment_address_type, &private_key, destination).await
+}
+
+/// Sends everything paid for our recourse against `recoursee` to `destination`.
+pub async fn sweep_recourse(
+    bill_name: &String,
+    recoursee: &str,
+    destination: &str,
+) -> Result<Payout, PayoutError> {
+    let identity = get_whole_identity();
+    let peer_id = identity.peer_id.to_string();
+    let bill = read_bill_from_file(bill_name);
+    let recourse = Chain::read_chain_from_file(bill_name)
+        .get_recourses()
+        .into_iter()
+        .find(|recourse| {
+            recourse.recourser.peer_id.eq(&peer_id) && recourse.recoursee.peer_id.eq(recoursee)
+        })
+        .ok_or(PayoutError::NotEntitled)?;
+    let recourser_private_key = bitcoin_private_key_for(
+        &identity.identity,
+        &read_retired_bitcoin_keys(),
+        &recourse.recourser.bitcoin_public_key,
+    )
+    .ok_or(PayoutError::NotEntitled)?;
+    let private_key = get_recourse_private_key(&recourser_private_key, &bill, &recourse.recoursee);
+    sweep(bill.payment_address_type, &private_key, destination).await
+}
+
+/// Builds, signs and broadcasts a PSBT that spends the confirmed coins of the
+/// `address_type` address of `private_key` to `destination`.
+pub async fn sweep(
+    address_type: PaymentAddressType,
+    private_key: &PrivateKey,
+    destination: &str,
+) -> Result<Payout, PayoutError> {
+    let destination = Address::from_str(destination)
+        .and_then(|address| address.require_network(USEDNET))
+        .map_err(|error| PayoutError::InvalidDestination(error.to_string()))?;
+    let secp = Secp256k1::new();
+    let address = address_type.address(&private_key.public_key(&secp).inner);
+
+    let backend = api::backend();
+    let mut inputs = Vec::new();
+    for utxo in backend.address_utxos(&address.to_string()).await? {
+        if utxo.status.confirmed {
+            let previous = backend.raw_transaction(&utxo.txid).await?;
+            inputs.push((utxo, previous));
+        }
+    }
+    if inputs.is_empty() {
+        return Err(PayoutError::NothingToSweep(address.to_string()));
+    }
+    let fee_rate = backend.fee_rate(PAYOUT_CONFIRMATION_TARGET).await?;
+    let total: u64 = inputs.iter().map(|(utxo, _)| utxo.value).sum();
+
+    let mut psbt = sweep_psbt(inputs, address_type, &destination, fee_rate)?;
+    sign_psbt(&mut psbt, address_type, private_key);
+    let amount = psbt.unsigned_tx.output[0].value;
+    let psbt_hex = psbt.serialize_hex();
+
+    let txid = backend.broadcast(serialize_hex(&psbt.extract_tx())).await?;
+    Ok(Payout {
+        txid,
+        psbt: psbt_hex,
+        amount,
+        fee: total - amount,
+    })
+}
+
+/// Unsigned PSBT spending the `address_type` `inputs` with their previous transactions
+/// to `destination`, less the fee at `fee_rate` sat/vB.
+pub fn sweep_psbt(
+    inputs: Vec<(Utxo, bitcoin::Transaction)>,
+    address_type: PaymentAddressType,
+    destination: &Address,
+    fee_rate: u64,
+) -> Result<PartiallySignedTransaction, PayoutError> {
+    let total: u64 = inputs.iter().map(|(utxo, _)| utxo.value).sum();
+    // Virtual sizes of the transaction overhead, the inputs and the output.
+    let output_size = 9 + destination.script_pubkey().len() as u64;
+    let fee = fee_rate * (10 + address_type.input_vsize() * inputs.len() as u64 + output_size);
+    if total < fee + DUST_LIMIT {
+        return Err(PayoutError::InsufficientFunds { total, fee });
+    }
+
+    let transaction = bitcoin::Transaction {
+        version: 2,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|(utxo, _)| TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::from_str(&utxo.txid).unwrap(),
+                    vout: utxo.vout,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: total - fee,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(transaction).unwrap();
+    for (input, (utxo, previous)) in psbt.inputs.iter_mut().zip(inputs) {
+        if address_type != PaymentAddressType::P2pkh {
+            input.witness_utxo = Some(previous.output[utxo.vout as usize].clone());
+        }
+        // Taproot signatures commit to the amounts, the others need the whole transaction
+        // for the signer to check them.
+        if address_type != PaymentAddressType::P2tr {
+            input.non_witness_utxo = Some(previous);
+        }
+    }
+    Ok(psbt)
+}
+
+/// Signs every input with `private_key` and finalizes it for `address_type`.
+pub fn sign_psbt(
+    psbt: &mut PartiallySignedTransaction,
+    address_type: PaymentAddressType,
+    private_key: &PrivateKey,
+) {
+    let secp = Secp256k1::new();
+    let public_key = private_key.public_key(&secp);
+    let transaction = psbt.unsigned_tx.clone();
+    let prevouts: Vec<TxOut> = psbt
+        .inputs
+        .iter()
+        .filter_map(|input| input.witness_utxo.clone())
+        .collect();
+    let mut sighash_cache = SighashCache::new(&transaction);
+    for index in 0..psbt.inputs.len() {
+        if address_type == PaymentAddressType::P2tr {
+            let sighash = sighash_cache
+                .taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let key_pair = KeyPair::from_secret_key(&secp, &private_key.inner)
+                .tap_tweak(&secp, None)
+                .to_inner();
+            let message = Message::from_slice(&sighash[..]).unwrap();
+            let signature = taproot::Signature {
+                sig: secp.sign_schnorr(&message, &key_pair),
+                hash_ty: TapSighashType::Default,
+            };
+            let input = &mut psbt.inputs[index];
+            input.tap_key_sig = Some(signature);
+            input.final_script_witness = Some(Witness::from_slice(&[signature.to_vec()]));
+            continue;
+        }
+
+        let (message, sighash_type) = psbt.sighash_ecdsa(index, &mut sighash_cache).unwrap();
+        let signature = bitcoin::ecdsa::Signature {
+            sig: secp.sign_ecdsa(&message, &private_key.inner),
+            hash_ty: sighash_type,
+        };
+        let input = &mut psbt.inputs[index];
+        input.partial_sigs.insert(public_key, signature);
+        if address_type == PaymentAddressType::P2wpkh {
+            input.final_script_witness = Some(Witness::from_slice(&[
+                signature.to_vec(),
+                public_key.to_bytes(),
+            ]));
+        } else {
+            input.final_script_sig = Some(
+                Builder::new()
+                    .push_slice(PushBytesBuf::try_from(signature.to_vec()).unwrap())
+                    .push_key(&public_key)
+                    .into_script(),
+            );
+        }
+    }
+}
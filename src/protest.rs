@@ -0,0 +1,107 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::{Days, NaiveDate};
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::{display_amount, signature, verify_rsa_signature};
+use crate::constants::PROTEST_NON_PAYMENT_PERIOD_DAYS;
+use crate::{BitcreditBill, IdentityPublicData};
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ProtestReason {
+    NonAcceptance,
+    NonPayment,
+}
+
+/// Statement of the holder that the drawee refused to accept or to pay the bill.
+/// It is signed with the holder's identity key and stored in the protest block.
+/// `amount_numbers` is the protested amount, the part that was not accepted or not paid.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+pub struct ProtestDocument {
+    pub bill_name: String,
+    pub reason: ProtestReason,
+    pub protester: IdentityPublicData,
+    pub drawee: IdentityPublicData,
+    pub amount_numbers: u64,
+    pub currency_code: String,
+    pub maturity_date: String,
+    pub date_of_protest: String,
+    pub signature: String,
+}
+
+impl ProtestDocument {
+    pub fn new(
+        bill: &BitcreditBill,
+        reason: ProtestReason,
+        protester: IdentityPublicData,
+        amount_numbers: u64,
+        date_of_protest: NaiveDate,
+        protester_private_key_pem: String,
+    ) -> Self {
+        let mut document = Self {
+            bill_name: bill.name.clone(),
+            reason,
+            protester,
+            drawee: bill.drawee.clone(),
+            amount_numbers,
+            currency_code: bill.currency_code.clone(),
+            maturity_date: bill.maturity_date.clone(),
+            date_of_protest: date_of_protest.to_string(),
+            signature: String::new(),
+        };
+        document.signature = signature(document.digest(), protester_private_key_pem);
+        document
+    }
+
+    /// Hex encoded sha256 of the document without its signature.
+    fn digest(&self) -> String {
+        let mut unsigned = self.clone();
+        unsigned.signature = String::new();
+        hex::encode(sha256(&unsigned.try_to_vec().unwrap()))
+    }
+
+    pub fn verify(&self) -> bool {
+        verify_rsa_signature(
+            &self.digest(),
+            &self.signature,
+            &self.protester.rsa_public_key_pem,
+        )
+    }
+
+    pub fn text(&self) -> String {
+        let refused = match self.reason {
+            ProtestReason::NonAcceptance => "refused to accept",
+            ProtestReason::NonPayment => "failed to pay",
+        };
+        format!(
+            "On {} {} of {} protests that the drawee {} of {} {} the bill {} over {} due on {}.",
+            self.date_of_protest,
+            self.protester.name,
+            self.protester.postal_address,
+            self.drawee.name,
+            self.drawee.postal_address,
+            refused,
+            self.bill_name,
+            display_amount(self.amount_numbers, &self.currency_code),
+            self.maturity_date
+        )
+    }
+}
+
+pub fn parse_maturity_date(maturity_date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(maturity_date, "%Y-%m-%d").ok()
+}
+
+/// Non-acceptance has to be protested within the time limit for presentment for
+/// acceptance, see `Maturity::acceptance_deadline`.
+pub fn is_within_non_acceptance_deadline(deadline: NaiveDate, today: NaiveDate) -> bool {
+    today <= deadline
+}
+
+/// Non-payment has to be protested on one of the days following the maturity date.
+pub fn is_within_non_payment_deadline(maturity_date: NaiveDate, today: NaiveDate) -> bool {
+    let last_day = maturity_date
+        .checked_add_days(Days::new(PROTEST_NON_PAYMENT_PERIOD_DAYS))
+        .unwrap();
+    today > maturity_date && today <= last_day
+}
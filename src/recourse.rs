@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+use bitcoin::secp256k1::{Scalar, Secp256k1};
+use borsh::{BorshDeserialize, BorshSerialize};
+use chrono::NaiveDate;
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+
+use crate::amount::AmountError;
+use crate::blockchain::Chain;
+use crate::constants::{DAYS_IN_YEAR, RECOURSE_INTEREST_RATE_PERCENT, USEDNET};
+use crate::protest::parse_maturity_date;
+use crate::{BitcreditBill, IdentityPublicData};
+
+/// Claim of the holder against a prior endorser or the drawer after the bill was protested.
+/// The recoursee pays `total` to `payment_address`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+pub struct RecourseClaim {
+    pub bill_name: String,
+    pub recourser: IdentityPublicData,
+    pub recoursee: IdentityPublicData,
+    pub principal: u64,
+    pub interest: u64,
+    pub costs: u64,
+    pub total: u64,
+    pub currency_code: String,
+    pub payment_address: String,
+    pub date_of_recourse: String,
+}
+
+impl RecourseClaim {
+    pub fn new(
+        bill: &BitcreditBill,
+        recourser: IdentityPublicData,
+        recoursee: IdentityPublicData,
+        principal: u64,
+        costs: u64,
+        date_of_recourse: NaiveDate,
+    ) -> Result<Self, AmountError> {
+        let (interest, total) = claim_amounts(
+            principal,
+            costs,
+            parse_maturity_date(&bill.maturity_date),
+            date_of_recourse,
+        )?;
+        let payment_address = get_recourse_address(bill, &recourser, &recoursee);
+
+        Ok(Self {
+            bill_name: bill.name.clone(),
+            recourser,
+            recoursee,
+            principal,
+            interest,
+            costs,
+            total,
+            currency_code: bill.currency_code.clone(),
+            payment_address,
+            date_of_recourse: date_of_recourse.to_string(),
+        })
+    }
+
+    /// Whether interest and total of the claim follow from its principal and costs for
+    /// a bill due on `maturity_date`.
+    pub fn has_consistent_amounts(&self, maturity_date: Option<NaiveDate>) -> bool {
+        let Some(date_of_recourse) = parse_maturity_date(&self.date_of_recourse) else {
+            return false;
+        };
+        claim_amounts(self.principal, self.costs, maturity_date, date_of_recourse)
+            .is_ok_and(|amounts| amounts == (self.interest, self.total))
+    }
+}
+
+/// Interest and total of a claim over `principal` and `costs` made on `date_of_recourse`.
+fn claim_amounts(
+    principal: u64,
+    costs: u64,
+    maturity_date: Option<NaiveDate>,
+    date_of_recourse: NaiveDate,
+) -> Result<(u64, u64), AmountError> {
+    let interest = match maturity_date {
+        Some(maturity_date) => recourse_interest(principal, maturity_date, date_of_recourse)?,
+        None => 0,
+    };
+    let total = principal
+        .checked_add(interest)
+        .and_then(|total| total.checked_add(costs))
+        .ok_or(AmountError::Overflow)?;
+    Ok((interest, total))
+}
+
+/// Simple interest at [`RECOURSE_INTEREST_RATE_PERCENT`] per year from the maturity date.
+/// Nothing accrues before maturity.
+pub fn recourse_interest(
+    principal: u64,
+    maturity_date: NaiveDate,
+    today: NaiveDate,
+) -> Result<u64, AmountError> {
+    let days = (today - maturity_date).num_days().max(0) as u64;
+    principal
+        .checked_mul(RECOURSE_INTEREST_RATE_PERCENT)
+        .and_then(|interest| interest.checked_mul(days))
+        .map(|interest| interest / (100 * DAYS_IN_YEAR))
+        .ok_or(AmountError::Overflow)
+}
+
+/// Liable parties `peer_id` can still take recourse against: nobody while its last
+/// recourse is open, otherwise everybody nobody took recourse against yet.
+pub fn remaining_recoursees(
+    liable_parties: Vec<IdentityPublicData>,
+    recourses: &[RecourseClaim],
+    peer_id: &str,
+    open_recourse: bool,
+) -> Vec<IdentityPublicData> {
+    if open_recourse {
+        return Vec::new();
+    }
+    liable_parties
+        .into_iter()
+        .filter(|party| {
+            party.peer_id != peer_id
+                && !recourses
+                    .iter()
+                    .any(|recourse| recourse.recoursee.peer_id == party.peer_id)
+        })
+        .collect()
+}
+
+/// Whether the last recourse on the bill has no `RecoursePaid` block yet. An unreadable
+/// chain counts as having one open.
+pub fn has_open_recourse(chain: &Chain) -> bool {
+    chain.get_state().map_or(true, |state| state.open_recourse)
+}
+
+/// Every recoursee gets its own address, so payments of different parties do not mix
+/// with each other or with the payment of the drawee.
+fn recoursee_tweak(recoursee: &IdentityPublicData) -> Scalar {
+    Scalar::from_be_bytes(sha256(recoursee.bitcoin_public_key.as_bytes()))
+        .expect("Tweak is out of range")
+}
+
+/// Same as `get_address_to_pay`, with the bill key and the key of the recourser combined
+/// and tweaked for the recoursee.
+pub fn get_recourse_address(
+    bill: &BitcreditBill,
+    recourser: &IdentityPublicData,
+    recoursee: &IdentityPublicData,
+) -> String {
+    let secp = Secp256k1::new();
+    let public_key_bill = bitcoin::PublicKey::from_str(&bill.public_key).unwrap();
+    let public_key_recourser = bitcoin::PublicKey::from_str(&recourser.bitcoin_public_key).unwrap();
+
+    let address_type = bill.payment_address_type;
+    let public_key = address_type
+        .aggregate_public_key(&[public_key_bill.inner, public_key_recourser.inner])
+        .add_exp_tweak(&secp, &recoursee_tweak(recoursee))
+        .unwrap();
+
+    address_type.address(&public_key).to_string()
+}
+
+/// Key of [`get_recourse_address`], `recourser_private_key` is the key the claim names the
+/// recourser with.
+pub fn get_recourse_private_key(
+    recourser_private_key: &str,
+    bill: &BitcreditBill,
+    recoursee: &IdentityPublicData,
+) -> bitcoin::PrivateKey {
+    let private_key_bill = bitcoin::PrivateKey::from_str(&bill.private_key).unwrap();
+    let private_key_recourser = bitcoin::PrivateKey::from_str(recourser_private_key).unwrap();
+
+    let private_key = bill
+        .payment_address_type
+        .aggregate_private_key(&[private_key_bill.inner, private_key_recourser.inner])
+        .add_tweak(&recoursee_tweak(recoursee))
+        .unwrap();
+
+    bitcoin::PrivateKey::new(private_key, USEDNET)
+}
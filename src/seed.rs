@@ -0,0 +1,128 @@
+use std::str::FromStr;
+
+use bitcoin::bip32::{DerivationPath, ExtendedPrivKey};
+use bitcoin::secp256k1::Secp256k1;
+use libp2p::identity::Keypair;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::pkey::Private;
+use openssl::rsa::Rsa;
+use openssl::sha::sha512;
+
+use crate::constants::{
+    ED25519_DERIVATION_PATH, MAINNET, RSA_DERIVATION_PATH, RSA_KEY_BITS, USEDNET,
+};
+
+const RSA_PUBLIC_EXPONENT: u32 = 65537;
+const PRIME_CHECKS: i32 = 64;
+
+/// BIP84 path of the bitcoin key, coin type 1 on the test networks. `index` counts the
+/// key rotations of the identity.
+pub fn bitcoin_derivation_path(index: u32) -> String {
+    let coin_type = if USEDNET == MAINNET { 0 } else { 1 };
+    format!("m/84'/{}'/0'/0/{}", coin_type, index)
+}
+
+pub fn bitcoin_private_key(seed: &[u8], index: u32) -> bitcoin::PrivateKey {
+    derive(seed, &bitcoin_derivation_path(index)).to_priv()
+}
+
+pub fn ed25519_keypair(seed: &[u8]) -> Keypair {
+    let secret_key = derive(seed, ED25519_DERIVATION_PATH)
+        .private_key
+        .secret_bytes();
+    Keypair::ed25519_from_bytes(secret_key).expect("Unable to create keypair ed25519")
+}
+
+/// RSA key whose primes are searched in a SHA-512 stream of the derived key, the same
+/// seed and index always give the same key.
+pub fn rsa_key(seed: &[u8], index: u32) -> Rsa<Private> {
+    let path = format!("{}/{}'", RSA_DERIVATION_PATH, index);
+    let derived = derive(seed, &path).private_key.secret_bytes();
+    let mut stream = KeyStream::new(&derived);
+    let mut ctx = BigNumContext::new().unwrap();
+    let e = BigNum::from_u32(RSA_PUBLIC_EXPONENT).unwrap();
+
+    let p = prime(&mut stream, &e, &mut ctx);
+    let mut q = prime(&mut stream, &e, &mut ctx);
+    while q == p {
+        q = prime(&mut stream, &e, &mut ctx);
+    }
+
+    let one = BigNum::from_u32(1).unwrap();
+    let mut n = BigNum::new().unwrap();
+    n.checked_mul(&p, &q, &mut ctx).unwrap();
+    let mut p1 = BigNum::new().unwrap();
+    p1.checked_sub(&p, &one).unwrap();
+    let mut q1 = BigNum::new().unwrap();
+    q1.checked_sub(&q, &one).unwrap();
+    let mut phi = BigNum::new().unwrap();
+    phi.checked_mul(&p1, &q1, &mut ctx).unwrap();
+
+    let mut d = BigNum::new().unwrap();
+    d.mod_inverse(&e, &phi, &mut ctx).unwrap();
+    let mut dmp1 = BigNum::new().unwrap();
+    dmp1.nnmod(&d, &p1, &mut ctx).unwrap();
+    let mut dmq1 = BigNum::new().unwrap();
+    dmq1.nnmod(&d, &q1, &mut ctx).unwrap();
+    let mut iqmp = BigNum::new().unwrap();
+    iqmp.mod_inverse(&q, &p, &mut ctx).unwrap();
+
+    Rsa::from_private_components(n, e, d, p, q, dmp1, dmq1, iqmp).unwrap()
+}
+
+fn derive(seed: &[u8], path: &str) -> ExtendedPrivKey {
+    let secp = Secp256k1::new();
+    let path = DerivationPath::from_str(path).expect("Invalid derivation path");
+    ExtendedPrivKey::new_master(USEDNET, seed)
+        .and_then(|master| master.derive_priv(&secp, &path))
+        .expect("Unable to derive key")
+}
+
+/// Prime of half the key size with the two top bits set, so the modulus has all bits.
+fn prime(stream: &mut KeyStream, e: &BigNum, ctx: &mut BigNumContext) -> BigNum {
+    let len = (RSA_KEY_BITS / 16) as usize;
+    loop {
+        let mut bytes = stream.take(len);
+        bytes[0] |= 0xc0;
+        bytes[len - 1] |= 1;
+        let candidate = BigNum::from_slice(&bytes).unwrap();
+
+        let mut remainder = BigNum::new().unwrap();
+        remainder.nnmod(&candidate, e, ctx).unwrap();
+        if remainder == BigNum::from_u32(1).unwrap() {
+            continue;
+        }
+        if candidate
+            .is_prime_fasttest(PRIME_CHECKS, ctx, true)
+            .unwrap()
+        {
+            return candidate;
+        }
+    }
+}
+
+struct KeyStream {
+    key: Vec<u8>,
+    counter: u32,
+}
+
+impl KeyStream {
+    fn new(key: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            counter: 0,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let mut block = self.key.clone();
+            block.extend_from_slice(&self.counter.to_be_bytes());
+            self.counter += 1;
+            bytes.extend_from_slice(&sha512(&block));
+        }
+        bytes.truncate(len);
+        bytes
+    }
+}
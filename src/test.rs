@@ -2,13 +2,15 @@
 mod test {
     use bitcoin::key::XOnlyPublicKey;
     use bitcoin::secp256k1::Scalar;
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, HashMap};
+    use std::fs;
     use std::io::{BufReader, Cursor, Read};
     use std::path::{Path, PathBuf};
+    use std::str::FromStr;
     use std::time::Duration;
-    use std::{fs, mem};
 
     use borsh::{BorshDeserialize, BorshSerialize};
+    use chrono::NaiveDate;
     use libp2p::identity::Keypair;
     use libp2p::kad::store::MemoryStore;
     use libp2p::kad::{Kademlia, KademliaConfig};
@@ -20,18 +22,56 @@ mod test {
     use openssl::sign::{Signer, Verifier};
     use openssl::symm::Cipher;
     use openssl::{aes, rsa, sha};
+    use rocket::http::Status;
     use serde_derive::Deserialize;
 
-    use crate::blockchain::{is_block_valid, signature, Block, Chain};
-    use crate::constants::BILLS_FOLDER_PATH;
-    use crate::numbers_to_words::encode;
+    use crate::amount::{Amount, AmountError, Currency};
+    use crate::anchor::{
+        anchoring_transaction, leaf_hash, merkle_tree, root_from_proof, AnchorError, ANCHOR_TAG,
+    };
+    use crate::api::{
+        chain_backend, default_backend_kind, AddressInfo, BitcoindBackend, ChainBackend,
+        EsploraBackend,
+    };
+    use crate::blockchain::{
+        is_block_valid, is_valid_protest, may_sign, prefers_branch, signature, Block, BlockData,
+        BlockDataError, Chain, ChainState, Fork, OperationCode, BLOCK_VERSION, RSA_BLOCK_VERSION,
+    };
+    use crate::bolt11::{Bolt11Error, Bolt11Invoice};
+    use crate::constants::{
+        BILLS_FOLDER_PATH, BITCOIND_PASSWORD_ENV_VAR, BITCOIND_USER_ENV_VAR, USEDNET,
+    };
+    use crate::encryption;
+    use crate::interest::interest;
+    use crate::key_rotation::{with_rotations_of, KeyRotation};
+    use crate::keystore::{KeystoreError, KeystoreFile, DATA_KEY_LEN};
+    use crate::lightning::{
+        check_bill_invoice, invoice_description, invoice_preimage, InvoiceState, LightningNode,
+        LightningPayment, MockLightningNode,
+    };
+    use crate::maturity::Maturity;
+    use crate::mnemonic::{Mnemonic, MnemonicError};
+    use crate::numbers_to_words::{encode, encode_amount, UnsupportedLanguage};
+    use crate::payment::confirmed_payments;
+    use crate::payment_address::PaymentAddressType;
+    use crate::payout::{payee_private_key, sign_psbt, sweep_psbt, PayoutError};
+    use crate::protest::{
+        is_within_non_acceptance_deadline, is_within_non_payment_deadline, ProtestDocument,
+        ProtestReason,
+    };
+    use crate::recourse::{recourse_interest, remaining_recoursees, RecourseClaim};
+    use crate::seed;
+    use crate::timestamp::{
+        store_with, verify_token_with, LocalTimestampAuthority, TimestampAuthority, TimestampError,
+    };
+    use crate::web::get_address_to_pay;
+    use crate::web_api;
     use crate::{
-        bill_from_byte_array, bill_to_byte_array, byte_array_to_size_array_keypair,
-        byte_array_to_size_array_peer_id, create_new_identity, decrypt_bytes, encrypt_bytes,
+        bill_to_byte_array, bitcoin_private_key_for, decrypt_bytes, encrypt_bytes,
         endorse_bitcredit_bill, generation_rsa_key, get_whole_identity, issue_new_bill,
-        pem_private_key_from_rsa, pem_public_key_from_rsa, private_key_from_pem_u8,
-        public_key_from_pem_u8, read_bill_from_file, read_identity_from_file,
-        read_peer_id_from_file, structure_as_u8_slice, BitcreditBill, Identity, IdentityPublicData,
+        keypair_from_legacy_bytes, pem_private_key_from_rsa, pem_public_key_from_rsa,
+        private_key_from_pem_u8, read_bill_from_file, read_identity_from_file,
+        read_peer_id_from_file, retire_bitcoin_key, BitcreditBill, Identity, IdentityPublicData,
     };
 
     //TODO: Change. Because we create new bill every time we run tests
@@ -307,34 +347,43 @@ mod test {
     fn structure_to_bytes() {
         let ed25519_keys = Keypair::generate_ed25519();
         let peer_id = PeerId::from(ed25519_keys.public());
-        let id = create_new_identity(
-            "qwq".to_string(),
-            "ewqe".to_string(),
-            "qwewqe".to_string(),
-            "qwewqe".to_string(),
-            "qweeq".to_string(),
-            "qwewqe".to_string(),
-        );
-
-        let bytes_ed25519_keys = unsafe { structure_as_u8_slice(&ed25519_keys) };
-        let bytes_peer_id = unsafe { structure_as_u8_slice(&peer_id) };
+        let id = identity_with_rsa_key("qwq", 1);
 
-        let bytes_ed25519_keys_sized = byte_array_to_size_array_keypair(bytes_ed25519_keys);
-        let bytes_peer_id_sized = byte_array_to_size_array_peer_id(bytes_peer_id);
+        let bytes_ed25519_keys = ed25519_keys.to_protobuf_encoding().unwrap();
+        let bytes_peer_id = peer_id.to_bytes();
 
         if !Path::new("test").exists() {
             fs::create_dir("test").expect("Can't create folder.");
         }
-        fs::write("test/keys", *bytes_ed25519_keys_sized).expect("Unable to write keys in file");
-        fs::write("test/peer_id", *bytes_peer_id_sized).expect("Unable to write peer id in file");
+        fs::write("test/keys", bytes_ed25519_keys).expect("Unable to write keys in file");
+        fs::write("test/peer_id", bytes_peer_id).expect("Unable to write peer id in file");
 
         let data_key = fs::read("test/keys").expect("Unable to read file with keypair");
-        let key_pair_bytes_sized = byte_array_to_size_array_keypair(data_key.as_slice());
-        let key_pair2: Keypair = unsafe { mem::transmute_copy(key_pair_bytes_sized) };
+        let key_pair2 = Keypair::from_protobuf_encoding(&data_key).unwrap();
 
         let data_peer_id = fs::read("test/peer_id").expect("Unable to read file with peer_id");
-        let peer_id_bytes_sized = byte_array_to_size_array_peer_id(data_peer_id.as_slice());
-        let peer_id2: PeerId = unsafe { mem::transmute_copy(peer_id_bytes_sized) };
+        let peer_id2 = PeerId::from_bytes(&data_peer_id).unwrap();
+
+        assert_eq!(key_pair2.public(), ed25519_keys.public());
+        assert_eq!(peer_id2, peer_id);
+    }
+
+    #[test]
+    fn legacy_keypair_bytes() {
+        let ed25519_keys = Keypair::generate_ed25519();
+        let bytes = ed25519_keys.clone().try_into_ed25519().unwrap().to_bytes();
+
+        // Old files held the raw memory: the compressed public key, the decompressed point
+        // and the secret key.
+        let mut legacy = bytes[32..].to_vec();
+        legacy.extend_from_slice(&[7; 160]);
+        legacy.extend_from_slice(&bytes[..32]);
+
+        let key_pair = keypair_from_legacy_bytes(&legacy).unwrap();
+        assert_eq!(key_pair.public(), ed25519_keys.public());
+        assert!(keypair_from_legacy_bytes(&legacy[..200]).is_none());
+        legacy[0] ^= 1;
+        assert!(keypair_from_legacy_bytes(&legacy).is_none());
     }
 
     // #[test]
@@ -516,7 +565,1544 @@ mod test {
 
     #[test]
     fn numbers_to_letters() {
-        let result = encode(&123_324_324);
+        let result = encode(&123_324_324, "en").unwrap();
         assert_eq!("one hundred twenty-three million three hundred twenty-four thousand three hundred twenty-four".to_string(), result);
     }
+
+    fn identity_public_data(name: &str) -> IdentityPublicData {
+        IdentityPublicData {
+            peer_id: format!("peer_{}", name),
+            name: name.to_string(),
+            bitcoin_public_key: "".to_string(),
+            postal_address: format!("{} street", name),
+            email: "".to_string(),
+            rsa_public_key_pem: "".to_string(),
+        }
+    }
+
+    /// Identity with the `index`th bitcoin key of `seed` and no RSA key.
+    fn identity_from_seed(seed: &[u8], index: u32) -> Identity {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let private_key = seed::bitcoin_private_key(seed, index);
+        Identity {
+            name: "holder".to_string(),
+            date_of_birth: String::new(),
+            city_of_birth: String::new(),
+            country_of_birth: String::new(),
+            email: String::new(),
+            postal_address: String::new(),
+            public_key_pem: String::new(),
+            private_key_pem: String::new(),
+            bitcoin_public_key: private_key.public_key(&secp).to_string(),
+            bitcoin_private_key: private_key.to_string(),
+        }
+    }
+
+    /// Identity `name` with the bitcoin key of the seed `[byte; 64]` and a new RSA key, apart
+    /// from the identity and seed phrase on disk.
+    fn identity_with_rsa_key(name: &str, byte: u8) -> Identity {
+        let rsa = generation_rsa_key();
+        let mut identity = identity_from_seed(&[byte; 64], 0);
+        identity.name = name.to_string();
+        identity.public_key_pem = pem_public_key_from_rsa(&rsa);
+        identity.private_key_pem = pem_private_key_from_rsa(&rsa);
+        identity
+    }
+
+    /// A new bitcoin key on `network`.
+    fn random_private_key(network: bitcoin::Network) -> bitcoin::PrivateKey {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        bitcoin::PrivateKey::new(
+            secp.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng())
+                .0,
+            network,
+        )
+    }
+
+    /// A bill of 1000 sats paid to `payee` with a new bill key.
+    fn bill_paid_to(payee: IdentityPublicData) -> BitcreditBill {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let bill_key = random_private_key(USEDNET);
+        BitcreditBill {
+            name: "bill".to_string(),
+            to_payee: true,
+            bill_jurisdiction: String::new(),
+            timestamp_at_drawing: 0,
+            drawee: identity_public_data("drawee"),
+            drawer: identity_public_data("drawer"),
+            payee,
+            endorsee: identity_public_data(""),
+            place_of_drawing: String::new(),
+            currency_code: "sat".to_string(),
+            amount_numbers: 1000,
+            amounts_letters: String::new(),
+            maturity_date: "2030-01-01".to_string(),
+            date_of_issue: "2029-01-01".to_string(),
+            compounding_interest_rate: 0,
+            type_of_interest_calculation: false,
+            place_of_payment: String::new(),
+            public_key: bill_key.public_key(&secp).to_string(),
+            private_key: bill_key.to_string(),
+            language: "en".to_string(),
+            maturity: Maturity::FixedDate,
+            payment_address_type: PaymentAddressType::P2wpkh,
+        }
+    }
+
+    #[test]
+    fn block_data_round_trip() {
+        let block_data = BlockData::Endorse {
+            endorsee: identity_public_data("alice"),
+            endorser: identity_public_data("bob"),
+        };
+        let bytes = block_data.to_bytes();
+
+        match BlockData::from_bytes(&bytes, &OperationCode::Endorse).unwrap() {
+            BlockData::Endorse { endorsee, endorser } => {
+                assert_eq!(endorsee.name, "alice");
+                assert_eq!(endorser.name, "bob");
+            }
+            other => panic!("unexpected block data {:?}", other),
+        }
+
+        assert!(matches!(
+            BlockData::from_bytes(&bytes, &OperationCode::Accept),
+            Err(BlockDataError::OperationCodeMismatch { .. })
+        ));
+        assert!(matches!(
+            BlockData::from_bytes(&[], &OperationCode::Accept),
+            Err(BlockDataError::Empty)
+        ));
+    }
+
+    #[test]
+    fn block_signature_versions() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let private_key = random_private_key(bitcoin::Network::Testnet);
+        let public_key = private_key.public_key(&secp).to_string();
+
+        let genesis = Block::new(
+            1,
+            hex::encode("GENESIS"),
+            "00".to_string(),
+            "bill".to_string(),
+            public_key.clone(),
+            OperationCode::Issue,
+            private_key.to_string(),
+        );
+        assert_eq!(genesis.version, BLOCK_VERSION);
+        assert!(genesis.verifier());
+        let block = Block::new(
+            2,
+            genesis.hash.clone(),
+            "01".to_string(),
+            "bill".to_string(),
+            public_key,
+            OperationCode::Accept,
+            private_key.to_string(),
+        );
+        assert!(is_block_valid(&block, &genesis));
+        let mut downgraded = block.clone();
+        downgraded.version = RSA_BLOCK_VERSION;
+        assert!(!is_block_valid(&downgraded, &genesis));
+
+        // Blocks written before versions have no version field and are signed with RSA.
+        let rsa = Rsa::generate(2048).unwrap();
+        let mut legacy = serde_json::json!({
+            "id": 3,
+            "bill_name": "bill",
+            "previous_hash": block.hash,
+            "data": "02",
+            "timestamp": block.timestamp,
+            "public_key": String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap(),
+            "operation_code": OperationCode::Endorse,
+        });
+        let hash = hex::encode(sha256(legacy.to_string().as_bytes()));
+        let private_key_pem = String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap();
+        legacy["signature"] = signature(hash.clone(), private_key_pem).into();
+        legacy["hash"] = hash.into();
+        let legacy: Block = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.version, RSA_BLOCK_VERSION);
+        assert!(is_block_valid(&legacy, &block));
+
+        // Blocks from peers are rejected instead of panicking on what they carry.
+        let mut garbled = legacy.clone();
+        garbled.public_key = "not a key".to_string();
+        assert!(!garbled.verifier());
+        garbled.signature = "not hex".to_string();
+        assert!(!garbled.verifier());
+        assert!(matches!(
+            block.get_block_data(),
+            Err(BlockDataError::Undecryptable(_))
+        ));
+        let other_bill = Block::new(
+            2,
+            genesis.hash.clone(),
+            "01".to_string(),
+            "other bill".to_string(),
+            private_key.public_key(&secp).to_string(),
+            OperationCode::Accept,
+            private_key.to_string(),
+        );
+        assert!(!is_block_valid(&other_bill, &genesis));
+    }
+
+    #[test]
+    fn protests_are_made_by_the_holder() {
+        let identity = identity_with_rsa_key("holder", 2);
+        let holder = IdentityPublicData::new(identity.clone(), "peer_holder".to_string());
+        let bill = bill_paid_to(holder.clone());
+        let protest = ProtestDocument::new(
+            &bill,
+            ProtestReason::NonPayment,
+            holder.clone(),
+            1000,
+            NaiveDate::from_ymd_opt(2030, 1, 2).unwrap(),
+            identity.private_key_pem.clone(),
+        );
+        let is_valid = |protest: &ProtestDocument, operation_code: OperationCode| {
+            is_valid_protest(protest, &operation_code, "bill", 0, &holder, &[])
+        };
+        assert!(is_valid(&protest, OperationCode::ProtestNonPayment));
+        assert!(protest.text().contains("failed to pay the bill bill"));
+        assert!(!is_valid(&protest, OperationCode::ProtestNonAcceptance));
+
+        let mut forged = protest.clone();
+        forged.amount_numbers = 10;
+        assert!(!is_valid(&forged, OperationCode::ProtestNonPayment));
+        // Signed by someone else in the name of the holder.
+        let other = identity_with_rsa_key("other", 3);
+        let mut impostor = holder.clone();
+        impostor.rsa_public_key_pem = other.public_key_pem.clone();
+        let impersonated = ProtestDocument::new(
+            &bill,
+            ProtestReason::NonPayment,
+            impostor,
+            1000,
+            NaiveDate::from_ymd_opt(2030, 1, 2).unwrap(),
+            other.private_key_pem,
+        );
+        assert!(impersonated.verify());
+        assert!(!is_valid(&impersonated, OperationCode::ProtestNonPayment));
+    }
+
+    #[test]
+    fn only_authorized_parties_sign() {
+        let mut drawee = identity_public_data("drawee");
+        drawee.bitcoin_public_key = "02drawee".to_string();
+        let mut holder = identity_public_data("holder");
+        holder.bitcoin_public_key = "02holder".to_string();
+        holder.rsa_public_key_pem = "holder pem".to_string();
+
+        let mut guarantor = identity_public_data("guarantor");
+        guarantor.bitcoin_public_key = "02guarantor".to_string();
+        let mut state = ChainState {
+            drawee: drawee.clone(),
+            holder: holder.clone(),
+            obligors: vec![drawee.clone()],
+            aval_requests: vec![(drawee.clone(), guarantor.clone())],
+            amount: 1000,
+            accepted_amount: None,
+            protested_non_acceptance: false,
+            protested_amount: None,
+            maturity_date: None,
+            recourses: Vec::new(),
+            open_recourse: false,
+            rotations: Vec::new(),
+        };
+        let accept = BlockData::Accept {
+            accepter: drawee.clone(),
+        };
+        let endorse = BlockData::Endorse {
+            endorsee: identity_public_data("endorsee"),
+            endorser: holder.clone(),
+        };
+        let request_to_pay = BlockData::RequestToPay {
+            requester: holder.clone(),
+        };
+        let paid = BlockData::Paid {
+            holder: holder.clone(),
+            payment_address: String::new(),
+            amount: 0,
+            txids: Vec::new(),
+            lightning: None,
+        };
+        let aval = BlockData::Aval {
+            guarantor: guarantor.clone(),
+            guaranteed: drawee.clone(),
+        };
+        let request_to_aval = BlockData::RequestToAval {
+            requester: drawee.clone(),
+            guarantor: guarantor.clone(),
+        };
+
+        assert!(may_sign(&accept, "02drawee", 0, &state));
+        assert!(!may_sign(&accept, "02holder", 0, &state));
+        assert!(may_sign(&endorse, "02holder", 0, &state));
+        assert!(may_sign(&request_to_pay, "holder pem", 0, &state));
+        assert!(!may_sign(&endorse, "02drawee", 0, &state));
+        assert!(!may_sign(&request_to_pay, "02other", 0, &state));
+        assert!(may_sign(&paid, "02holder", 0, &state));
+        assert!(!may_sign(&paid, "02drawee", 0, &state));
+        assert!(may_sign(&aval, "02guarantor", 0, &state));
+        assert!(!may_sign(&aval, "02holder", 0, &state));
+        let unasked = BlockData::Aval {
+            guarantor: holder.clone(),
+            guaranteed: drawee.clone(),
+        };
+        assert!(!may_sign(&unasked, "02holder", 0, &state));
+        assert!(may_sign(&request_to_aval, "02drawee", 0, &state));
+        assert!(!may_sign(&request_to_aval, "02guarantor", 0, &state));
+        // Parties without a key can't be impersonated with an empty one.
+        assert!(!may_sign(&endorse, "", 0, &state));
+
+        state.apply(&aval);
+        assert!(!may_sign(&aval, "02guarantor", 0, &state));
+        assert!(state
+            .obligors
+            .iter()
+            .any(|obligor| obligor.peer_id == guarantor.peer_id));
+        state.apply(&endorse);
+        assert!(!may_sign(&request_to_pay, "02holder", 0, &state));
+        // The endorser is liable now and may ask for an aval.
+        let endorser_request = BlockData::RequestToAval {
+            requester: holder.clone(),
+            guarantor: guarantor.clone(),
+        };
+        assert!(may_sign(&endorser_request, "02holder", 0, &state));
+        assert_eq!(state.holder.peer_id, "peer_endorsee");
+
+        // The drawee accepts once, for at most the bill amount.
+        let partial_accept = |amount| BlockData::PartialAccept {
+            accepter: drawee.clone(),
+            amount,
+        };
+        assert!(!may_sign(&partial_accept(0), "02drawee", 0, &state));
+        assert!(!may_sign(&partial_accept(1001), "02drawee", 0, &state));
+        assert!(may_sign(&partial_accept(400), "02drawee", 0, &state));
+        state.apply(&partial_accept(400));
+        assert_eq!(state.accepted_amount, Some(400));
+        assert!(!may_sign(&accept, "02drawee", 0, &state));
+        assert!(!may_sign(&partial_accept(600), "02drawee", 0, &state));
+        state.accepted_amount = None;
+        state.protested_non_acceptance = true;
+        assert!(!may_sign(&accept, "02drawee", 0, &state));
+    }
+
+    #[test]
+    fn forks_resolve_to_earliest_branch() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let private_key = random_private_key(bitcoin::Network::Testnet);
+        let new_block = |id: u64, previous_hash: &str, data: &str| {
+            Block::new(
+                id,
+                previous_hash.to_string(),
+                data.to_string(),
+                "bill".to_string(),
+                private_key.public_key(&secp).to_string(),
+                OperationCode::Endorse,
+                private_key.to_string(),
+            )
+        };
+        let mut genesis = new_block(1, &hex::encode("GENESIS"), "00");
+        genesis.timestamp -= 10;
+        let mut local = Chain::new(genesis.clone());
+        local.blocks.push(new_block(2, &genesis.hash, "01"));
+        let mut remote = Chain::new(genesis.clone());
+        let mut endorse = new_block(2, &genesis.hash, "02");
+        endorse.timestamp -= 5;
+        remote.blocks.push(endorse.clone());
+
+        assert_eq!(local.find_fork(&remote), Some(0));
+        assert_eq!(local.find_fork(&Chain::new(genesis.clone())), None);
+        assert_eq!(local.find_fork(&local.clone()), None);
+        let branch = local.branch_with(&endorse).unwrap();
+        assert_eq!(branch.blocks, remote.blocks);
+        assert!(local.branch_with(&local.blocks[1]).is_none());
+
+        // Without attested times the lower hash wins, whatever the signers claim as time.
+        let (mut lower, higher) = if endorse.hash < local.blocks[1].hash {
+            (endorse.clone(), local.blocks[1].clone())
+        } else {
+            (local.blocks[1].clone(), endorse.clone())
+        };
+        assert!(prefers_branch(&lower, &higher));
+        assert!(!prefers_branch(&higher, &lower));
+        lower.timestamp = higher.timestamp + 1000;
+        assert!(prefers_branch(&lower, &higher));
+
+        let fork = Fork {
+            ancestor_id: 1,
+            kept: remote.blocks[1..].to_vec(),
+            discarded: local.blocks[1..].to_vec(),
+            detected_at: 0,
+        };
+        assert_eq!(fork.conflict(), "double endorse after block 1");
+    }
+
+    #[test]
+    fn timestamp_tokens() {
+        let authority = LocalTimestampAuthority::new();
+        let hash = hex::encode(sha256(b"block"));
+        let token = authority.timestamp(&hash).unwrap();
+
+        let store = store_with(vec![authority.certificate().clone()]).unwrap();
+        let time = verify_token_with(&token, &hash, &store).unwrap();
+        assert!((chrono::Utc::now().timestamp() - time).abs() < 5);
+        let other_hash = hex::encode(sha256(b"other block"));
+        assert!(matches!(
+            verify_token_with(&token, &other_hash, &store),
+            Err(TimestampError::ImprintMismatch)
+        ));
+        let store = store_with(Vec::new()).unwrap();
+        assert!(matches!(
+            verify_token_with(&token, &hash, &store),
+            Err(TimestampError::Untrusted(_))
+        ));
+    }
+
+    #[test]
+    fn anchor_merkle_proofs_and_transaction() {
+        for count in 1..6 {
+            let leaves: Vec<[u8; 32]> = (0..count)
+                .map(|index| leaf_hash(&format!("bill{}", index), "head"))
+                .collect();
+            let (root, proofs) = merkle_tree(&leaves);
+            for (leaf, proof) in leaves.iter().zip(&proofs) {
+                assert_eq!(root_from_proof(*leaf, proof), root);
+            }
+            assert_ne!(
+                root_from_proof(leaf_hash("other", "head"), &proofs[0]),
+                root
+            );
+        }
+
+        let private_key = random_private_key(bitcoin::Network::Testnet);
+        let utxos: Vec<crate::api::Utxo> = serde_json::from_str(
+            r#"[
+                {"txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b", "vout": 0, "value": 400, "status": {"confirmed": true}},
+                {"txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b", "vout": 1, "value": 5000, "status": {"confirmed": true}},
+                {"txid": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b", "vout": 2, "value": 90000, "status": {"confirmed": false}}
+            ]"#,
+        )
+        .unwrap();
+        let root = [7u8; 32];
+        let transaction = anchoring_transaction(&utxos, &root, &private_key, 2).unwrap();
+        assert_eq!(transaction.input.len(), 1);
+        assert_eq!(transaction.input[0].previous_output.vout, 1);
+        assert_eq!(transaction.input[0].witness.len(), 2);
+        let data = transaction.output[0].script_pubkey.as_bytes();
+        assert!(transaction.output[0].script_pubkey.is_op_return());
+        assert_eq!(&data[2..6], ANCHOR_TAG);
+        assert_eq!(&data[6..], &root);
+        assert_eq!(transaction.output[1].value, 5000 - 2 * (11 + 68 + 47 + 31));
+
+        assert!(matches!(
+            anchoring_transaction(&utxos[..1], &root, &private_key, 2),
+            Err(AnchorError::InsufficientFunds(_))
+        ));
+    }
+
+    #[test]
+    fn payment_address_keys_aggregate() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let (bill_key, bill_public_key) =
+            secp.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng());
+        let (holder_key, holder_public_key) =
+            secp.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng());
+
+        for address_type in [
+            PaymentAddressType::P2pkh,
+            PaymentAddressType::P2wpkh,
+            PaymentAddressType::P2tr,
+        ] {
+            let public_key =
+                address_type.aggregate_public_key(&[bill_public_key, holder_public_key]);
+            let private_key = address_type.aggregate_private_key(&[bill_key, holder_key]);
+            assert_eq!(private_key.public_key(&secp), public_key);
+            // The order of the keys does not matter.
+            assert_eq!(
+                address_type.aggregate_public_key(&[holder_public_key, bill_public_key]),
+                public_key
+            );
+        }
+
+        let sum =
+            PaymentAddressType::P2wpkh.aggregate_public_key(&[bill_public_key, holder_public_key]);
+        let aggregate =
+            PaymentAddressType::P2tr.aggregate_public_key(&[bill_public_key, holder_public_key]);
+        assert_ne!(sum, aggregate);
+        assert!(PaymentAddressType::P2wpkh
+            .address(&sum)
+            .to_string()
+            .starts_with("tb1q"));
+        assert!(PaymentAddressType::P2tr
+            .address(&aggregate)
+            .to_string()
+            .starts_with("tb1p"));
+        assert_eq!(
+            PaymentAddressType::from_code("p2tr"),
+            Some(PaymentAddressType::P2tr)
+        );
+    }
+
+    #[test]
+    fn sweep_psbt_spends_payment_address() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let private_key = random_private_key(USEDNET);
+        let public_key = private_key.public_key(&secp);
+        let destination = bitcoin::Address::p2wpkh(&public_key, USEDNET).unwrap();
+
+        for address_type in [
+            PaymentAddressType::P2pkh,
+            PaymentAddressType::P2wpkh,
+            PaymentAddressType::P2tr,
+        ] {
+            let address = address_type.address(&public_key.inner);
+            let previous = bitcoin::Transaction {
+                version: 2,
+                lock_time: bitcoin::absolute::LockTime::ZERO,
+                input: Vec::new(),
+                output: vec![bitcoin::TxOut {
+                    value: 10_000,
+                    script_pubkey: address.script_pubkey(),
+                }],
+            };
+            let utxo: crate::api::Utxo = serde_json::from_value(serde_json::json!({
+                "txid": previous.txid().to_string(),
+                "vout": 0,
+                "value": 10_000,
+                "status": {"confirmed": true}
+            }))
+            .unwrap();
+
+            let mut psbt = sweep_psbt(
+                vec![(utxo.clone(), previous.clone())],
+                address_type,
+                &destination,
+                2,
+            )
+            .unwrap();
+            assert_eq!(
+                psbt.unsigned_tx.output[0].value,
+                10_000 - 2 * (10 + address_type.input_vsize() + 31)
+            );
+            sign_psbt(&mut psbt, address_type, &private_key);
+            let transaction = psbt.extract_tx();
+
+            let mut sighash_cache = bitcoin::sighash::SighashCache::new(&transaction);
+            let verified = match address_type {
+                PaymentAddressType::P2pkh => {
+                    let sighash = sighash_cache
+                        .legacy_signature_hash(0, &address.script_pubkey(), 1)
+                        .unwrap();
+                    let signature = match transaction.input[0].script_sig.instructions().next() {
+                        Some(Ok(bitcoin::script::Instruction::PushBytes(bytes))) => {
+                            bitcoin::ecdsa::Signature::from_slice(bytes.as_bytes()).unwrap()
+                        }
+                        other => panic!("unexpected script sig {:?}", other),
+                    };
+                    let message = bitcoin::secp256k1::Message::from_slice(&sighash[..]).unwrap();
+                    secp.verify_ecdsa(&message, &signature.sig, &public_key.inner)
+                }
+                PaymentAddressType::P2wpkh => {
+                    let sighash = sighash_cache
+                        .segwit_signature_hash(
+                            0,
+                            &address.script_pubkey().p2wpkh_script_code().unwrap(),
+                            10_000,
+                            bitcoin::sighash::EcdsaSighashType::All,
+                        )
+                        .unwrap();
+                    let signature = bitcoin::ecdsa::Signature::from_slice(
+                        transaction.input[0].witness.nth(0).unwrap(),
+                    )
+                    .unwrap();
+                    let message = bitcoin::secp256k1::Message::from_slice(&sighash[..]).unwrap();
+                    secp.verify_ecdsa(&message, &signature.sig, &public_key.inner)
+                }
+                PaymentAddressType::P2tr => {
+                    let sighash = sighash_cache
+                        .taproot_key_spend_signature_hash(
+                            0,
+                            &bitcoin::sighash::Prevouts::All(&previous.output),
+                            bitcoin::sighash::TapSighashType::Default,
+                        )
+                        .unwrap();
+                    let signature = bitcoin::secp256k1::schnorr::Signature::from_slice(
+                        transaction.input[0].witness.nth(0).unwrap(),
+                    )
+                    .unwrap();
+                    let output_key =
+                        XOnlyPublicKey::from_slice(&address.script_pubkey().as_bytes()[2..])
+                            .unwrap();
+                    let message = bitcoin::secp256k1::Message::from_slice(&sighash[..]).unwrap();
+                    secp.verify_schnorr(&signature, &message, &output_key)
+                }
+            };
+            assert!(verified.is_ok(), "{:?} signature is invalid", address_type);
+
+            assert!(matches!(
+                sweep_psbt(vec![(utxo, previous)], address_type, &destination, 100),
+                Err(PayoutError::InsufficientFunds { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rotated_keys_still_sweep_bill() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let seed = [7; 64];
+        let old_identity = identity_from_seed(&seed, 0);
+        let new_identity = identity_from_seed(&seed, 1);
+        let mut payee = identity_public_data("payee");
+        payee.bitcoin_public_key = old_identity.bitcoin_public_key.clone();
+        let bill = bill_paid_to(payee);
+        let address = bitcoin::Address::from_str(&get_address_to_pay(bill.clone()))
+            .unwrap()
+            .require_network(USEDNET)
+            .unwrap();
+
+        // The bill names the holder with the key it rotated away from.
+        let no_retired_keys = HashMap::new();
+        assert_eq!(
+            bitcoin_private_key_for(
+                &new_identity,
+                &no_retired_keys,
+                &old_identity.bitcoin_public_key
+            ),
+            None
+        );
+        let mut retired_keys = HashMap::new();
+        retire_bitcoin_key(&mut retired_keys, &old_identity);
+        let holder_private_key = bitcoin_private_key_for(
+            &new_identity,
+            &retired_keys,
+            &old_identity.bitcoin_public_key,
+        )
+        .unwrap();
+        let private_key = payee_private_key(&bill, &holder_private_key);
+        assert_eq!(
+            bill.payment_address_type
+                .address(&private_key.public_key(&secp).inner),
+            address
+        );
+
+        let previous = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: Vec::new(),
+            output: vec![bitcoin::TxOut {
+                value: 10_000,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        let utxo: crate::api::Utxo = serde_json::from_value(serde_json::json!({
+            "txid": previous.txid().to_string(),
+            "vout": 0,
+            "value": 10_000,
+            "status": {"confirmed": true}
+        }))
+        .unwrap();
+        let destination =
+            bitcoin::Address::p2wpkh(&private_key.public_key(&secp), USEDNET).unwrap();
+        let mut psbt = sweep_psbt(
+            vec![(utxo, previous)],
+            bill.payment_address_type,
+            &destination,
+            2,
+        )
+        .unwrap();
+        sign_psbt(&mut psbt, bill.payment_address_type, &private_key);
+        let transaction = psbt.extract_tx();
+        let signature =
+            bitcoin::ecdsa::Signature::from_slice(transaction.input[0].witness.nth(0).unwrap())
+                .unwrap();
+        let sighash = bitcoin::sighash::SighashCache::new(&transaction)
+            .segwit_signature_hash(
+                0,
+                &address.script_pubkey().p2wpkh_script_code().unwrap(),
+                10_000,
+                bitcoin::sighash::EcdsaSighashType::All,
+            )
+            .unwrap();
+        let message = bitcoin::secp256k1::Message::from_slice(&sighash[..]).unwrap();
+        assert!(secp
+            .verify_ecdsa(
+                &message,
+                &signature.sig,
+                &private_key.public_key(&secp).inner
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn block_data_from_legacy_string() {
+        let endorsee = hex::encode(serde_json::to_vec(&identity_public_data("alice")).unwrap());
+        let endorser = hex::encode(serde_json::to_vec(&identity_public_data("bob")).unwrap());
+        let legacy = format!("Endorsed to {} endorsed by {}", endorsee, endorser);
+
+        match BlockData::from_bytes(legacy.as_bytes(), &OperationCode::Endorse).unwrap() {
+            BlockData::Endorse { endorsee, endorser } => {
+                assert_eq!(endorsee.peer_id, "peer_alice");
+                assert_eq!(endorser.peer_id, "peer_bob");
+            }
+            other => panic!("unexpected block data {:?}", other),
+        }
+
+        assert!(matches!(
+            BlockData::from_bytes(b"Accepted by zz", &OperationCode::Accept),
+            Err(BlockDataError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn protest_deadlines() {
+        let maturity = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
+        let day = |d: u32| NaiveDate::from_ymd_opt(2023, 7, d).unwrap();
+
+        assert!(is_within_non_acceptance_deadline(maturity, maturity));
+        assert!(!is_within_non_acceptance_deadline(maturity, day(1)));
+
+        assert!(!is_within_non_payment_deadline(maturity, maturity));
+        assert!(is_within_non_payment_deadline(maturity, day(1)));
+        assert!(is_within_non_payment_deadline(maturity, day(2)));
+        assert!(!is_within_non_payment_deadline(maturity, day(3)));
+    }
+
+    #[test]
+    fn recourse_interest_from_maturity() {
+        let maturity = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let year_later = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let before_maturity = NaiveDate::from_ymd_opt(2022, 12, 1).unwrap();
+
+        assert_eq!(
+            recourse_interest(1_000_000, maturity, year_later),
+            Ok(60_000)
+        );
+        assert_eq!(recourse_interest(1_000_000, maturity, maturity), Ok(0));
+        assert_eq!(
+            recourse_interest(1_000_000, maturity, before_maturity),
+            Ok(0)
+        );
+        assert_eq!(
+            recourse_interest(u64::MAX, maturity, year_later),
+            Err(AmountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn recourse_claims_follow_the_chain() {
+        let holder = identity_public_data("holder");
+        let mut state = ChainState::new(&bill_paid_to(holder.clone()), Vec::new());
+        let claim = |recoursee: &str| RecourseClaim {
+            bill_name: "bill".to_string(),
+            recourser: holder.clone(),
+            recoursee: identity_public_data(recoursee),
+            principal: 1000,
+            interest: 0,
+            costs: 10,
+            total: 1010,
+            currency_code: "sat".to_string(),
+            payment_address: String::new(),
+            date_of_recourse: "2030-01-01".to_string(),
+        };
+        let date = NaiveDate::from_ymd_opt(2030, 1, 2).unwrap();
+
+        // No recourse before a protest.
+        assert!(!state.may_take_recourse(&claim("drawer"), date));
+        state.protested_amount = Some(1000);
+        assert!(state.may_take_recourse(&claim("drawer"), date));
+        assert!(!state.may_take_recourse(&claim("drawee"), date));
+        assert!(!state.may_take_recourse(&claim("stranger"), date));
+        let mut inflated = claim("drawer");
+        inflated.total = 2000;
+        assert!(!state.may_take_recourse(&inflated, date));
+        let mut overflowing = claim("drawer");
+        overflowing.costs = u64::MAX;
+        overflowing.total = u64::MAX;
+        assert!(!state.may_take_recourse(&overflowing, date));
+        let mut postdated = claim("drawer");
+        postdated.date_of_recourse = "2030-01-03".to_string();
+        assert!(!state.may_take_recourse(&postdated, date));
+
+        state.obligors.push(identity_public_data("endorser"));
+        state.apply(&BlockData::Recourse {
+            recourse: claim("drawer"),
+        });
+        // One recourse at a time, and once against each party.
+        assert!(!state.may_take_recourse(&claim("endorser"), date));
+        state.apply(&BlockData::RecoursePaid {
+            recourser: holder.clone(),
+            recoursee: identity_public_data("drawer"),
+            txids: Vec::new(),
+        });
+        assert!(state.may_take_recourse(&claim("endorser"), date));
+        assert!(!state.may_take_recourse(&claim("drawer"), date));
+    }
+
+    #[test]
+    fn one_recourse_at_a_time() {
+        let liable_parties = vec![
+            identity_public_data("drawer"),
+            identity_public_data("endorser"),
+            identity_public_data("holder"),
+        ];
+        let first_claim = RecourseClaim {
+            bill_name: "bill".to_string(),
+            recourser: identity_public_data("holder"),
+            recoursee: identity_public_data("drawer"),
+            principal: 1000,
+            interest: 0,
+            costs: 0,
+            total: 1000,
+            currency_code: "sat".to_string(),
+            payment_address: String::new(),
+            date_of_recourse: "2030-01-02".to_string(),
+        };
+        let names = |parties: Vec<IdentityPublicData>| -> Vec<String> {
+            parties.into_iter().map(|party| party.name).collect()
+        };
+
+        assert_eq!(
+            names(remaining_recoursees(
+                liable_parties.clone(),
+                &[],
+                "peer_holder",
+                false
+            )),
+            vec!["drawer", "endorser"]
+        );
+        // No second claim while the first one is not paid.
+        assert!(remaining_recoursees(
+            liable_parties.clone(),
+            std::slice::from_ref(&first_claim),
+            "peer_holder",
+            true
+        )
+        .is_empty());
+        assert_eq!(
+            names(remaining_recoursees(
+                liable_parties,
+                &[first_claim],
+                "peer_holder",
+                false
+            )),
+            vec!["endorser"]
+        );
+    }
+
+    #[test]
+    fn aval_names_guarantor_as_participant() {
+        let block_data = BlockData::Aval {
+            guarantor: identity_public_data("carol"),
+            guaranteed: identity_public_data("dave"),
+        };
+        let bytes = block_data.to_bytes();
+        let block_data = BlockData::from_bytes(&bytes, &OperationCode::Aval).unwrap();
+
+        let participants: Vec<&str> = block_data
+            .participants()
+            .iter()
+            .map(|participant| participant.peer_id.as_str())
+            .collect();
+        assert_eq!(participants, vec!["peer_carol", "peer_dave"]);
+    }
+
+    #[test]
+    fn partial_payment_received_by_address() {
+        let transaction: crate::api::Transaction = serde_json::from_str(
+            r#"{
+                "txid": "abc",
+                "status": {"confirmed": false},
+                "vout": [
+                    {"scriptpubkey_address": "bill_address", "value": 400},
+                    {"scriptpubkey_address": "change_address", "value": 1000},
+                    {"scriptpubkey_address": "bill_address", "value": 100},
+                    {"value": 0}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(transaction.received_by("bill_address"), 500);
+        assert_eq!(transaction.received_by("other_address"), 0);
+    }
+
+    #[test]
+    fn address_info_from_transactions() {
+        let transactions: Vec<crate::api::Transaction> = serde_json::from_str(
+            r#"[
+                {"txid": "b", "status": {"confirmed": false}, "vout": [{"scriptpubkey_address": "bill_address", "value": 300}]},
+                {"txid": "a", "status": {"confirmed": true, "block_time": 1}, "vout": [
+                    {"scriptpubkey_address": "bill_address", "value": 400},
+                    {"scriptpubkey_address": "bill_address", "value": 100},
+                    {"scriptpubkey_address": "change_address", "value": 1000}
+                ]}
+            ]"#,
+        )
+        .unwrap();
+        let utxos: Vec<crate::api::Utxo> = serde_json::from_str(
+            r#"[
+                {"txid": "a", "vout": 1, "value": 100, "status": {"confirmed": true}},
+                {"txid": "b", "vout": 0, "value": 300, "status": {"confirmed": false}}
+            ]"#,
+        )
+        .unwrap();
+
+        let info = AddressInfo::from_transactions("bill_address", &transactions, &utxos);
+        assert_eq!(info.chain_stats.tx_count, 1);
+        assert_eq!(info.chain_stats.funded_txo_count, 2);
+        assert_eq!(info.chain_stats.funded_txo_sum, 500);
+        assert_eq!(info.chain_stats.spent_txo_sum, 400);
+        assert_eq!(info.mempool_stats.funded_txo_sum, 300);
+        assert_eq!(info.mempool_stats.spent_txo_sum, 0);
+    }
+
+    #[test]
+    fn payments_count_once_deep_enough() {
+        let transactions: Vec<crate::api::Transaction> = serde_json::from_str(
+            r#"[
+                {"txid": "c", "status": {"confirmed": false}, "vout": [{"scriptpubkey_address": "bill_address", "value": 50}]},
+                {"txid": "b", "status": {"confirmed": true, "block_height": 105}, "vout": [{"scriptpubkey_address": "bill_address", "value": 300}]},
+                {"txid": "a", "status": {"confirmed": true, "block_height": 100}, "vout": [{"scriptpubkey_address": "bill_address", "value": 200}]}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(transactions[1].status.confirmations(105), 1);
+        assert_eq!(transactions[0].status.confirmations(105), 0);
+        assert_eq!(
+            confirmed_payments(&transactions, "bill_address", 105, 6),
+            (vec!["a".to_string()], 200)
+        );
+        assert_eq!(
+            confirmed_payments(&transactions, "bill_address", 110, 6),
+            (vec!["a".to_string(), "b".to_string()], 500)
+        );
+    }
+
+    /// Runs against the regtest bitcoind in `BITCREDIT_TEST_BITCOIND_URL` if it is set.
+    #[tokio::test]
+    async fn payment_flow_on_regtest() {
+        let url = match std::env::var("BITCREDIT_TEST_BITCOIND_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let user = std::env::var(BITCOIND_USER_ENV_VAR).unwrap_or_default();
+        let password = std::env::var(BITCOIND_PASSWORD_ENV_VAR).unwrap_or_default();
+        let funder = BitcoindBackend::new(
+            url.clone(),
+            user.clone(),
+            password.clone(),
+            "bitcredit_test_funder".to_string(),
+        );
+        let _ = funder
+            .call("createwallet", serde_json::json!(["bitcredit_test_funder"]))
+            .await;
+        let _ = funder
+            .call("loadwallet", serde_json::json!(["bitcredit_test_funder"]))
+            .await;
+        let mining_address = funder
+            .wallet_call("getnewaddress", serde_json::json!([]))
+            .await
+            .unwrap();
+        funder
+            .call(
+                "generatetoaddress",
+                serde_json::json!([101, mining_address]),
+            )
+            .await
+            .unwrap();
+        let backend = BitcoindBackend::new(url, user, password, "bitcredit_test_watch".to_string());
+        assert_eq!(
+            backend.genesis_hash().await.unwrap(),
+            bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Regtest)
+                .block_hash()
+                .to_string()
+        );
+
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let public_key = bitcoin::PublicKey::new(
+            secp.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng())
+                .1,
+        );
+        let address = bitcoin::Address::p2pkh(&public_key, bitcoin::Network::Testnet).to_string();
+        assert!(backend
+            .address_transactions(&address)
+            .await
+            .unwrap()
+            .is_empty());
+
+        funder
+            .wallet_call("sendtoaddress", serde_json::json!([address, 0.001]))
+            .await
+            .unwrap();
+        let transactions = backend.address_transactions(&address).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].received_by(&address), 100_000);
+        assert!(!transactions[0].status.confirmed);
+
+        funder
+            .call("generatetoaddress", serde_json::json!([1, mining_address]))
+            .await
+            .unwrap();
+        let info = backend.address_info(&address).await.unwrap();
+        assert_eq!(info.chain_stats.funded_txo_sum, 100_000);
+        assert_eq!(info.mempool_stats.funded_txo_sum, 0);
+    }
+
+    /// Answers HTTP requests on a local port with `respond(path, body)` and records the paths.
+    fn serve_http(
+        respond: impl Fn(&str, &str) -> String + Send + 'static,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::{BufRead, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let paths = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requested = paths.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let response = respond(&path, &String::from_utf8(body).unwrap());
+                requested.lock().unwrap().push(path);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        (url, paths)
+    }
+
+    fn esplora_transactions(
+        txids: std::ops::Range<u32>,
+        confirmed: bool,
+    ) -> Vec<serde_json::Value> {
+        txids
+            .map(|index| {
+                serde_json::json!({
+                    "txid": format!("tx{}", index),
+                    "status": {"confirmed": confirmed, "block_height": 1000 - index},
+                    "vout": [{"scriptpubkey_address": "bill_address", "value": 1}],
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn esplora_pages_through_address_transactions() {
+        let (url, paths) = serve_http(|path, _| {
+            let transactions = match path {
+                "/address/bill_address/txs" => {
+                    let mut page = esplora_transactions(100..101, false);
+                    page.extend(esplora_transactions(0..25, true));
+                    page
+                }
+                "/address/bill_address/txs/chain/tx24" => esplora_transactions(25..50, true),
+                "/address/bill_address/txs/chain/tx49" => esplora_transactions(50..53, true),
+                _ => Vec::new(),
+            };
+            serde_json::Value::from(transactions).to_string()
+        });
+        let backend = EsploraBackend { url };
+
+        let transactions = backend.address_transactions("bill_address").await.unwrap();
+        assert_eq!(transactions.len(), 54);
+        assert_eq!(transactions[0].txid, "tx100");
+        assert_eq!(transactions[53].txid, "tx52");
+        assert_eq!(
+            *paths.lock().unwrap(),
+            vec![
+                "/address/bill_address/txs",
+                "/address/bill_address/txs/chain/tx24",
+                "/address/bill_address/txs/chain/tx49",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn bitcoind_confirmations_above_the_tip_do_not_underflow() {
+        let transaction = bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: 1000,
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        };
+        let hex = bitcoin::consensus::encode::serialize_hex(&transaction);
+        // The tip moved back in a reorg after the confirmations were counted.
+        let (url, _) = serve_http(move |_, body| {
+            let request: serde_json::Value = serde_json::from_str(body).unwrap();
+            let result = match request["method"].as_str() {
+                Some("gettransaction") => serde_json::json!({"hex": hex, "confirmations": 10}),
+                Some("getblockcount") => serde_json::json!(5),
+                _ => serde_json::Value::Null,
+            };
+            serde_json::json!({"result": result, "error": null}).to_string()
+        });
+        let backend = BitcoindBackend::new(url, String::new(), String::new(), "w".to_string());
+
+        let transaction = backend
+            .transaction(&transaction.txid().to_string())
+            .await
+            .unwrap();
+        assert!(transaction.status.confirmed);
+        assert_eq!(transaction.status.block_height, Some(0));
+        assert_eq!(transaction.vout[0].value, 1000);
+    }
+
+    #[test]
+    fn unknown_chain_backend_is_not_built() {
+        assert!(chain_backend("nonsense").is_none());
+        for kind in ["esplora", "electrum", "bitcoind"] {
+            assert!(chain_backend(kind).is_some());
+        }
+        assert_eq!(default_backend_kind(bitcoin::Network::Regtest), "bitcoind");
+        assert_eq!(default_backend_kind(bitcoin::Network::Testnet), "esplora");
+    }
+
+    #[test]
+    fn simple_and_compound_interest() {
+        assert_eq!(interest(1_000_000, 500, false, 365).unwrap(), 50_000);
+        assert_eq!(interest(1_000_000, 500, false, 73).unwrap(), 10_000);
+        assert_eq!(interest(1_000_000, 500, true, 730).unwrap(), 102_500);
+        assert_eq!(interest(1_000_000, 0, true, 730).unwrap(), 0);
+        // Large amounts don't overflow in between, only interest beyond the amount range.
+        assert_eq!(
+            interest(u64::MAX / 2, 10_000, false, 365).unwrap(),
+            u64::MAX / 2
+        );
+        assert!(matches!(
+            interest(u64::MAX / 2, 10_000, false, 1000),
+            Err(AmountError::Overflow)
+        ));
+        assert!(matches!(
+            interest(u64::MAX / 2, 10_000, true, 1000),
+            Err(AmountError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn amounts_in_currencies() {
+        let amount = Amount::parse("1.05", Currency::Bitcoin).unwrap();
+        assert_eq!(amount.minor_units(), 105_000_000);
+        assert_eq!(amount.to_string(), "1.05 BTC");
+        assert_eq!(amount.to_sats(), Some(105_000_000));
+        assert_eq!(
+            encode_amount(&amount, "en").unwrap(),
+            "one bitcoin and fifty millibitcoin"
+        );
+
+        let amount = amount.convert(Currency::MilliBitcoin).unwrap();
+        assert_eq!(amount.to_string(), "1050 mBTC");
+
+        let amount = Amount::parse("12.5", Currency::Fiat("EUR".to_string())).unwrap();
+        assert_eq!(amount.to_string(), "12.50 EUR");
+        assert_eq!(amount.to_sats(), None);
+        assert_eq!(
+            encode_amount(&amount, "en").unwrap(),
+            "twelve euros and fifty cents"
+        );
+
+        assert_eq!(
+            Amount::parse("0.001", Currency::Fiat("USD".to_string())),
+            Err(AmountError::TooPrecise { decimals: 2 })
+        );
+        assert!(matches!(
+            Amount::parse("1,5", Currency::Satoshi),
+            Err(AmountError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn amounts_in_words_in_bill_language() {
+        assert_eq!(encode(&21, "de").unwrap(), "einundzwanzig");
+        assert_eq!(encode(&1_001_000, "de").unwrap(), "eine Million eintausend");
+        assert_eq!(encode(&80_000, "fr").unwrap(), "quatre-vingt mille");
+        assert_eq!(encode(&71, "fr").unwrap(), "soixante et onze");
+        assert_eq!(encode(&21_000, "es").unwrap(), "veintiún mil");
+        assert_eq!(encode(&500, "es").unwrap(), "quinientos");
+        assert_eq!(encode(&128, "it").unwrap(), "centoventotto");
+        assert_eq!(encode(&2_000, "it").unwrap(), "duemila");
+
+        let amount = Amount::parse("1000000.01", Currency::Fiat("EUR".to_string())).unwrap();
+        assert_eq!(
+            encode_amount(&amount, "fr").unwrap(),
+            "un million d'euros et un centime"
+        );
+        let amount = Amount::parse("21", Currency::Fiat("EUR".to_string())).unwrap();
+        assert_eq!(encode_amount(&amount, "es").unwrap(), "veintiún euros");
+
+        assert_eq!(encode(&1, "xx"), Err(UnsupportedLanguage("xx".to_string())));
+    }
+
+    #[test]
+    fn maturity_types() {
+        let date_of_issue = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+
+        let maturity = Maturity::from_form("after_date", 30).unwrap();
+        assert_eq!(
+            maturity.fixed_due_date(date_of_issue, ""),
+            NaiveDate::from_ymd_opt(2023, 3, 2)
+        );
+        assert_eq!(
+            Maturity::FixedDate.fixed_due_date(date_of_issue, "2023-06-30"),
+            NaiveDate::from_ymd_opt(2023, 6, 30)
+        );
+
+        let maturity = Maturity::from_form("after_sight", 30).unwrap();
+        assert_eq!(maturity.fixed_due_date(date_of_issue, ""), None);
+        assert_eq!(
+            maturity.acceptance_deadline(date_of_issue, ""),
+            NaiveDate::from_ymd_opt(2024, 1, 31)
+        );
+        assert_eq!(
+            Maturity::AtSight.acceptance_deadline(date_of_issue, ""),
+            None
+        );
+
+        assert_eq!(Maturity::from_form("after_sight", 0), None);
+        assert_eq!(Maturity::from_form("", 0), Some(Maturity::FixedDate));
+    }
+
+    #[test]
+    fn hybrid_encryption_reads_legacy_chunks() {
+        let rsa_key = generation_rsa_key();
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+
+        let envelope = encrypt_bytes(&data, &rsa_key);
+        assert!(envelope.starts_with(b"BCE\x00\x01"));
+        assert_eq!(decrypt_bytes(&envelope, &rsa_key).unwrap(), data);
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(encryption::open(&tampered, &rsa_key).is_err());
+
+        let mut legacy = Vec::new();
+        for chunk in data.chunks(rsa_key.size() as usize / 2) {
+            let mut buffer = vec![0; rsa_key.size() as usize];
+            rsa_key
+                .public_encrypt(chunk, &mut buffer, Padding::PKCS1)
+                .unwrap();
+            legacy.append(&mut buffer);
+        }
+        assert_eq!(decrypt_bytes(&legacy, &rsa_key).unwrap(), data);
+    }
+
+    #[test]
+    fn mnemonic_seed_phrase() {
+        let zero = Mnemonic::from_entropy(&[0; 16]).unwrap();
+        assert_eq!(
+            zero.phrase(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert_eq!(
+            hex::encode(zero.to_seed("TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+
+        let legal = Mnemonic::from_phrase(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        assert_eq!(legal.entropy(), &[0x7f; 16]);
+        let zoo = Mnemonic::from_entropy(&[0xff; 32]).unwrap();
+        assert_eq!(zoo.phrase(), "zoo ".repeat(23) + "vote");
+        assert!(Mnemonic::from_phrase(&zoo.phrase().to_uppercase()).is_ok());
+
+        assert_eq!(
+            Mnemonic::from_phrase(&"abandon ".repeat(12)).err(),
+            Some(MnemonicError::Checksum)
+        );
+        assert_eq!(
+            Mnemonic::from_phrase("abandon about").err(),
+            Some(MnemonicError::WordCount(2))
+        );
+
+        let seed = Mnemonic::generate().to_seed("");
+        assert_eq!(
+            seed::ed25519_keypair(&seed).public(),
+            seed::ed25519_keypair(&seed).public()
+        );
+        assert_eq!(
+            seed::bitcoin_private_key(&seed, 0),
+            seed::bitcoin_private_key(&seed, 0)
+        );
+        assert_ne!(
+            seed::bitcoin_private_key(&seed, 0),
+            seed::bitcoin_private_key(&seed, 1)
+        );
+        let rsa = seed::rsa_key(&seed, 0);
+        assert!(rsa.check_key().unwrap());
+        assert_eq!(rsa.size() * 8, 2048);
+        assert_eq!(rsa.n(), seed::rsa_key(&seed, 0).n());
+    }
+
+    #[test]
+    fn key_rotation_signatures() {
+        let peer_key = Keypair::generate_ed25519();
+        let old_identity = identity_with_rsa_key("alice", 4);
+        let mut new_identity = old_identity.clone();
+        new_identity.bitcoin_private_key = bitcoin::PrivateKey::new(
+            bitcoin::secp256k1::SecretKey::from_slice(&[7; 32]).unwrap(),
+            bitcoin::Network::Testnet,
+        )
+        .to_string();
+        new_identity.bitcoin_public_key =
+            bitcoin::PrivateKey::from_wif(&new_identity.bitcoin_private_key)
+                .unwrap()
+                .public_key(&bitcoin::secp256k1::Secp256k1::new())
+                .to_string();
+
+        let rotation = KeyRotation::new(&peer_key, &old_identity, &new_identity, None);
+        assert!(rotation.verify());
+        assert!(!rotation.revoked);
+        let old_key = &old_identity.bitcoin_public_key;
+        assert!(rotation.accepts(old_key, rotation.timestamp - 1));
+        assert!(!rotation.accepts(old_key, rotation.timestamp));
+        assert!(rotation.accepts(&new_identity.bitcoin_public_key, rotation.timestamp + 1));
+
+        let mut forged = rotation.clone();
+        forged.new_bitcoin_public_key = old_identity.bitcoin_public_key.clone();
+        assert!(!forged.verify());
+        let mut other_peer = rotation.clone();
+        other_peer.peer_id = Keypair::generate_ed25519()
+            .public()
+            .to_peer_id()
+            .to_string();
+        assert!(!other_peer.verify());
+
+        let leaked_since = rotation.timestamp - 1000;
+        let revocation =
+            KeyRotation::new(&peer_key, &old_identity, &new_identity, Some(leaked_since));
+        assert!(revocation.verify());
+        assert!(revocation.revoked);
+        assert!(revocation.accepts(&old_identity.public_key_pem, leaked_since - 1));
+        assert!(!revocation.accepts(&old_identity.public_key_pem, leaked_since));
+    }
+
+    #[test]
+    fn key_rotations_belong_to_their_peer() {
+        let with_bitcoin_key = |identity: &Identity, byte: u8| {
+            let private_key = bitcoin::PrivateKey::new(
+                bitcoin::secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap(),
+                bitcoin::Network::Testnet,
+            );
+            let mut identity = identity.clone();
+            identity.bitcoin_public_key = private_key
+                .public_key(&bitcoin::secp256k1::Secp256k1::new())
+                .to_string();
+            identity.bitcoin_private_key = private_key.to_string();
+            identity
+        };
+        let victim_key = Keypair::generate_ed25519();
+        let victim_peer_id = victim_key.public().to_peer_id().to_string();
+        let victim = with_bitcoin_key(&identity_with_rsa_key("alice", 4), 1);
+        let victim_record = IdentityPublicData::new(victim.clone(), victim_peer_id.clone());
+
+        // Another peer names the key of the victim as its old key.
+        let attacker_key = Keypair::generate_ed25519();
+        let attacker = with_bitcoin_key(&victim, 2);
+        let forged = KeyRotation::new(&attacker_key, &victim, &with_bitcoin_key(&victim, 3), None);
+        assert!(with_rotations_of(Vec::new(), vec![forged.clone()], &victim_record).is_empty());
+        let attacker_record =
+            IdentityPublicData::new(attacker, attacker_key.public().to_peer_id().to_string());
+        assert!(with_rotations_of(Vec::new(), vec![forged], &attacker_record).is_empty());
+
+        let first = KeyRotation::new(&victim_key, &victim, &with_bitcoin_key(&victim, 4), None);
+        let rotations = with_rotations_of(Vec::new(), vec![first.clone()], &victim_record);
+        assert_eq!(rotations, vec![first.clone()]);
+        let unlinked = KeyRotation::new(
+            &victim_key,
+            &with_bitcoin_key(&victim, 5),
+            &with_bitcoin_key(&victim, 6),
+            None,
+        );
+        assert_eq!(
+            with_rotations_of(rotations, vec![unlinked], &victim_record),
+            vec![first]
+        );
+    }
+
+    #[test]
+    fn keystore_passphrase() {
+        let data_key = [7; DATA_KEY_LEN];
+        let keystore_file = KeystoreFile::new(&data_key, "correct horse").unwrap();
+        assert_eq!(keystore_file.data_key("correct horse").unwrap(), data_key);
+        assert!(matches!(
+            keystore_file.data_key("battery staple"),
+            Err(KeystoreError::WrongPassphrase)
+        ));
+
+        // The scrypt cost comes after the version and the salt, a huge one is refused.
+        let mut bytes = keystore_file.try_to_vec().unwrap();
+        bytes[1 + 4 + 16] = 64;
+        let keystore_file = KeystoreFile::try_from_slice(&bytes).unwrap();
+        assert!(matches!(
+            keystore_file.data_key("correct horse"),
+            Err(KeystoreError::Malformed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn lightning_preimage_proves_payment() {
+        let node = MockLightningNode::default();
+        let holder_identity = identity_from_seed(&[3; 64], 0);
+        let mut holder = identity_public_data("holder");
+        holder.bitcoin_public_key = holder_identity.bitcoin_public_key.clone();
+        let preimage = invoice_preimage(&holder_identity.bitcoin_private_key, "bill", 0);
+        let payment_hash = sha256(&preimage);
+        let description =
+            invoice_description("bill", &payment_hash, &holder_identity.bitcoin_private_key);
+        let bolt11 = node
+            .create_invoice(21_000, &description, &preimage, 3600)
+            .await
+            .unwrap();
+        assert_eq!(
+            node.invoice_state(&payment_hash).await.unwrap(),
+            InvoiceState::Open
+        );
+
+        assert_eq!(node.pay(&bolt11).await.unwrap(), preimage);
+        assert_eq!(
+            node.invoice_state(&payment_hash).await.unwrap(),
+            InvoiceState::Settled {
+                amount_msat: 21_000
+            }
+        );
+
+        let mut payment = LightningPayment {
+            invoice: bolt11,
+            payment_hash: hex::encode(payment_hash),
+            preimage: hex::encode(preimage),
+        };
+        assert!(payment.is_valid());
+        assert!(payment.is_valid_for("bill", 21, &holder));
+        // The invoice has to be over the amount, for the bill and from its holder.
+        assert!(!payment.is_valid_for("bill", 22, &holder));
+        assert!(!payment.is_valid_for("other bill", 21, &holder));
+        assert!(!payment.is_valid_for("bill", 21, &identity_public_data("drawee")));
+        payment.preimage = hex::encode(invoice_preimage(
+            &holder_identity.bitcoin_private_key,
+            "bill",
+            1,
+        ));
+        assert!(!payment.is_valid());
+        assert!(!payment.is_valid_for("bill", 21, &holder));
+    }
+
+    #[test]
+    fn bolt11_invoices_round_trip() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let (node_key, payee) = secp.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng());
+        let holder_identity = identity_from_seed(&[4; 64], 0);
+        let mut holder = identity_public_data("holder");
+        holder.bitcoin_public_key = holder_identity.bitcoin_public_key.clone();
+        let payment_hash = [9; 32];
+        let invoice = Bolt11Invoice {
+            network: USEDNET,
+            amount_msat: Some(2_500_000),
+            timestamp: 1_700_000_000,
+            payment_hash,
+            description: invoice_description(
+                "bill",
+                &payment_hash,
+                &holder_identity.bitcoin_private_key,
+            ),
+            expiry: 3600,
+            payee,
+        };
+        let bolt11 = invoice.encode(&node_key);
+
+        assert!(bolt11.starts_with("lntb25000000p1"));
+        assert_eq!(Bolt11Invoice::decode(&bolt11).unwrap(), invoice);
+        assert!(check_bill_invoice(&invoice, "bill", &holder).is_ok());
+        assert!(check_bill_invoice(&invoice, "other bill", &holder).is_err());
+        assert!(check_bill_invoice(&invoice, "bill", &identity_public_data("drawee")).is_err());
+        assert!(!invoice.is_expired(1_700_003_600));
+        assert!(invoice.is_expired(1_700_003_601));
+
+        // Signed by another node than the payee it names.
+        let (other_key, _) = secp.generate_keypair(&mut bitcoin::secp256k1::rand::thread_rng());
+        assert_eq!(
+            Bolt11Invoice::decode(&invoice.encode(&other_key)),
+            Err(Bolt11Error::InvalidSignature)
+        );
+        let mut tampered = bolt11.into_bytes();
+        tampered[20] = if tampered[20] == b'q' { b'p' } else { b'q' };
+        assert!(Bolt11Invoice::decode(&String::from_utf8(tampered).unwrap()).is_err());
+    }
+
+    fn api_client() -> rocket::local::blocking::Client {
+        let rocket = rocket::build()
+            .register("/api/v1", catchers![web_api::default_catcher])
+            .mount(
+                "/api/v1",
+                routes![
+                    web_api::get_bill,
+                    web_api::get_bill_chain,
+                    web_api::get_bill_history
+                ],
+            );
+        rocket::local::blocking::Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn api_rejects_bill_ids_that_are_paths() {
+        let client = api_client();
+        for path in [
+            "/api/v1/bills/..%2Fidentity%2Fidentity",
+            "/api/v1/bills/..%2F..%2Fetc%2Fpasswd/chain",
+            "/api/v1/bills/bills%2Fbill/history",
+            "/api/v1/bills/..",
+        ] {
+            let response = client.get(path).dispatch();
+            assert_eq!(response.status(), Status::BadRequest, "{}", path);
+            let error: serde_json::Value = response.into_json().unwrap();
+            assert_eq!(error["message"], "Invalid bill id.");
+        }
+    }
+
+    #[test]
+    fn api_answers_unknown_paths_with_json() {
+        let client = api_client();
+        let response = client.get("/api/v1/identity/seed").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let error: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(error["status"], 404);
+        assert_eq!(
+            error["message"],
+            "We couldn't find the requested path '/api/v1/identity/seed'"
+        );
+    }
 }
@@ -0,0 +1,391 @@
+use std::error::Error;
+use std::path::Path;
+use std::{env, fmt, fs, thread};
+
+use chrono::{NaiveDateTime, Utc};
+use log::warn;
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::cms::{CMSOptions, CmsContentInfo};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rand::rand_bytes;
+use openssl::x509::extension::ExtendedKeyUsage;
+use openssl::x509::store::{X509Store, X509StoreBuilder, X509StoreRef};
+use openssl::x509::{X509Builder, X509NameBuilder, X509PurposeId, X509};
+
+use crate::constants::{TIMESTAMP_AUTHORITIES_FOLDER_PATH, TSA_URL_ENV_VAR};
+use crate::keystore;
+
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+const BOOLEAN: u8 = 0x01;
+/// 2.16.840.1.101.3.4.2.1
+const SHA256_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+/// Value of [`TSA_URL_ENV_VAR`] that timestamps with the [`LocalTimestampAuthority`].
+const LOCAL_AUTHORITY: &str = "local";
+const LOCAL_CERTIFICATE_FILE: &str = "local.pem";
+pub const LOCAL_KEY_FILE: &str = "local.key";
+/// 1.2.3.4.1, the policy of tokens issued by [`LocalTimestampAuthority`].
+const LOCAL_POLICY_OID: &[u8] = &[0x2a, 0x03, 0x04, 0x01];
+
+/// Issues RFC 3161 timestamp tokens.
+pub trait TimestampAuthority {
+    /// Hex of the DER `TimeStampToken` for the SHA-256 digest `hash` in hex.
+    fn timestamp(&self, hash: &str) -> Result<String, TimestampError>;
+}
+
+/// Timestamp authority reached over HTTP as described in RFC 3161.
+pub struct Rfc3161Authority {
+    pub url: String,
+}
+
+impl TimestampAuthority for Rfc3161Authority {
+    fn timestamp(&self, hash: &str) -> Result<String, TimestampError> {
+        let digest = hex::decode(hash).map_err(|_| TimestampError::Malformed)?;
+        let mut nonce = [0u8; 8];
+        rand_bytes(&mut nonce)?;
+        let request = timestamp_request(&digest, u64::from_be_bytes(nonce));
+        let url = self.url.clone();
+
+        // Blocks are created outside of an async context, so the request runs on its own runtime.
+        let response = thread::spawn(move || -> Result<Vec<u8>, TimestampError> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| TimestampError::Request(e.to_string()))?;
+            runtime.block_on(async {
+                let response = reqwest::Client::new()
+                    .post(url)
+                    .header("Content-Type", "application/timestamp-query")
+                    .body(request)
+                    .send()
+                    .await
+                    .map_err(|e| TimestampError::Request(e.to_string()))?;
+                let bytes = response
+                    .bytes()
+                    .await
+                    .map_err(|e| TimestampError::Request(e.to_string()))?;
+                Ok(bytes.to_vec())
+            })
+        })
+        .join()
+        .map_err(|_| TimestampError::Request("request thread panicked".to_string()))??;
+
+        token_from_response(&response).map(hex::encode)
+    }
+}
+
+/// Stand-in authority with a self-signed certificate, for tests and local setups.
+/// Its tokens wrap the `TSTInfo` as plain CMS data instead of `id-ct-TSTInfo`.
+pub struct LocalTimestampAuthority {
+    certificate: X509,
+    key: PKey<Private>,
+}
+
+impl LocalTimestampAuthority {
+    pub fn new() -> Self {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "Local timestamp authority")
+            .unwrap();
+        let name = name.build();
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(365).unwrap())
+            .unwrap();
+        let usage = ExtendedKeyUsage::new()
+            .critical()
+            .time_stamping()
+            .build()
+            .unwrap();
+        builder.append_extension(usage).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        Self {
+            certificate: builder.build(),
+            key,
+        }
+    }
+
+    /// The authority kept in [`TIMESTAMP_AUTHORITIES_FOLDER_PATH`], created on first use.
+    /// Only this node trusts its certificate.
+    pub fn load_or_create() -> Self {
+        let folder = Path::new(TIMESTAMP_AUTHORITIES_FOLDER_PATH);
+        let key_path = folder.join(LOCAL_KEY_FILE).to_string_lossy().to_string();
+        let certificate_path = folder.join(LOCAL_CERTIFICATE_FILE);
+        if Path::new(&key_path).exists() {
+            let key = keystore::read_file(&key_path).expect("Can't read local authority key.");
+            let certificate = fs::read(certificate_path).expect("file not found");
+            return Self {
+                certificate: X509::from_pem(&certificate).unwrap(),
+                key: PKey::private_key_from_pem(&key).unwrap(),
+            };
+        }
+
+        let authority = Self::new();
+        fs::create_dir_all(folder).expect("Can't create folder timestamp_authorities.");
+        fs::write(certificate_path, authority.certificate().to_pem().unwrap()).unwrap();
+        let key = authority.key.private_key_to_pem_pkcs8().unwrap();
+        keystore::write_file(&key_path, &key).expect("Can't write local authority key.");
+        authority
+    }
+
+    pub fn certificate(&self) -> &X509 {
+        &self.certificate
+    }
+}
+
+impl TimestampAuthority for LocalTimestampAuthority {
+    fn timestamp(&self, hash: &str) -> Result<String, TimestampError> {
+        let digest = hex::decode(hash).map_err(|_| TimestampError::Malformed)?;
+        let mut serial = [0u8; 8];
+        rand_bytes(&mut serial)?;
+        let info = tst_info(&digest, u64::from_be_bytes(serial), Utc::now().naive_utc());
+        let token = CmsContentInfo::sign(
+            Some(&self.certificate),
+            Some(&self.key),
+            None,
+            Some(&info),
+            CMSOptions::BINARY,
+        )?;
+        Ok(hex::encode(token.to_der()?))
+    }
+}
+
+#[derive(Debug)]
+pub enum TimestampError {
+    Request(String),
+    Rejected(u8),
+    Malformed,
+    ImprintMismatch,
+    Untrusted(ErrorStack),
+    Crypto(ErrorStack),
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampError::Request(error) => write!(f, "timestamp request failed: {}", error),
+            TimestampError::Rejected(status) => {
+                write!(f, "timestamp authority rejected the request: {}", status)
+            }
+            TimestampError::Malformed => write!(f, "timestamp token is malformed"),
+            TimestampError::ImprintMismatch => write!(f, "timestamp token is for another hash"),
+            TimestampError::Untrusted(error) => {
+                write!(
+                    f,
+                    "timestamp token is not signed by a trusted authority: {}",
+                    error
+                )
+            }
+            TimestampError::Crypto(error) => write!(f, "timestamp token failed: {}", error),
+        }
+    }
+}
+
+impl Error for TimestampError {}
+
+impl From<ErrorStack> for TimestampError {
+    fn from(error: ErrorStack) -> Self {
+        TimestampError::Crypto(error)
+    }
+}
+
+/// Token for `hash` from the authority in [`TSA_URL_ENV_VAR`], if one is configured.
+pub fn request_token(hash: &str) -> Option<String> {
+    let url = env::var(TSA_URL_ENV_VAR).ok()?;
+    let token = if url == LOCAL_AUTHORITY {
+        LocalTimestampAuthority::load_or_create().timestamp(hash)
+    } else {
+        Rfc3161Authority { url }.timestamp(hash)
+    };
+    match token {
+        Ok(token) => Some(token),
+        Err(error) => {
+            warn!("block is not timestamped: {}", error);
+            None
+        }
+    }
+}
+
+/// Time `token` was issued at for `hash`, checked against [`trusted_store`].
+pub fn verify_token(token: &str, hash: &str) -> Result<i64, TimestampError> {
+    let store = trusted_store()?;
+    verify_token_with(token, hash, &store)
+}
+
+pub fn verify_token_with(
+    token: &str,
+    hash: &str,
+    store: &X509StoreRef,
+) -> Result<i64, TimestampError> {
+    let token = hex::decode(token).map_err(|_| TimestampError::Malformed)?;
+    let mut token = CmsContentInfo::from_der(&token).map_err(|_| TimestampError::Malformed)?;
+    let mut info = Vec::new();
+    token
+        .verify(None, Some(store), None, Some(&mut info), CMSOptions::BINARY)
+        .map_err(TimestampError::Untrusted)?;
+    let (imprint, time) = parse_tst_info(&info).ok_or(TimestampError::Malformed)?;
+    if hex::decode(hash).ok() != Some(imprint) {
+        return Err(TimestampError::ImprintMismatch);
+    }
+    Ok(time)
+}
+
+/// System certificates and the authority certificates in [`TIMESTAMP_AUTHORITIES_FOLDER_PATH`].
+pub fn trusted_store() -> Result<X509Store, TimestampError> {
+    let mut certificates = Vec::new();
+    if let Ok(entries) = fs::read_dir(TIMESTAMP_AUTHORITIES_FOLDER_PATH) {
+        for entry in entries.flatten() {
+            if entry.path().extension().is_some_and(|ext| ext == "pem") {
+                let pem = fs::read(entry.path()).expect("file not found");
+                certificates.extend(X509::stack_from_pem(&pem)?);
+            }
+        }
+    } else if Path::new(TIMESTAMP_AUTHORITIES_FOLDER_PATH).exists() {
+        warn!("can not read {}", TIMESTAMP_AUTHORITIES_FOLDER_PATH);
+    }
+    Ok(store_with(certificates)?)
+}
+
+pub fn store_with(certificates: Vec<X509>) -> Result<X509Store, ErrorStack> {
+    let mut store = X509StoreBuilder::new()?;
+    store.set_default_paths()?;
+    for certificate in certificates {
+        store.add_cert(certificate)?;
+    }
+    store.set_purpose(X509PurposeId::TIMESTAMP_SIGN)?;
+    Ok(store.build())
+}
+
+fn timestamp_request(digest: &[u8], nonce: u64) -> Vec<u8> {
+    let mut request = integer(1);
+    request.extend(message_imprint(digest));
+    request.extend(integer(nonce));
+    // certReq, so the token carries the certificate of the authority.
+    request.extend(element(BOOLEAN, &[0xff]));
+    element(SEQUENCE, &request)
+}
+
+fn tst_info(digest: &[u8], serial: u64, time: NaiveDateTime) -> Vec<u8> {
+    let mut info = integer(1);
+    info.extend(element(OBJECT_IDENTIFIER, LOCAL_POLICY_OID));
+    info.extend(message_imprint(digest));
+    info.extend(integer(serial));
+    let time = time.format("%Y%m%d%H%M%SZ").to_string();
+    info.extend(element(GENERALIZED_TIME, time.as_bytes()));
+    element(SEQUENCE, &info)
+}
+
+fn message_imprint(digest: &[u8]) -> Vec<u8> {
+    let mut algorithm = element(OBJECT_IDENTIFIER, SHA256_OID);
+    algorithm.extend(element(NULL, &[]));
+    let mut imprint = element(SEQUENCE, &algorithm);
+    imprint.extend(element(OCTET_STRING, digest));
+    element(SEQUENCE, &imprint)
+}
+
+/// The `TimeStampToken` of a granted `TimeStampResp`.
+fn token_from_response(response: &[u8]) -> Result<Vec<u8>, TimestampError> {
+    let (body, _) = read_element(response, SEQUENCE).ok_or(TimestampError::Malformed)?;
+    let (status_info, token) = read_element(body, SEQUENCE).ok_or(TimestampError::Malformed)?;
+    let (status, _) = read_element(status_info, INTEGER).ok_or(TimestampError::Malformed)?;
+    // 0 is granted, 1 granted with modifications.
+    match status {
+        [0] | [1] => {}
+        [status] => return Err(TimestampError::Rejected(*status)),
+        _ => return Err(TimestampError::Malformed),
+    }
+    let (_, rest) = read_element(token, SEQUENCE).ok_or(TimestampError::Malformed)?;
+    Ok(token[..token.len() - rest.len()].to_vec())
+}
+
+/// Hashed message and generation time of a `TSTInfo`.
+fn parse_tst_info(info: &[u8]) -> Option<(Vec<u8>, i64)> {
+    let (info, _) = read_element(info, SEQUENCE)?;
+    let (_, rest) = read_element(info, INTEGER)?;
+    let (_, rest) = read_element(rest, OBJECT_IDENTIFIER)?;
+    let (imprint, rest) = read_element(rest, SEQUENCE)?;
+    let (_, imprint) = read_element(imprint, SEQUENCE)?;
+    let (hashed_message, _) = read_element(imprint, OCTET_STRING)?;
+    let (_, rest) = read_element(rest, INTEGER)?;
+    let (time, _) = read_element(rest, GENERALIZED_TIME)?;
+    // Fractions of seconds are allowed after the seconds.
+    let time = std::str::from_utf8(time).ok()?.get(..14)?;
+    let time = NaiveDateTime::parse_from_str(time, "%Y%m%d%H%M%S").ok()?;
+    Some((hashed_message.to_vec(), time.timestamp()))
+}
+
+/// Content of the first DER element of `data` if it has `tag`, and what follows it.
+fn read_element(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&first, rest) = data.split_first()?;
+    let (&length, rest) = rest.split_first()?;
+    if first != tag {
+        return None;
+    }
+    let (length, rest) = if length < 0x80 {
+        (length as usize, rest)
+    } else {
+        let count = (length & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |length, &byte| length << 8 | byte as usize);
+        (length, &rest[count..])
+    };
+    if rest.len() < length {
+        return None;
+    }
+    Some(rest.split_at(length))
+}
+
+fn element(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    if content.len() < 0x80 {
+        der.push(content.len() as u8);
+    } else {
+        let length: Vec<u8> = content
+            .len()
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&byte| byte == 0)
+            .collect();
+        der.push(0x80 | length.len() as u8);
+        der.extend(length);
+    }
+    der.extend_from_slice(content);
+    der
+}
+
+fn integer(value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|&byte| byte == 0)
+        .collect();
+    if bytes.first().is_none_or(|&byte| byte & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    element(INTEGER, &bytes)
+}
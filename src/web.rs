@@ -1,26 +1,113 @@
 use bitcoin::secp256k1::Scalar;
+use std::error::Error;
+use std::fmt;
 use std::path::Path;
 use std::str::FromStr;
 
-use chrono::{Days, Utc};
+use chrono::{Days, TimeZone, Utc};
 use rocket::form::Form;
-use rocket::{Request, State};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{Request, Shutdown, State};
 use rocket_dyn_templates::{context, handlebars, Template};
 
+use crate::amount::{Amount, AmountError, Currency};
+use crate::api::BackendError;
 use crate::blockchain::{Chain, GossipsubEvent, GossipsubEventId};
-use crate::constants::{BILLS_FOLDER_PATH, BILL_VALIDITY_PERIOD, IDENTITY_FILE_PATH, USEDNET};
+use crate::constants::{
+    BILLS_FOLDER_PATH, BILL_VALIDITY_PERIOD, IDENTITY_FILE_PATH, MAX_INTEREST_RATE_BASIS_POINTS,
+    USEDNET,
+};
 use crate::dht::network::Client;
+use crate::interest::amount_due;
+use crate::key_rotation;
+use crate::keystore;
+use crate::lightning;
+use crate::maturity::Maturity;
+use crate::mnemonic::Mnemonic;
+use crate::numbers_to_words;
+use crate::payout::{self, Payout};
+use crate::protest::parse_maturity_date;
+use crate::recourse::{has_open_recourse, RecourseClaim};
+use crate::seed;
 use crate::{
-    accept_bill, add_in_contacts_map, api, blockchain, create_whole_identity,
-    endorse_bitcredit_bill, get_bills, get_contact_from_map, get_whole_identity, issue_new_bill,
-    read_bill_from_file, read_contacts_map, read_identity_from_file, read_peer_id_from_file,
-    request_acceptance, request_pay, AcceptBitcreditBillForm, BitcreditBill, BitcreditBillForm,
-    EndorseBitcreditBillForm, Identity, IdentityForm, IdentityPublicData, IdentityWithAll,
-    NewContactForm, RequestToAcceptBitcreditBillForm, RequestToPayBitcreditBillForm,
+    accept_bill, add_in_contacts_map, api, aval_bill, bill_holder, blockchain,
+    can_protest_non_acceptance, can_protest_non_payment, can_request_aval, create_whole_identity,
+    endorse_bitcredit_bill, get_bills, get_contact_from_map, get_recourse_candidates,
+    get_whole_identity, issue_new_bill, protest_non_acceptance, protest_non_payment,
+    read_bill_from_file, read_contacts_map, read_identity_from_file, read_mnemonic_from_file,
+    read_peer_id_from_file, recourse, request_acceptance, request_aval, request_pay,
+    restore_whole_identity, rotate_identity_keys, AcceptBitcreditBillForm, AvalBitcreditBillForm,
+    BitcreditBill, BitcreditBillForm, EndorseBitcreditBillForm, Identity, IdentityForm,
+    IdentityPublicData, IdentityWithAll, InvoiceBitcreditBillForm, NewContactForm, PassphraseForm,
+    PayInvoiceBitcreditBillForm, ProtestBitcreditBillForm, RecourseBitcreditBillForm,
+    RequestToAcceptBitcreditBillForm, RequestToAvalBitcreditBillForm,
+    RequestToPayBitcreditBillForm, RestoreIdentityForm, RotateKeysForm, SweepBitcreditBillForm,
+    UnlockForm,
 };
 
 use self::handlebars::{Handlebars, JsonRender};
 
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RecourseForBill {
+    pub recourse: RecourseClaim,
+    pub payed: bool,
+    /// Whether we took the recourse and can send its payment to our own address.
+    pub can_sweep: bool,
+}
+
+/// Why the balance of a bill can't be told.
+#[derive(Debug)]
+pub enum BalanceError {
+    Request(BackendError),
+    Amount(AmountError),
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalanceError::Request(error) => write!(f, "chain backend failed: {}", error),
+            BalanceError::Amount(error) => write!(f, "amount due: {}", error),
+        }
+    }
+}
+
+impl Error for BalanceError {}
+
+impl From<BackendError> for BalanceError {
+    fn from(error: BackendError) -> Self {
+        BalanceError::Request(error)
+    }
+}
+
+impl From<AmountError> for BalanceError {
+    fn from(error: AmountError) -> Self {
+        BalanceError::Amount(error)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PaymentBalance {
+    pub amount: u64,
+    pub paid: u64,
+    pub paid_on_chain: u64,
+    pub remaining: u64,
+    /// False for fiat bills, which are not paid to the bill address.
+    pub on_chain: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PaymentStep {
+    pub txid: String,
+    pub amount: u64,
+    pub remaining: u64,
+    pub confirmed: bool,
+    pub confirmations: u64,
+    pub block_time: Option<i64>,
+}
+
 #[get("/")]
 pub async fn start() -> Template {
     if !Path::new(IDENTITY_FILE_PATH).exists() {
@@ -44,6 +131,46 @@ pub async fn exit() {
     std::process::exit(0x0100);
 }
 
+#[get("/")]
+pub async fn unlock_page() -> Template {
+    Template::render("hbs/unlock", context! {})
+}
+
+#[post("/unlock", data = "<unlock_form>")]
+pub async fn unlock_keystore(unlock_form: Form<UnlockForm>, shutdown: Shutdown) -> Template {
+    match keystore::unlock(&unlock_form.passphrase) {
+        Ok(()) => {
+            shutdown.notify();
+            Template::render("hbs/unlock", context! { unlocked: true })
+        }
+        Err(error) => Template::render("hbs/unlock", context! { error: error.to_string() }),
+    }
+}
+
+#[get("/")]
+pub async fn passphrase() -> Template {
+    Template::render(
+        "hbs/keystore",
+        context! { protected: keystore::is_protected() },
+    )
+}
+
+#[post("/passphrase", data = "<passphrase_form>")]
+pub async fn change_passphrase(passphrase_form: Form<PassphraseForm>) -> Template {
+    let result = keystore::set_passphrase(
+        &passphrase_form.current_passphrase,
+        &passphrase_form.new_passphrase,
+    );
+    Template::render(
+        "hbs/keystore",
+        context! {
+            protected: keystore::is_protected(),
+            changed: result.is_ok(),
+            error: result.err().map(|error| error.to_string()),
+        },
+    )
+}
+
 #[get("/")]
 pub async fn info() -> Template {
     Template::render("hbs/info", context! {})
@@ -95,6 +222,105 @@ pub async fn create_identity(identity_form: Form<IdentityForm>, state: &State<Cl
     )
 }
 
+#[get("/seed")]
+pub async fn seed_phrase() -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let phrase = read_mnemonic_from_file().map(|mnemonic| mnemonic.phrase());
+        Template::render("hbs/seed", context! { phrase: phrase })
+    }
+}
+
+#[get("/restore")]
+pub async fn restore_identity_page() -> Template {
+    Template::render("hbs/restore_identity", context! {})
+}
+
+/// Rebuilds the identity from its seed phrase and fetches the bills it is part of.
+/// The node keeps its old peer id until it is restarted.
+#[post("/restore", data = "<restore_form>")]
+pub async fn restore_identity(
+    restore_form: Form<RestoreIdentityForm>,
+    state: &State<Client>,
+) -> Template {
+    if Path::new(IDENTITY_FILE_PATH).exists() {
+        return Template::render(
+            "hbs/restore_identity",
+            context! { error: "An identity already exists on this node." },
+        );
+    }
+    let restore_form: RestoreIdentityForm = restore_form.into_inner();
+    let mnemonic = match Mnemonic::from_phrase(&restore_form.mnemonic) {
+        Ok(mnemonic) => mnemonic,
+        Err(error) => {
+            return Template::render(
+                "hbs/restore_identity",
+                context! { error: error.to_string() },
+            )
+        }
+    };
+
+    let mut client = state.inner().clone();
+    let peer_id = seed::ed25519_keypair(&mnemonic.to_seed(""))
+        .public()
+        .to_peer_id();
+    let rotations = client.get_key_rotations_from_dht(peer_id.to_string()).await;
+
+    let identity: IdentityWithAll = restore_whole_identity(&mnemonic, restore_form, rotations);
+    client.check_new_bills(identity.peer_id.to_string()).await;
+    client.put_identity_public_data_in_dht().await;
+
+    Template::render(
+        "hbs/restore_identity",
+        context! {
+            restored: true,
+            peer_id: identity.peer_id.to_string(),
+            bills: get_bills().len(),
+        },
+    )
+}
+
+#[get("/keys")]
+pub async fn identity_keys() -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        key_rotations_page(None)
+    }
+}
+
+#[post("/rotate", data = "<rotate_form>")]
+pub async fn rotate_keys(rotate_form: Form<RotateKeysForm>, state: &State<Client>) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        return Template::render("hbs/create_identity", context! {});
+    }
+    let compromised_since = match rotate_form.compromised_since() {
+        Ok(compromised_since) => compromised_since,
+        Err(error) => return key_rotations_page(Some(error.to_string())),
+    };
+
+    rotate_identity_keys(compromised_since);
+    let mut client = state.inner().clone();
+    client.put_key_rotations_in_dht().await;
+    client.put_identity_public_data_in_dht().await;
+
+    key_rotations_page(None)
+}
+
+fn key_rotations_page(error: Option<String>) -> Template {
+    let identity: IdentityWithAll = get_whole_identity();
+    let rotations = key_rotation::rotations_of(&identity.peer_id.to_string());
+    Template::render(
+        "hbs/key_rotations",
+        context! {
+            identity: identity.identity,
+            rotations: rotations,
+            error: error,
+        },
+    )
+}
+
 #[get("/")]
 pub async fn bills_list() -> Template {
     if !Path::new(IDENTITY_FILE_PATH).exists() {
@@ -121,8 +347,19 @@ pub async fn get_bill_history(id: String) -> Template {
         let history = chain.get_bill_history();
 
         let address_to_pay = get_address_to_pay(bill.clone());
-        let info_about_address =
-            api::AddressInfo::get_testnet_address_info(address_to_pay.clone()).await;
+        let payment_balance = get_bill_payment_balance(&bill, &chain)
+            .await
+            .expect("Failed to get payment balance");
+        let mut payment_steps = Vec::new();
+        if payment_balance.on_chain {
+            payment_steps = get_payment_steps(address_to_pay.clone(), payment_balance.amount)
+                .await
+                .expect("Failed to get payments");
+        }
+        let info_about_address = api::backend()
+            .address_info(&address_to_pay)
+            .await
+            .expect("Failed to get address info");
         let chain_received_summ = info_about_address.chain_stats.funded_txo_sum;
         let chain_spent_summ = info_about_address.chain_stats.spent_txo_sum;
         let chain_summ = chain_received_summ + chain_spent_summ;
@@ -138,6 +375,7 @@ pub async fn get_bill_history(id: String) -> Template {
                 chain_summ: chain_summ,
                 mempool_summ: mempool_summ,
                 address_to_pay: address_to_pay,
+                payment_steps: payment_steps,
             },
         )
     } else {
@@ -233,52 +471,91 @@ pub async fn get_bill(id: String) -> Template {
         let local_peer_id = identity.peer_id.to_string().clone();
         let drawer_from_bill = bill.drawer.clone();
         let drawee_from_bill = bill.drawee.clone();
-        let amount = bill.amount_numbers.clone();
         let payee_public_key = bill.payee.bitcoin_public_key.clone();
         let mut address_to_pay = String::new();
-        let mut pr_key_bill = String::new();
         let mut payed: bool = false;
         let usednet = USEDNET.to_string();
         let mut pending = String::new();
 
         address_to_pay = get_address_to_pay(bill.clone());
-        let check_if_already_paid = check_if_paid(address_to_pay.clone(), amount).await;
-        payed = check_if_already_paid.0;
-        if payed && check_if_already_paid.1.eq(&0) {
-            pending = "Pending".to_string();
+        let payment_balance = get_bill_payment_balance(&bill, &chain)
+            .await
+            .expect("Failed to get payment balance");
+        if !payment_balance.on_chain {
+            address_to_pay = String::new();
         }
-        if !endorsed.clone() && payee_public_key.eq(&identity.identity.bitcoin_public_key)
-        // && !payee.peer_id.eq(&drawee_from_bill.peer_id)
-        {
-            pr_key_bill = get_current_payee_private_key(identity.identity.clone(), bill.clone());
-        } else if endorsed
-            && bill
-                .endorsee
-                .bitcoin_public_key
-                .eq(&identity.identity.bitcoin_public_key)
-        {
-            pr_key_bill = get_current_payee_private_key(identity.identity.clone(), bill.clone());
+        payed = chain.is_paid() || payment_balance.remaining == 0;
+        if payed && payment_balance.paid_on_chain.eq(&0) {
+            pending = "Pending".to_string();
         }
+        let accepted_amount = chain.get_accepted_amount();
+        // The payee or last endorsee can send the payment to an address of their own.
+        let can_sweep = payment_balance.on_chain
+            && ((!endorsed && payee_public_key.eq(&identity.identity.bitcoin_public_key))
+                || (endorsed
+                    && bill
+                        .endorsee
+                        .bitcoin_public_key
+                        .eq(&identity.identity.bitcoin_public_key)));
 
         // if payed {
         //     bill.payee = bill.drawee.clone();
         // }
 
+        let today = Utc::now().naive_local().date();
+        let can_protest_non_acceptance =
+            can_protest_non_acceptance(&bill, &chain, &local_peer_id, today);
+        let can_protest_non_payment =
+            !payed && can_protest_non_payment(&bill, &chain, &local_peer_id, today);
+        let open_recourse = has_open_recourse(&chain);
+        let recourse_candidates =
+            get_recourse_candidates(&bill, &chain, &local_peer_id, open_recourse);
+        let avals = chain.get_avals();
+        let can_request_aval = can_request_aval(&bill, &chain, &local_peer_id);
+        let pending_aval = chain.get_pending_aval_request(&local_peer_id);
+
+        let mut recourses: Vec<RecourseForBill> = Vec::new();
+        let recourse_claims = chain.get_recourses();
+        let recourse_count = recourse_claims.len();
+        for (index, recourse) in recourse_claims.into_iter().enumerate() {
+            // Only the last recourse can be open, the ones before it were paid.
+            let payed_recourse = !open_recourse || index + 1 < recourse_count;
+            let can_sweep = recourse.recourser.peer_id.eq(&local_peer_id);
+            recourses.push(RecourseForBill {
+                recourse,
+                payed: payed_recourse,
+                can_sweep,
+            });
+        }
+
         Template::render(
             "hbs/bill",
             context! {
                 codes: blockchain::OperationCode::get_all_operation_codes(),
                 operation_code: operation_code,
                 peer_id: local_peer_id,
+                maturity: bill.maturity.to_string(),
+                payment_address_type: bill.payment_address_type.code(),
+                lightning: lightning::node().is_ok(),
+                invoices: lightning::read_invoices(&bill.name),
                 bill: Some(bill),
                 identity: Some(identity.identity),
                 accepted: accepted,
                 payed: payed,
                 address_to_pay: address_to_pay,
-                pr_key_bill: pr_key_bill,
+                can_sweep: can_sweep,
                 usednet: usednet,
                 endorsed: endorsed,
                 pending: pending,
+                payment_balance: payment_balance,
+                accepted_amount: accepted_amount,
+                can_protest_non_acceptance: can_protest_non_acceptance,
+                can_protest_non_payment: can_protest_non_payment,
+                recourse_candidates: recourse_candidates,
+                recourses: recourses,
+                avals: avals,
+                can_request_aval: can_request_aval,
+                pending_aval: pending_aval,
             },
         )
     } else {
@@ -297,56 +574,107 @@ pub async fn get_bill(id: String) -> Template {
     }
 }
 
-async fn check_if_paid(address: String, amount: u64) -> (bool, u64) {
-    //todo check what net we used
-    let info_about_address = api::AddressInfo::get_testnet_address_info(address.clone()).await;
-    let received_summ = info_about_address.chain_stats.funded_txo_sum;
-    let spent_summ = info_about_address.chain_stats.spent_txo_sum;
-    let received_summ_mempool = info_about_address.mempool_stats.funded_txo_sum;
-    let spent_summ_mempool = info_about_address.mempool_stats.spent_txo_sum;
-    return if amount.eq(&(received_summ + spent_summ + received_summ_mempool + spent_summ_mempool))
-    {
-        (true, received_summ.clone())
-    } else {
-        (false, 0)
-    };
+/// Running balance of the payments to `address`. Everything that was ever received counts,
+/// also when it was spent later on.
+pub async fn get_payment_balance(
+    address: String,
+    amount: u64,
+) -> Result<PaymentBalance, BackendError> {
+    let info_about_address = api::backend().address_info(&address).await?;
+    let paid_on_chain = info_about_address.chain_stats.funded_txo_sum;
+    let paid = paid_on_chain + info_about_address.mempool_stats.funded_txo_sum;
+
+    Ok(PaymentBalance {
+        amount,
+        paid,
+        paid_on_chain,
+        remaining: amount.saturating_sub(paid),
+        on_chain: true,
+    })
 }
 
-fn get_address_to_pay(bill: BitcreditBill) -> String {
-    let public_key_bill = bitcoin::PublicKey::from_str(&bill.public_key).unwrap();
-
-    let mut person_to_pay = bill.payee.clone();
-
-    if !bill.endorsee.name.is_empty() {
-        person_to_pay = bill.endorsee.clone();
+/// Balance of the payment of `bill` with its interest. A bill paid late owes interest up to
+/// the day the payments covered the amount due, an unpaid one up to today.
+/// Payments of fiat bills are not visible on chain, so they stay unpaid here.
+pub async fn get_bill_payment_balance(
+    bill: &BitcreditBill,
+    chain: &Chain,
+) -> Result<PaymentBalance, BalanceError> {
+    let address_to_pay = get_address_to_pay(bill.clone());
+    let principal = chain.get_amount_to_pay();
+    let today = Utc::now().naive_local().date();
+
+    let mut amount = amount_due(bill, principal, today)?;
+    let paid_on_chain = Amount::from_code(amount, &bill.currency_code)
+        .ok()
+        .and_then(|amount| amount.to_sats())
+        .is_some();
+    if !paid_on_chain {
+        return Ok(PaymentBalance {
+            amount,
+            paid: 0,
+            paid_on_chain: 0,
+            remaining: amount,
+            on_chain: false,
+        });
+    }
+    if bill.compounding_interest_rate > 0 {
+        let mut paid: u64 = 0;
+        for step in get_payment_steps(address_to_pay.clone(), 0).await? {
+            paid += step.amount;
+            let payment_date = step
+                .block_time
+                .and_then(|block_time| Utc.timestamp_opt(block_time, 0).single())
+                .map_or(today, |time| time.naive_utc().date());
+            let amount_due_on_payment = amount_due(bill, principal, payment_date)?;
+            if paid >= amount_due_on_payment {
+                amount = amount_due_on_payment;
+                break;
+            }
+        }
     }
 
-    let public_key_holder = person_to_pay.bitcoin_public_key;
-    let public_key_bill_holder = bitcoin::PublicKey::from_str(&public_key_holder).unwrap();
-
-    let public_key_bill = public_key_bill
-        .inner
-        .combine(&public_key_bill_holder.inner)
-        .unwrap();
-    let pub_key_bill = bitcoin::PublicKey::new(public_key_bill);
-    let address_to_pay = bitcoin::Address::p2pkh(&pub_key_bill, USEDNET).to_string();
-
-    address_to_pay
+    Ok(get_payment_balance(address_to_pay, amount).await?)
 }
 
-fn get_current_payee_private_key(identity: Identity, bill: BitcreditBill) -> String {
-    let private_key_bill = bitcoin::PrivateKey::from_str(&bill.private_key).unwrap();
+/// Every payment to `address` in the order they were made, with the balance left after it.
+pub async fn get_payment_steps(
+    address: String,
+    amount: u64,
+) -> Result<Vec<PaymentStep>, BackendError> {
+    let transactions = api::backend().address_transactions(&address).await?;
+    let tip_height = api::backend().tip_height().await?;
+
+    let mut steps = Vec::new();
+    let mut paid: u64 = 0;
+    for transaction in transactions.iter().rev() {
+        let received = transaction.received_by(&address);
+        if received == 0 {
+            continue;
+        }
+        paid += received;
+        steps.push(PaymentStep {
+            txid: transaction.txid.clone(),
+            amount: received,
+            remaining: amount.saturating_sub(paid),
+            confirmed: transaction.status.confirmed,
+            confirmations: transaction.status.confirmations(tip_height),
+            block_time: transaction.status.block_time,
+        });
+    }
+    Ok(steps)
+}
 
-    let private_key_bill_holder =
-        bitcoin::PrivateKey::from_str(&identity.bitcoin_private_key).unwrap();
+pub fn get_address_to_pay(bill: BitcreditBill) -> String {
+    let public_key_bill = bitcoin::PublicKey::from_str(&bill.public_key).unwrap();
 
-    let privat_key_bill = private_key_bill
-        .inner
-        .add_tweak(&Scalar::from(private_key_bill_holder.inner.clone()))
-        .unwrap();
-    let pr_key_bill = bitcoin::PrivateKey::new(privat_key_bill, USEDNET).to_string();
+    let public_key_holder = &bill_holder(&bill).bitcoin_public_key;
+    let public_key_bill_holder = bitcoin::PublicKey::from_str(public_key_holder).unwrap();
 
-    pr_key_bill
+    let address_type = bill.payment_address_type;
+    let public_key =
+        address_type.aggregate_public_key(&[public_key_bill.inner, public_key_bill_holder.inner]);
+    address_type.address(&public_key).to_string()
 }
 
 #[get("/dht")]
@@ -403,22 +731,40 @@ pub async fn issue_bill(state: &State<Client>, bill_form: Form<BitcreditBillForm
         Template::render("hbs/create_identity", context! {})
     } else {
         let bill = bill_form.into_inner();
+        let amount = match Currency::from_code(&bill.currency_code)
+            .and_then(|currency| Amount::parse(&bill.amount, currency))
+        {
+            Ok(amount) if amount.minor_units() > 0 => amount,
+            _ => return new_bill().await,
+        };
+        if bill.compounding_interest_rate > MAX_INTEREST_RATE_BASIS_POINTS {
+            return new_bill().await;
+        }
+        if numbers_to_words::language(&bill.language).is_err() {
+            return new_bill().await;
+        }
+        let maturity = match Maturity::from_form(&bill.maturity_type, bill.maturity_days) {
+            Some(Maturity::FixedDate) if parse_maturity_date(&bill.maturity_date).is_none() => {
+                return new_bill().await
+            }
+            Some(maturity) => maturity,
+            None => return new_bill().await,
+        };
         let drawer = get_whole_identity();
 
         let mut client = state.inner().clone();
 
-        let public_data_drawee = get_identity_public_data(bill.drawee_name, client.clone()).await;
+        let public_data_drawee =
+            get_identity_public_data(bill.drawee_name.clone(), client.clone()).await;
 
-        let public_data_payee = get_identity_public_data(bill.payee_name, client.clone()).await;
+        let public_data_payee =
+            get_identity_public_data(bill.payee_name.clone(), client.clone()).await;
 
         let bill = issue_new_bill(
-            bill.bill_jurisdiction,
-            bill.place_of_drawing,
-            bill.amount_numbers,
-            bill.place_of_payment,
-            bill.maturity_date,
+            bill,
+            amount,
+            maturity,
             drawer.clone(),
-            bill.language,
             public_data_drawee,
             public_data_payee,
         );
@@ -592,6 +938,295 @@ pub async fn request_to_accept_bill(
     }
 }
 
+#[post("/protest_non_acceptance", data = "<protest_bill_form>")]
+pub async fn protest_non_acceptance_bill(
+    state: &State<Client>,
+    protest_bill_form: Form<ProtestBitcreditBillForm>,
+) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let mut client = state.inner().clone();
+
+        let correct = protest_non_acceptance(&protest_bill_form.bill_name);
+
+        if correct {
+            let chain: Chain = Chain::read_chain_from_file(&protest_bill_form.bill_name);
+            let block = chain.get_latest_block();
+
+            let block_bytes = serde_json::to_vec(block).expect("Error serializing block");
+            let event = GossipsubEvent::new(GossipsubEventId::Block, block_bytes);
+            let message = event.to_byte_array();
+
+            client
+                .add_message_to_topic(message, protest_bill_form.bill_name.clone())
+                .await;
+        }
+
+        let bills = get_bills();
+        let identity: Identity = read_identity_from_file();
+
+        Template::render(
+            "hbs/home",
+            context! {
+                identity: Some(identity),
+                bills: bills,
+            },
+        )
+    }
+}
+
+#[post("/protest_non_payment", data = "<protest_bill_form>")]
+pub async fn protest_non_payment_bill(
+    state: &State<Client>,
+    protest_bill_form: Form<ProtestBitcreditBillForm>,
+) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let mut client = state.inner().clone();
+
+        let bill = read_bill_from_file(&protest_bill_form.bill_name);
+        let chain = Chain::read_chain_from_file(&bill.name);
+        let payment_balance = get_bill_payment_balance(&bill, &chain)
+            .await
+            .expect("Failed to get payment balance");
+
+        let correct = protest_non_payment(&protest_bill_form.bill_name, payment_balance.remaining);
+
+        if correct {
+            let chain: Chain = Chain::read_chain_from_file(&protest_bill_form.bill_name);
+            let block = chain.get_latest_block();
+
+            let block_bytes = serde_json::to_vec(block).expect("Error serializing block");
+            let event = GossipsubEvent::new(GossipsubEventId::Block, block_bytes);
+            let message = event.to_byte_array();
+
+            client
+                .add_message_to_topic(message, protest_bill_form.bill_name.clone())
+                .await;
+        }
+
+        let bills = get_bills();
+        let identity: Identity = read_identity_from_file();
+
+        Template::render(
+            "hbs/home",
+            context! {
+                identity: Some(identity),
+                bills: bills,
+            },
+        )
+    }
+}
+
+#[post("/recourse", data = "<recourse_bill_form>")]
+pub async fn recourse_bill(
+    state: &State<Client>,
+    recourse_bill_form: Form<RecourseBitcreditBillForm>,
+) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let mut client = state.inner().clone();
+
+        let chain = Chain::read_chain_from_file(&recourse_bill_form.bill_name);
+        let open_recourse = has_open_recourse(&chain);
+        let correct = recourse(
+            &recourse_bill_form.bill_name,
+            &recourse_bill_form.recoursee,
+            recourse_bill_form.costs,
+            open_recourse,
+        );
+
+        if correct {
+            let chain: Chain = Chain::read_chain_from_file(&recourse_bill_form.bill_name);
+            let block = chain.get_latest_block();
+
+            let block_bytes = serde_json::to_vec(block).expect("Error serializing block");
+            let event = GossipsubEvent::new(GossipsubEventId::Block, block_bytes);
+            let message = event.to_byte_array();
+
+            client
+                .add_message_to_topic(message, recourse_bill_form.bill_name.clone())
+                .await;
+        }
+
+        let bills = get_bills();
+        let identity: Identity = read_identity_from_file();
+
+        Template::render(
+            "hbs/home",
+            context! {
+                identity: Some(identity),
+                bills: bills,
+            },
+        )
+    }
+}
+
+#[post("/request_to_aval", data = "<request_to_aval_bill_form>")]
+pub async fn request_to_aval_bill(
+    state: &State<Client>,
+    request_to_aval_bill_form: Form<RequestToAvalBitcreditBillForm>,
+) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let mut client = state.inner().clone();
+
+        let public_data_guarantor =
+            get_identity_public_data(request_to_aval_bill_form.guarantor.clone(), client.clone())
+                .await;
+
+        let correct = request_aval(
+            &request_to_aval_bill_form.bill_name,
+            public_data_guarantor.clone(),
+        );
+        if correct {
+            let chain: Chain = Chain::read_chain_from_file(&request_to_aval_bill_form.bill_name);
+            let block = chain.get_latest_block();
+
+            let block_bytes = serde_json::to_vec(block).expect("Error serializing block");
+            let event = GossipsubEvent::new(GossipsubEventId::Block, block_bytes);
+            let message = event.to_byte_array();
+
+            client
+                .add_message_to_topic(message, request_to_aval_bill_form.bill_name.clone())
+                .await;
+
+            client
+                .add_bill_to_dht_for_node(
+                    &request_to_aval_bill_form.bill_name,
+                    &public_data_guarantor.peer_id,
+                )
+                .await;
+        }
+
+        let bills = get_bills();
+        let identity: Identity = read_identity_from_file();
+
+        Template::render(
+            "hbs/home",
+            context! {
+                identity: Some(identity),
+                bills: bills,
+            },
+        )
+    }
+}
+
+#[post("/sweep", data = "<sweep_bill_form>")]
+pub async fn sweep_bill(sweep_bill_form: Form<SweepBitcreditBillForm>) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let payout: Result<Payout, String> = if sweep_bill_form.recoursee.is_empty() {
+            payout::sweep_bill(&sweep_bill_form.bill_name, &sweep_bill_form.destination).await
+        } else {
+            payout::sweep_recourse(
+                &sweep_bill_form.bill_name,
+                &sweep_bill_form.recoursee,
+                &sweep_bill_form.destination,
+            )
+            .await
+        }
+        .map_err(|error| error.to_string());
+
+        Template::render(
+            "hbs/payout",
+            context! {
+                bill_name: sweep_bill_form.bill_name.clone(),
+                payout: payout.as_ref().ok(),
+                error: payout.as_ref().err(),
+            },
+        )
+    }
+}
+
+#[post("/invoice", data = "<invoice_bill_form>")]
+pub async fn create_bill_invoice(invoice_bill_form: Form<InvoiceBitcreditBillForm>) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let invoice = lightning::create_bill_invoice(&invoice_bill_form.bill_name)
+            .await
+            .map_err(|error| error.to_string());
+
+        Template::render(
+            "hbs/lightning",
+            context! {
+                bill_name: invoice_bill_form.bill_name.clone(),
+                invoice: invoice.as_ref().ok(),
+                error: invoice.as_ref().err(),
+            },
+        )
+    }
+}
+
+#[post("/pay_invoice", data = "<pay_invoice_bill_form>")]
+pub async fn pay_bill_invoice(
+    pay_invoice_bill_form: Form<PayInvoiceBitcreditBillForm>,
+) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let preimage = lightning::pay_bill_invoice(
+            &pay_invoice_bill_form.bill_name,
+            &pay_invoice_bill_form.invoice,
+        )
+        .await
+        .map_err(|error| error.to_string());
+
+        Template::render(
+            "hbs/lightning",
+            context! {
+                bill_name: pay_invoice_bill_form.bill_name.clone(),
+                preimage: preimage.as_ref().ok(),
+                error: preimage.as_ref().err(),
+            },
+        )
+    }
+}
+
+#[post("/aval", data = "<aval_bill_form>")]
+pub async fn aval_bill_form(
+    state: &State<Client>,
+    aval_bill_form: Form<AvalBitcreditBillForm>,
+) -> Template {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        Template::render("hbs/create_identity", context! {})
+    } else {
+        let mut client = state.inner().clone();
+
+        let correct = aval_bill(&aval_bill_form.bill_name);
+
+        if correct {
+            let chain: Chain = Chain::read_chain_from_file(&aval_bill_form.bill_name);
+            let block = chain.get_latest_block();
+
+            let block_bytes = serde_json::to_vec(block).expect("Error serializing block");
+            let event = GossipsubEvent::new(GossipsubEventId::Block, block_bytes);
+            let message = event.to_byte_array();
+
+            client
+                .add_message_to_topic(message, aval_bill_form.bill_name.clone())
+                .await;
+        }
+
+        let bills = get_bills();
+        let identity: Identity = read_identity_from_file();
+
+        Template::render(
+            "hbs/home",
+            context! {
+                identity: Some(identity),
+                bills: bills,
+            },
+        )
+    }
+}
+
 #[post("/accept", data = "<accept_bill_form>")]
 pub async fn accept_bill_form(
     state: &State<Client>,
@@ -602,7 +1237,14 @@ pub async fn accept_bill_form(
     } else {
         let mut client = state.inner().clone();
 
-        let correct = accept_bill(&accept_bill_form.bill_name);
+        let bill = read_bill_from_file(&accept_bill_form.bill_name);
+        let amount = Currency::from_code(&bill.currency_code)
+            .and_then(|currency| Amount::parse(&accept_bill_form.amount, currency));
+
+        let correct = match amount {
+            Ok(amount) => accept_bill(&accept_bill_form.bill_name, amount.minor_units()),
+            Err(_) => false,
+        };
 
         if correct {
             let chain: Chain = Chain::read_chain_from_file(&accept_bill_form.bill_name);
@@ -682,6 +1324,7 @@ pub fn not_found(req: &Request) -> String {
 
 pub fn customize(hbs: &mut Handlebars) {
     hbs.register_helper("wow", Box::new(wow_helper));
+    hbs.register_helper("amount", Box::new(amount_helper));
     hbs.register_template_string(
         "hbs/about.html",
         r#"
@@ -696,6 +1339,23 @@ pub fn customize(hbs: &mut Handlebars) {
     .expect("valid HBS template");
 }
 
+/// `{{amount bill.amount_numbers bill.currency_code}}` writes the amount in its currency.
+fn amount_helper(
+    h: &handlebars::Helper<'_, '_>,
+    _: &handlebars::Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext<'_, '_>,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    let minor_units = h.param(0).and_then(|param| param.value().as_u64());
+    let currency_code = h.param(1).and_then(|param| param.value().as_str());
+    if let (Some(minor_units), Some(currency_code)) = (minor_units, currency_code) {
+        out.write(&blockchain::display_amount(minor_units, currency_code))?;
+    }
+
+    Ok(())
+}
+
 fn wow_helper(
     h: &handlebars::Helper<'_, '_>,
     _: &handlebars::Handlebars,
@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{Request, State};
+
+use crate::amount::{Amount, Currency};
+use crate::anchor::{self, Anchor, AnchorError, AnchorVerification};
+use crate::api::BackendError;
+use crate::blockchain::{
+    Block, BlockForHistory, Chain, Fork, GossipsubEvent, GossipsubEventId, Guarantee,
+};
+use crate::constants::{BILLS_FOLDER_PATH, IDENTITY_FILE_PATH, MAX_INTEREST_RATE_BASIS_POINTS};
+use crate::dht::network::Client;
+use crate::key_rotation::{self, KeyRotation};
+use crate::keystore::{self, KeystoreError};
+use crate::lightning::{self, LightningError, LightningInvoice};
+use crate::maturity::Maturity;
+use crate::mnemonic::Mnemonic;
+use crate::numbers_to_words;
+use crate::payout::{self, Payout, PayoutError};
+use crate::protest::{parse_maturity_date, ProtestDocument};
+use crate::recourse::{has_open_recourse, RecourseClaim};
+use crate::seed;
+use crate::web::{
+    get_address_to_pay, get_bill_payment_balance, get_payment_steps, BalanceError, PaymentBalance,
+    PaymentStep,
+};
+use crate::{
+    accept_bill, add_in_contacts_map, aval_bill, create_whole_identity, endorse_bitcredit_bill,
+    get_bills, get_contact_from_map, get_recourse_candidates, get_whole_identity, issue_new_bill,
+    protest_non_acceptance, protest_non_payment, read_bill_from_file, read_contacts_map,
+    read_peer_id_from_file, recourse, request_acceptance, request_aval, request_pay,
+    restore_whole_identity, rotate_identity_keys, BitcreditBill, BitcreditBillForm, IdentityForm,
+    IdentityPublicData, NewContactForm, PassphraseForm, RestoreIdentityForm, RotateKeysForm,
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+pub type ApiResult<T> = Result<Json<T>, (Status, Json<ApiError>)>;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AnchorTransaction {
+    pub txid: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct EndorseBillRequest {
+    pub endorsee: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AcceptBillRequest {
+    /// Accepted amount as a decimal in the bill currency, the whole bill amount when left out.
+    pub amount: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct BillPayments {
+    pub balance: PaymentBalance,
+    pub steps: Vec<PaymentStep>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RequestToAvalBillRequest {
+    pub guarantor: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct BillProtest {
+    #[serde(flatten)]
+    pub protest: ProtestDocument,
+    /// The protest as a sentence, as it is shown in the bill history.
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RecourseBillRequest {
+    /// Peer id of the drawer or prior endorser to claim from.
+    pub recoursee: String,
+    pub costs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct SweepBillRequest {
+    /// Address the payment is sent to.
+    pub destination: String,
+    /// Peer id of the recoursee, to sweep the payment of our recourse instead of the bill.
+    pub recoursee: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PayInvoiceRequest {
+    /// BOLT11 invoice the holder issued for the bill.
+    pub invoice: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct InvoicePayment {
+    /// Hex encoded preimage, the receipt of the payment.
+    pub preimage: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct BillRecourses {
+    /// Parties we can still take recourse against.
+    pub candidates: Vec<IdentityPublicData>,
+    pub recourses: Vec<RecourseClaim>,
+}
+
+fn api_error(status: Status, message: &str) -> (Status, Json<ApiError>) {
+    (
+        status,
+        Json(ApiError {
+            status: status.code,
+            message: message.to_string(),
+        }),
+    )
+}
+
+fn anchor_error(error: AnchorError) -> (Status, Json<ApiError>) {
+    let status = match error {
+        AnchorError::NothingToAnchor => Status::Conflict,
+        AnchorError::NotAnchored => Status::NotFound,
+        AnchorError::Request(_) => Status::BadGateway,
+        _ => Status::UnprocessableEntity,
+    };
+    api_error(status, &error.to_string())
+}
+
+fn backend_error(error: BackendError) -> (Status, Json<ApiError>) {
+    api_error(Status::BadGateway, &error.to_string())
+}
+
+fn balance_error(error: BalanceError) -> (Status, Json<ApiError>) {
+    match error {
+        BalanceError::Request(error) => backend_error(error),
+        BalanceError::Amount(error) => api_error(Status::UnprocessableEntity, &error.to_string()),
+    }
+}
+
+fn payout_error(error: PayoutError) -> (Status, Json<ApiError>) {
+    let status = match error {
+        PayoutError::NotEntitled => Status::Forbidden,
+        PayoutError::NothingToSweep(_) => Status::Conflict,
+        PayoutError::Request(_) => Status::BadGateway,
+        _ => Status::UnprocessableEntity,
+    };
+    api_error(status, &error.to_string())
+}
+
+fn lightning_error(error: LightningError) -> (Status, Json<ApiError>) {
+    let status = match error {
+        LightningError::Disabled => Status::NotImplemented,
+        LightningError::NotEntitled => Status::Forbidden,
+        LightningError::AlreadyPaid => Status::Conflict,
+        LightningError::NotPayable | LightningError::InvalidInvoice(_) => {
+            Status::UnprocessableEntity
+        }
+        LightningError::Misconfigured(_) => Status::ServiceUnavailable,
+        _ => Status::BadGateway,
+    };
+    api_error(status, &error.to_string())
+}
+
+fn require_identity() -> Result<(), (Status, Json<ApiError>)> {
+    if Path::new(IDENTITY_FILE_PATH).exists() {
+        Ok(())
+    } else {
+        Err(api_error(Status::Conflict, "Identity is not created yet."))
+    }
+}
+
+/// Whether `id` names a file in the bills folder rather than a path leading out of it.
+fn is_plain_file_name(id: &str) -> bool {
+    Path::new(id).file_name().and_then(|name| name.to_str()) == Some(id)
+}
+
+fn require_bill(id: &str) -> Result<(), (Status, Json<ApiError>)> {
+    if !is_plain_file_name(id) {
+        return Err(api_error(Status::BadRequest, "Invalid bill id."));
+    }
+    require_identity()?;
+    if Path::new((BILLS_FOLDER_PATH.to_string() + "/" + id + ".json").as_str()).exists() {
+        Ok(())
+    } else {
+        Err(api_error(Status::NotFound, "Bill not found."))
+    }
+}
+
+async fn get_identity_public_data(
+    contact_name: &String,
+    client: &mut Client,
+) -> Result<IdentityPublicData, (Status, Json<ApiError>)> {
+    let peer_id = get_contact_from_map(contact_name);
+    if peer_id.is_empty() {
+        return Err(api_error(
+            Status::UnprocessableEntity,
+            &format!("Unknown contact {}.", contact_name),
+        ));
+    }
+    Ok(client.get_identity_public_data_from_dht(peer_id).await)
+}
+
+pub async fn publish_latest_block(client: &mut Client, bill_name: &String) -> Block {
+    let chain: Chain = Chain::read_chain_from_file(bill_name);
+    let block = chain.get_latest_block().clone();
+
+    let block_bytes = serde_json::to_vec(&block).expect("Error serializing block");
+    let event = GossipsubEvent::new(GossipsubEventId::Block, block_bytes);
+    let message = event.to_byte_array();
+
+    client
+        .add_message_to_topic(message, bill_name.clone())
+        .await;
+
+    block
+}
+
+//-------------------------Identity-----------------------------
+#[get("/identity")]
+pub async fn get_identity() -> ApiResult<IdentityPublicData> {
+    if !Path::new(IDENTITY_FILE_PATH).exists() {
+        return Err(api_error(Status::NotFound, "Identity is not created yet."));
+    }
+    let identity = get_whole_identity();
+    Ok(Json(IdentityPublicData::new(
+        identity.identity,
+        identity.peer_id.to_string(),
+    )))
+}
+
+#[post("/identity", format = "json", data = "<identity_form>")]
+pub async fn create_identity(
+    state: &State<Client>,
+    identity_form: Json<IdentityForm>,
+) -> ApiResult<IdentityPublicData> {
+    if Path::new(IDENTITY_FILE_PATH).exists() {
+        return Err(api_error(Status::Conflict, "Identity already exists."));
+    }
+    let identity_form: IdentityForm = identity_form.into_inner();
+    let identity = create_whole_identity(
+        identity_form.name,
+        identity_form.date_of_birth,
+        identity_form.city_of_birth,
+        identity_form.country_of_birth,
+        identity_form.email,
+        identity_form.postal_address,
+    );
+
+    let mut client = state.inner().clone();
+    client.put_identity_public_data_in_dht().await;
+
+    Ok(Json(IdentityPublicData::new(
+        identity.identity,
+        identity.peer_id.to_string(),
+    )))
+}
+
+/// Rebuilds the identity from its seed phrase and fetches the bills it is part of.
+/// The node keeps its old peer id until it is restarted.
+#[post("/identity/restore", format = "json", data = "<restore_form>")]
+pub async fn restore_identity(
+    state: &State<Client>,
+    restore_form: Json<RestoreIdentityForm>,
+) -> ApiResult<IdentityPublicData> {
+    if Path::new(IDENTITY_FILE_PATH).exists() {
+        return Err(api_error(Status::Conflict, "Identity already exists."));
+    }
+    let restore_form: RestoreIdentityForm = restore_form.into_inner();
+    let mnemonic = Mnemonic::from_phrase(&restore_form.mnemonic)
+        .map_err(|error| api_error(Status::UnprocessableEntity, &error.to_string()))?;
+
+    let mut client = state.inner().clone();
+    let peer_id = seed::ed25519_keypair(&mnemonic.to_seed(""))
+        .public()
+        .to_peer_id();
+    let rotations = client.get_key_rotations_from_dht(peer_id.to_string()).await;
+
+    let identity = restore_whole_identity(&mnemonic, restore_form, rotations);
+    client.check_new_bills(identity.peer_id.to_string()).await;
+    client.put_identity_public_data_in_dht().await;
+
+    Ok(Json(IdentityPublicData::new(
+        identity.identity,
+        identity.peer_id.to_string(),
+    )))
+}
+
+#[get("/identity/rotations")]
+pub async fn get_key_rotations() -> ApiResult<Vec<KeyRotation>> {
+    require_identity()?;
+    let peer_id = read_peer_id_from_file().to_string();
+    Ok(Json(key_rotation::rotations_of(&peer_id)))
+}
+
+/// Replaces the RSA and bitcoin keys of the identity and publishes the rotation.
+#[post("/identity/rotate", format = "json", data = "<rotate_form>")]
+pub async fn rotate_keys(
+    state: &State<Client>,
+    rotate_form: Json<RotateKeysForm>,
+) -> ApiResult<KeyRotation> {
+    require_identity()?;
+    let compromised_since = rotate_form
+        .compromised_since()
+        .map_err(|error| api_error(Status::UnprocessableEntity, &error.to_string()))?;
+
+    let rotation = rotate_identity_keys(compromised_since);
+    let mut client = state.inner().clone();
+    client.put_key_rotations_in_dht().await;
+    client.put_identity_public_data_in_dht().await;
+
+    Ok(Json(rotation))
+}
+//--------------------------------------------------------------
+
+//-------------------------Contacts-----------------------------
+#[get("/contacts")]
+pub async fn contacts() -> ApiResult<HashMap<String, String>> {
+    require_identity()?;
+    Ok(Json(read_contacts_map()))
+}
+
+#[post("/contacts", format = "json", data = "<new_contact_form>")]
+pub async fn new_contact(
+    new_contact_form: Json<NewContactForm>,
+) -> ApiResult<HashMap<String, String>> {
+    require_identity()?;
+    if new_contact_form.name.is_empty() || new_contact_form.node_id.is_empty() {
+        return Err(api_error(
+            Status::UnprocessableEntity,
+            "Contact name and node id are required.",
+        ));
+    }
+    add_in_contacts_map(
+        new_contact_form.name.clone(),
+        new_contact_form.node_id.clone(),
+    );
+    Ok(Json(read_contacts_map()))
+}
+//--------------------------------------------------------------
+
+//-------------------------Bills--------------------------------
+#[get("/bills")]
+pub async fn bills_list() -> ApiResult<Vec<BitcreditBill>> {
+    require_identity()?;
+    Ok(Json(get_bills()))
+}
+
+#[get("/bills/<id>")]
+pub async fn get_bill(id: String) -> ApiResult<BitcreditBill> {
+    require_bill(&id)?;
+    Ok(Json(read_bill_from_file(&id)))
+}
+
+#[get("/bills/<id>/chain")]
+pub async fn get_bill_chain(id: String) -> ApiResult<Chain> {
+    require_bill(&id)?;
+    Ok(Json(Chain::read_chain_from_file(&id)))
+}
+
+#[get("/bills/<id>/chain/<block_id>")]
+pub async fn get_block(id: String, block_id: u64) -> ApiResult<Block> {
+    require_bill(&id)?;
+    let chain = Chain::read_chain_from_file(&id);
+    match chain.blocks.iter().find(|block| block.id == block_id) {
+        Some(block) => Ok(Json(block.clone())),
+        None => Err(api_error(Status::NotFound, "Block not found.")),
+    }
+}
+
+#[get("/bills/<id>/forks")]
+pub async fn get_bill_forks(id: String) -> ApiResult<Vec<Fork>> {
+    require_bill(&id)?;
+    let chain = Chain::read_chain_from_file(&id);
+    Ok(Json(chain.forks))
+}
+
+#[get("/bills/<id>/anchors")]
+pub async fn get_bill_anchors(id: String) -> ApiResult<Vec<Anchor>> {
+    require_bill(&id)?;
+    Ok(Json(anchor::read_anchors(&id)))
+}
+
+#[get("/bills/<id>/chain/<block_id>/anchor")]
+pub async fn verify_block_anchor(id: String, block_id: u64) -> ApiResult<AnchorVerification> {
+    require_bill(&id)?;
+    anchor::verify_anchor(&id, block_id)
+        .await
+        .map(Json)
+        .map_err(anchor_error)
+}
+
+#[post("/anchors")]
+pub async fn anchor_chain_heads() -> ApiResult<AnchorTransaction> {
+    require_identity()?;
+    let txid = anchor::anchor_chain_heads().await.map_err(anchor_error)?;
+    Ok(Json(AnchorTransaction { txid }))
+}
+
+#[get("/bills/<id>/history")]
+pub async fn get_bill_history(id: String) -> ApiResult<Vec<BlockForHistory>> {
+    require_bill(&id)?;
+    let chain = Chain::read_chain_from_file(&id);
+    Ok(Json(chain.get_bill_history()))
+}
+
+#[post("/bills", format = "json", data = "<bill_form>")]
+pub async fn issue_bill(
+    state: &State<Client>,
+    bill_form: Json<BitcreditBillForm>,
+) -> Result<(Status, Json<BitcreditBill>), (Status, Json<ApiError>)> {
+    require_identity()?;
+    let bill_form = bill_form.into_inner();
+    let amount = Currency::from_code(&bill_form.currency_code)
+        .and_then(|currency| Amount::parse(&bill_form.amount, currency))
+        .map_err(|error| api_error(Status::UnprocessableEntity, &error.to_string()))?;
+    if amount.minor_units() == 0 {
+        return Err(api_error(
+            Status::UnprocessableEntity,
+            "Amount must be greater than zero.",
+        ));
+    }
+    if bill_form.compounding_interest_rate > MAX_INTEREST_RATE_BASIS_POINTS {
+        return Err(api_error(
+            Status::UnprocessableEntity,
+            &format!(
+                "Interest rate must be at most {} basis points.",
+                MAX_INTEREST_RATE_BASIS_POINTS
+            ),
+        ));
+    }
+    numbers_to_words::language(&bill_form.language)
+        .map_err(|error| api_error(Status::UnprocessableEntity, &error.to_string()))?;
+    let maturity = match Maturity::from_form(&bill_form.maturity_type, bill_form.maturity_days) {
+        Some(Maturity::FixedDate) if parse_maturity_date(&bill_form.maturity_date).is_none() => {
+            return Err(api_error(
+                Status::UnprocessableEntity,
+                "Maturity date must be a date like 2024-12-31.",
+            ))
+        }
+        Some(maturity) => maturity,
+        None => return Err(api_error(
+            Status::UnprocessableEntity,
+            "Maturity type must be date, sight, after_sight or after_date with a number of days.",
+        )),
+    };
+
+    let drawer = get_whole_identity();
+    let mut client = state.inner().clone();
+
+    let public_data_drawee = get_identity_public_data(&bill_form.drawee_name, &mut client).await?;
+    let public_data_payee = get_identity_public_data(&bill_form.payee_name, &mut client).await?;
+
+    let bill = issue_new_bill(
+        bill_form,
+        amount,
+        maturity,
+        drawer.clone(),
+        public_data_drawee,
+        public_data_payee,
+    );
+
+    let nodes: Vec<String> = vec![
+        drawer.peer_id.to_string(),
+        bill.drawee.peer_id.clone(),
+        bill.payee.peer_id.clone(),
+    ];
+    for node in nodes {
+        if !node.is_empty() {
+            client.add_bill_to_dht_for_node(&bill.name, &node).await;
+        }
+    }
+
+    client.subscribe_to_topic(bill.name.clone()).await;
+    client.put(&bill.name).await;
+
+    Ok((Status::Created, Json(bill)))
+}
+
+#[post("/bills/<id>/endorse", format = "json", data = "<endorse_request>")]
+pub async fn endorse_bill(
+    state: &State<Client>,
+    id: String,
+    endorse_request: Json<EndorseBillRequest>,
+) -> ApiResult<Block> {
+    require_bill(&id)?;
+    let mut client = state.inner().clone();
+
+    let public_data_endorsee =
+        get_identity_public_data(&endorse_request.endorsee, &mut client).await?;
+
+    if !endorse_bitcredit_bill(&id, public_data_endorsee.clone()) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to endorse this bill.",
+        ));
+    }
+
+    let block = publish_latest_block(&mut client, &id).await;
+    client
+        .add_bill_to_dht_for_node(&id, &public_data_endorsee.peer_id)
+        .await;
+
+    Ok(Json(block))
+}
+
+#[post("/bills/<id>/request_to_accept")]
+pub async fn request_to_accept_bill(state: &State<Client>, id: String) -> ApiResult<Block> {
+    require_bill(&id)?;
+    if !request_acceptance(&id) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to request acceptance of this bill.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[post("/bills/<id>/request_to_pay")]
+pub async fn request_to_pay_bill(state: &State<Client>, id: String) -> ApiResult<Block> {
+    require_bill(&id)?;
+    if !request_pay(&id) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to request payment of this bill.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[post("/bills/<id>/accept", data = "<accept_request>")]
+pub async fn accept_bill_request(
+    state: &State<Client>,
+    id: String,
+    accept_request: Option<Json<AcceptBillRequest>>,
+) -> ApiResult<Block> {
+    require_bill(&id)?;
+    let bill = read_bill_from_file(&id);
+    let amount = match accept_request.and_then(|request| request.into_inner().amount) {
+        Some(amount) => Currency::from_code(&bill.currency_code)
+            .and_then(|currency| Amount::parse(&amount, currency))
+            .map_err(|error| api_error(Status::UnprocessableEntity, &error.to_string()))?
+            .minor_units(),
+        None => bill.amount_numbers,
+    };
+    if !accept_bill(&id, amount) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to accept this bill.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[post("/bills/<id>/protest_non_acceptance")]
+pub async fn protest_non_acceptance_bill(state: &State<Client>, id: String) -> ApiResult<Block> {
+    require_bill(&id)?;
+    if !protest_non_acceptance(&id) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to protest non-acceptance of this bill.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[post("/bills/<id>/protest_non_payment")]
+pub async fn protest_non_payment_bill(state: &State<Client>, id: String) -> ApiResult<Block> {
+    require_bill(&id)?;
+    let bill = read_bill_from_file(&id);
+    let chain = Chain::read_chain_from_file(&id);
+    let payment_balance = get_bill_payment_balance(&bill, &chain)
+        .await
+        .map_err(balance_error)?;
+    if payment_balance.remaining == 0 {
+        return Err(api_error(Status::Conflict, "Bill is already paid."));
+    }
+    if !protest_non_payment(&id, payment_balance.remaining) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to protest non-payment of this bill.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[get("/bills/<id>/payments")]
+pub async fn get_bill_payments(id: String) -> ApiResult<BillPayments> {
+    require_bill(&id)?;
+    let bill = read_bill_from_file(&id);
+    let chain = Chain::read_chain_from_file(&id);
+    let balance = get_bill_payment_balance(&bill, &chain)
+        .await
+        .map_err(balance_error)?;
+    let mut steps = Vec::new();
+    if balance.on_chain {
+        steps = get_payment_steps(get_address_to_pay(bill), balance.amount)
+            .await
+            .map_err(backend_error)?;
+    }
+    Ok(Json(BillPayments { balance, steps }))
+}
+
+#[get("/bills/<id>/protests")]
+pub async fn get_bill_protests(id: String) -> ApiResult<Vec<BillProtest>> {
+    require_bill(&id)?;
+    let chain = Chain::read_chain_from_file(&id);
+    let protests = chain
+        .get_protests()
+        .into_iter()
+        .map(|protest| BillProtest {
+            text: protest.text(),
+            protest,
+        })
+        .collect();
+    Ok(Json(protests))
+}
+
+#[post("/bills/<id>/recourse", format = "json", data = "<recourse_request>")]
+pub async fn recourse_bill(
+    state: &State<Client>,
+    id: String,
+    recourse_request: Json<RecourseBillRequest>,
+) -> ApiResult<Block> {
+    require_bill(&id)?;
+    let chain = Chain::read_chain_from_file(&id);
+    let open_recourse = has_open_recourse(&chain);
+    if !recourse(
+        &id,
+        &recourse_request.recoursee,
+        recourse_request.costs,
+        open_recourse,
+    ) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to take recourse against this party.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[post("/bills/<id>/sweep", format = "json", data = "<sweep_request>")]
+pub async fn sweep_bill(id: String, sweep_request: Json<SweepBillRequest>) -> ApiResult<Payout> {
+    require_bill(&id)?;
+    let payout = match &sweep_request.recoursee {
+        Some(recoursee) => payout::sweep_recourse(&id, recoursee, &sweep_request.destination).await,
+        None => payout::sweep_bill(&id, &sweep_request.destination).await,
+    };
+    payout.map(Json).map_err(payout_error)
+}
+
+#[post("/bills/<id>/invoices")]
+pub async fn create_bill_invoice(
+    id: String,
+) -> Result<(Status, Json<LightningInvoice>), (Status, Json<ApiError>)> {
+    require_bill(&id)?;
+    let invoice = lightning::create_bill_invoice(&id)
+        .await
+        .map_err(lightning_error)?;
+    Ok((Status::Created, Json(invoice)))
+}
+
+#[get("/bills/<id>/invoices")]
+pub async fn get_bill_invoices(id: String) -> ApiResult<Vec<LightningInvoice>> {
+    require_bill(&id)?;
+    Ok(Json(lightning::read_invoices(&id)))
+}
+
+#[post("/bills/<id>/pay_invoice", format = "json", data = "<pay_request>")]
+pub async fn pay_bill_invoice(
+    id: String,
+    pay_request: Json<PayInvoiceRequest>,
+) -> ApiResult<InvoicePayment> {
+    require_bill(&id)?;
+    let preimage = lightning::pay_bill_invoice(&id, &pay_request.invoice)
+        .await
+        .map_err(lightning_error)?;
+    Ok(Json(InvoicePayment { preimage }))
+}
+
+#[get("/bills/<id>/recourse")]
+pub async fn get_bill_recourses(id: String) -> ApiResult<BillRecourses> {
+    require_bill(&id)?;
+    let bill = read_bill_from_file(&id);
+    let chain = Chain::read_chain_from_file(&id);
+    let peer_id = read_peer_id_from_file().to_string();
+    let open_recourse = has_open_recourse(&chain);
+    Ok(Json(BillRecourses {
+        candidates: get_recourse_candidates(&bill, &chain, &peer_id, open_recourse),
+        recourses: chain.get_recourses(),
+    }))
+}
+
+#[post(
+    "/bills/<id>/request_to_aval",
+    format = "json",
+    data = "<request_to_aval_request>"
+)]
+pub async fn request_to_aval_bill(
+    state: &State<Client>,
+    id: String,
+    request_to_aval_request: Json<RequestToAvalBillRequest>,
+) -> ApiResult<Block> {
+    require_bill(&id)?;
+    let mut client = state.inner().clone();
+
+    let public_data_guarantor =
+        get_identity_public_data(&request_to_aval_request.guarantor, &mut client).await?;
+
+    if !request_aval(&id, public_data_guarantor.clone()) {
+        return Err(api_error(
+            Status::Forbidden,
+            "You are not allowed to request an aval for this bill.",
+        ));
+    }
+
+    let block = publish_latest_block(&mut client, &id).await;
+    client
+        .add_bill_to_dht_for_node(&id, &public_data_guarantor.peer_id)
+        .await;
+
+    Ok(Json(block))
+}
+
+#[post("/bills/<id>/aval")]
+pub async fn aval_bill_request(state: &State<Client>, id: String) -> ApiResult<Block> {
+    require_bill(&id)?;
+    if !aval_bill(&id) {
+        return Err(api_error(
+            Status::Forbidden,
+            "Nobody requested an aval for this bill from you.",
+        ));
+    }
+    let mut client = state.inner().clone();
+    Ok(Json(publish_latest_block(&mut client, &id).await))
+}
+
+#[get("/bills/<id>/avals")]
+pub async fn get_bill_avals(id: String) -> ApiResult<Vec<Guarantee>> {
+    require_bill(&id)?;
+    let chain = Chain::read_chain_from_file(&id);
+    Ok(Json(chain.get_avals()))
+}
+
+/// Sets the passphrase the key files are encrypted with, or changes it.
+#[put("/keystore/passphrase", format = "json", data = "<passphrase_form>")]
+pub async fn change_passphrase(
+    passphrase_form: Json<PassphraseForm>,
+) -> Result<Status, (Status, Json<ApiError>)> {
+    match keystore::set_passphrase(
+        &passphrase_form.current_passphrase,
+        &passphrase_form.new_passphrase,
+    ) {
+        Ok(()) => Ok(Status::NoContent),
+        Err(error @ KeystoreError::WrongPassphrase) => {
+            Err(api_error(Status::Forbidden, &error.to_string()))
+        }
+        Err(error @ KeystoreError::EmptyPassphrase) => {
+            Err(api_error(Status::UnprocessableEntity, &error.to_string()))
+        }
+        Err(error) => Err(api_error(Status::InternalServerError, &error.to_string())),
+    }
+}
+//--------------------------------------------------------------
+
+#[catch(default)]
+pub fn default_catcher(status: Status, req: &Request) -> (Status, Json<ApiError>) {
+    let message = match status.code {
+        404 => format!("We couldn't find the requested path '{}'", req.uri()),
+        _ => status.reason_lossy().to_string(),
+    };
+    api_error(status, &message)
+}